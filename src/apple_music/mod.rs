@@ -0,0 +1,495 @@
+use std::{env, time::Duration};
+
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::error::{Error, Result};
+use crate::events::{own_items, quiet, report_match, searched, Event, SharedReporter};
+use crate::matching::confidence;
+use crate::playlist_file::parse_location;
+use crate::registry::{Capabilities, Provider, ProviderConfig};
+use crate::types::{
+    AppleMusicPage, AppleMusicSearch, AppleMusicSong, ItemMatch, MusicClient, ParsedPlaylistItems,
+    PlaylistItem, PlaylistItemId, PlaylistItemKind, PlaylistOptions, PlaylistSink, PlaylistSource,
+    TrackSearcher,
+};
+
+const API_URL: &str = "https://api.music.apple.com";
+const STOREFRONT: &str = "us";
+
+/// Reads catalog and library playlists from Apple Music, searches its catalog
+/// and creates library playlists. Everything needs a developer token; the
+/// library additionally needs the Music User Token of its owner.
+pub struct AppleMusic {
+    http: reqwest::Client,
+    api_url: String,
+    developer_token: Option<String>,
+    user_token: Option<String>,
+    storefront: String,
+    reporter: SharedReporter,
+}
+
+impl AppleMusic {
+    /// Takes the tokens from `APPLE_MUSIC_DEVELOPER_TOKEN` and
+    /// `APPLE_MUSIC_USER_TOKEN`, and the storefront searched in from
+    /// `APPLE_MUSIC_STOREFRONT` ("us" by default).
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: API_URL.into(),
+            developer_token: env::var("APPLE_MUSIC_DEVELOPER_TOKEN").ok(),
+            user_token: env::var("APPLE_MUSIC_USER_TOKEN").ok(),
+            storefront: env::var("APPLE_MUSIC_STOREFRONT").unwrap_or(STOREFRONT.into()),
+            reporter: quiet(),
+        }
+    }
+
+    pub fn with_tokens(developer_token: String, user_token: Option<String>) -> Self {
+        Self {
+            developer_token: Some(developer_token),
+            user_token,
+            ..Self::new()
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+
+    /// The two-letter country code of the catalog to search, e.g. "gb".
+    pub fn set_storefront(&mut self, storefront: String) {
+        self.storefront = storefront;
+    }
+
+    /// Sends the requests to another server than Apple's, e.g. a local
+    /// stand-in of the API.
+    pub fn set_base_url(&mut self, api_url: String) {
+        self.api_url = api_url;
+    }
+
+    fn from_config(config: &ProviderConfig) -> AppleMusic {
+        let mut apple_music = AppleMusic::new();
+        apple_music.set_reporter(config.reporter.clone());
+
+        apple_music
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder> {
+        let developer_token = self
+            .developer_token
+            .as_deref()
+            .ok_or(Error::MissingCredentials("Apple Music"))?;
+
+        let mut request = self
+            .http
+            .request(method, format!("{}{}", self.api_url, path))
+            .bearer_auth(developer_token);
+
+        if let Some(user_token) = &self.user_token {
+            request = request.header("Music-User-Token", user_token);
+        }
+
+        Ok(request)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T> {
+        Ok(self
+            .request(Method::GET, path)?
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    fn user_token(&self) -> Result<&str> {
+        self.user_token
+            .as_deref()
+            .ok_or(Error::MissingCredentials("Apple Music user"))
+    }
+
+    async fn get_songs(&self, path: String) -> Result<Vec<AppleMusicSong>> {
+        let mut songs = vec![];
+        let mut next = Some(path);
+
+        while let Some(path) = next {
+            let page: AppleMusicPage<AppleMusicSong> = self.get(&path, &[]).await?;
+
+            songs.extend(page.data);
+            // The next page is given as a path relative to the API.
+            next = page.next;
+        }
+
+        Ok(songs)
+    }
+
+    async fn search_song(&self, playlist_item: &PlaylistItem) -> Result<Option<AppleMusicSong>> {
+        if let Some(isrc) = &playlist_item.isrc {
+            let page: AppleMusicPage<AppleMusicSong> = self
+                .get(
+                    &format!("/v1/catalog/{}/songs", self.storefront),
+                    &[("filter[isrc]", isrc)],
+                )
+                .await?;
+
+            if let Some(song) = page.data.into_iter().next() {
+                return Ok(Some(song));
+            }
+        }
+
+        // The catalog search has no field-scoped syntax, so the artist and
+        // title are searched as plain terms.
+        let term = format!(
+            "{} {}",
+            playlist_item.credited_artists(),
+            playlist_item.credited_name()
+        );
+
+        let handle = playlist_item.credited_handle();
+        match self.search_songs(&term).await? {
+            Some(song) => Ok(Some(song)),
+            None if term != handle => self.search_songs(&handle).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn search_songs(&self, term: &str) -> Result<Option<AppleMusicSong>> {
+        let search: AppleMusicSearch = self
+            .get(
+                &format!("/v1/catalog/{}/search", self.storefront),
+                &[("term", term), ("types", "songs"), ("limit", "1")],
+            )
+            .await?;
+
+        Ok(search
+            .results
+            .songs
+            .and_then(|songs| songs.data.into_iter().next()))
+    }
+}
+
+impl Default for AppleMusic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct AppleMusicProvider;
+
+impl Provider for AppleMusicProvider {
+    fn name(&self) -> &'static str {
+        "apple-music"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Apple Music"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            search: true,
+            write: true,
+            ..Default::default()
+        }
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        location.contains("music.apple.com")
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(Some(Box::new(AppleMusic::from_config(config))))
+    }
+
+    fn searcher(&self, config: &ProviderConfig) -> Result<Option<Box<dyn TrackSearcher>>> {
+        Ok(Some(Box::new(AppleMusic::from_config(config))))
+    }
+
+    fn sink(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSink>>> {
+        Ok(Some(Box::new(AppleMusic::from_config(config))))
+    }
+}
+
+#[async_trait]
+impl MusicClient for AppleMusic {
+    async fn authenticate(&mut self) -> Result<()> {
+        // The tokens are issued outside of linksen, there is nothing to
+        // request, only to check.
+        match self.developer_token {
+            Some(_) => Ok(()),
+            None => Err(Error::MissingCredentials("Apple Music")),
+        }
+    }
+}
+
+#[async_trait]
+impl PlaylistSource for AppleMusic {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::LoadingStarted);
+
+        let path = match extract_resource(url).ok_or(Error::InvalidUrl(url.to_string()))? {
+            AppleMusicResource::CatalogPlaylist { storefront, id } => format!(
+                "/v1/catalog/{}/playlists/{}/tracks?limit=100",
+                storefront.unwrap_or(self.storefront.clone()),
+                id
+            ),
+            AppleMusicResource::LibraryPlaylist(id) => {
+                self.user_token()?;
+
+                format!("/v1/me/library/playlists/{}/tracks?limit=100", id)
+            }
+        };
+
+        let playlist_items = self
+            .get_songs(path)
+            .await?
+            .iter()
+            .filter_map(song_playlist_item)
+            .collect::<Vec<_>>();
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+
+    async fn get_item(&self, url: &str) -> Result<PlaylistItem> {
+        let invalid_url = || Error::InvalidUrl(url.to_string());
+
+        match parse_location(url) {
+            PlaylistItemId::AppleMusic(id) => {
+                let path = format!("/v1/catalog/{}/songs/{}", self.storefront, id);
+                let page: AppleMusicPage<AppleMusicSong> = self.get(&path, &[]).await?;
+
+                page.data
+                    .first()
+                    .and_then(song_playlist_item)
+                    .ok_or_else(invalid_url)
+            }
+            _ => Err(invalid_url()),
+        }
+    }
+}
+
+#[async_trait]
+impl TrackSearcher for AppleMusic {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        self.reporter.report(&Event::TransformStarted {
+            total: playlist_items.len(),
+        });
+
+        let mut parsed_playlist_items = ParsedPlaylistItems::default();
+
+        for playlist_item in playlist_items.into_iter() {
+            self.reporter.report(&Event::ItemSearching {
+                item: playlist_item.clone(),
+            });
+
+            let item_match = match playlist_item.id {
+                PlaylistItemId::AppleMusic(_) => ItemMatch {
+                    target: Some(playlist_item.clone()),
+                    source: playlist_item,
+                    confidence: 1.0,
+                },
+                _ => {
+                    let target = searched(
+                        self.reporter.as_ref(),
+                        &playlist_item,
+                        self.search_song(&playlist_item).await,
+                    )
+                    .as_ref()
+                    .and_then(song_playlist_item);
+                    let confidence = target
+                        .as_ref()
+                        .map_or(0.0, |target| confidence(&playlist_item, target));
+
+                    ItemMatch {
+                        source: playlist_item,
+                        target,
+                        confidence,
+                    }
+                }
+            };
+
+            report_match(self.reporter.as_ref(), &item_match);
+            parsed_playlist_items.items.push(item_match);
+        }
+
+        self.reporter.report(&Event::TransformFinished);
+
+        Ok(parsed_playlist_items)
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<String>> {
+        Ok(self.search_songs(query).await?.map(|song| song.id))
+    }
+}
+
+#[async_trait]
+impl PlaylistSink for AppleMusic {
+    /// Library playlists are always private, so the privacy is ignored.
+    async fn create_playlist(
+        &self,
+        options: &PlaylistOptions,
+        playlist_items: &[PlaylistItem],
+    ) -> Result<String> {
+        self.user_token()?;
+
+        self.reporter.report(&Event::CreatingPlaylist {
+            name: options.name.clone(),
+        });
+
+        let mut attributes = json!({ "name": options.name });
+        if let Some(description) = &options.description {
+            attributes["description"] = json!(description);
+        }
+
+        let created: Value = self
+            .request(Method::POST, "/v1/me/library/playlists")?
+            .json(&json!({ "attributes": attributes }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let playlist_id = created["data"][0]["id"]
+            .as_str()
+            .ok_or_else(|| {
+                Error::UnexpectedResponse("created Apple Music playlist has no ID".into())
+            })?
+            .to_string();
+
+        self.add_items(&playlist_id, playlist_items).await?;
+
+        self.reporter.report(&Event::PlaylistCreated {
+            id: playlist_id.clone(),
+        });
+
+        Ok(playlist_id)
+    }
+
+    /// Apple Music lets tracks be added to library playlists, but never
+    /// removed from them.
+    async fn add_items(&self, playlist_id: &str, playlist_items: &[PlaylistItem]) -> Result<()> {
+        self.user_token()?;
+
+        let playlist_items = own_items(
+            self.reporter.as_ref(),
+            "Apple Music",
+            playlist_items,
+            |id| matches!(id, PlaylistItemId::AppleMusic(_)),
+        );
+
+        for chunk in playlist_items.chunks(100) {
+            let tracks = chunk
+                .iter()
+                .filter_map(|playlist_item| match &playlist_item.id {
+                    // Songs only in the library have "i." IDs.
+                    PlaylistItemId::AppleMusic(id) if id.starts_with("i.") => {
+                        Some(json!({ "id": id, "type": "library-songs" }))
+                    }
+                    PlaylistItemId::AppleMusic(id) => Some(json!({ "id": id, "type": "songs" })),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            let added = match self.request(
+                Method::POST,
+                &format!("/v1/me/library/playlists/{}/tracks", playlist_id),
+            ) {
+                Ok(request) => request
+                    .json(&json!({ "data": tracks }))
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(Error::from),
+                Err(error) => Err(error),
+            };
+
+            for playlist_item in chunk {
+                match &added {
+                    Ok(_) => self.reporter.report(&Event::ItemInserted {
+                        item: (*playlist_item).clone(),
+                    }),
+                    Err(error) => self.reporter.report(&Event::ItemFailed {
+                        item: (*playlist_item).clone(),
+                        error: format!("not added: {}", error),
+                    }),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+enum AppleMusicResource {
+    CatalogPlaylist {
+        storefront: Option<String>,
+        id: String,
+    },
+    LibraryPlaylist(String),
+}
+
+/// Accepts links to catalog playlists such as
+/// https://music.apple.com/us/playlist/<name>/pl.<id>, links to library
+/// playlists such as https://music.apple.com/library/playlist/p.<id>, and the
+/// bare IDs of both.
+fn extract_resource(url: &str) -> Option<AppleMusicResource> {
+    let url = url.trim();
+
+    let library = Regex::new(r"(?:^|/library/playlist/)(p\.[\w-]+)").unwrap();
+    if let Some(captures) = library.captures(url) {
+        return Some(AppleMusicResource::LibraryPlaylist(captures[1].to_string()));
+    }
+
+    let catalog =
+        Regex::new(r"(?:music\.apple\.com/([a-z]{2})/playlist/(?:[^/?#]+/)?)?(pl\.[\w-]+)")
+            .unwrap();
+    let captures = catalog.captures(url)?;
+
+    Some(AppleMusicResource::CatalogPlaylist {
+        storefront: captures
+            .get(1)
+            .map(|storefront| storefront.as_str().to_string()),
+        id: captures[2].to_string(),
+    })
+}
+
+fn song_playlist_item(song: &AppleMusicSong) -> Option<PlaylistItem> {
+    let attributes = song.attributes.as_ref()?;
+
+    // Library songs link to the catalog song they were added from.
+    let id = attributes
+        .play_params
+        .as_ref()
+        .and_then(|play_params| play_params.catalog_id.clone())
+        .unwrap_or(song.id.clone());
+
+    let name = attributes.name.clone();
+    let artists = attributes.artist_name.clone();
+    let handle = format!("{} - {}", name, artists);
+
+    Some(PlaylistItem {
+        id: PlaylistItemId::AppleMusic(id),
+        kind: PlaylistItemKind::Track,
+        name,
+        artists,
+        handle,
+        duration: attributes.duration_in_millis.map(Duration::from_millis),
+        album: attributes.album_name.clone(),
+        isrc: attributes.isrc.clone(),
+        mbid: None,
+        canonical: None,
+    })
+}
@@ -0,0 +1,377 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use colored::Colorize;
+use linksen::{
+    playlist_file::write_playlist, MatchCache, MusicBrainz, ParsedPlaylistItems,
+    PlaylistFileFormat, PlaylistOptions, PlaylistSink, PlaylistSource, Privacy, ProviderConfig,
+    Registry, SearchPreference, TrackSearcher,
+};
+use serde::Deserialize;
+
+use crate::output::{print_batch_report, BatchReport, JobRecord, JobStatus, OutputFormat, Report};
+use crate::target_searcher;
+
+/// The conversions to run, read from a TOML file:
+///
+/// ```toml
+/// [quota]
+/// youtube = 10000
+///
+/// [[job]]
+/// from = "https://open.spotify.com/playlist/<ID>"
+/// to = "youtube"
+/// name = "From Spotify"
+/// ```
+#[derive(Deserialize)]
+struct BatchFile {
+    /// Units of API quota each provider may spend in this run, keyed by
+    /// `Provider::quota_pool`.
+    #[serde(default)]
+    quota: HashMap<String, u64>,
+    #[serde(default, rename = "job")]
+    jobs: Vec<Job>,
+}
+
+/// A conversion, with the same options as `convert`.
+#[derive(Deserialize)]
+struct Job {
+    from: String,
+    to: String,
+    name: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    privacy: Privacy,
+    fallback: Option<String>,
+    prefer: Option<SearchPreference>,
+    #[serde(default)]
+    enrich: bool,
+    /// The target file's, or the source file's when the target is a provider.
+    format: Option<PlaylistFileFormat>,
+    from_format: Option<PlaylistFileFormat>,
+}
+
+/// What a job got to, whether it was written or not.
+struct Outcome {
+    mode: String,
+    status: JobStatus,
+    error: Option<String>,
+    matched: usize,
+    unmatched: usize,
+    playlist_id: Option<String>,
+    items: Option<ParsedPlaylistItems>,
+}
+
+/// The clients of every provider the jobs use, built and authenticated once
+/// for the whole run.
+#[derive(Default)]
+struct Clients {
+    sources: HashMap<String, Box<dyn PlaylistSource>>,
+    searchers: HashMap<String, Box<dyn TrackSearcher>>,
+    sinks: HashMap<String, Box<dyn PlaylistSink>>,
+}
+
+/// Runs every job of `path` in turn, then prints what each of them did. A job
+/// that fails does not stop the others.
+pub async fn batch(
+    path: &Path,
+    config: ProviderConfig,
+    cache: Arc<MatchCache>,
+    output: OutputFormat,
+    terminal: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut batch_file: BatchFile = toml::from_str(&fs::read_to_string(path)?)?;
+
+    let registry = Registry::new();
+    for job in &batch_file.jobs {
+        validate(&registry, job)?;
+    }
+    for pool in batch_file.quota.keys() {
+        validate_quota(&registry, pool)?;
+    }
+
+    let mut clients = Clients::default();
+    let mut outcomes = vec![];
+
+    for (index, job) in batch_file.jobs.iter().enumerate() {
+        if terminal {
+            eprintln!(
+                "{}",
+                format!(
+                    "Job {}/{}: {} -> {}",
+                    index + 1,
+                    batch_file.jobs.len(),
+                    job.from,
+                    job.to
+                )
+                .on_blue()
+                .black()
+            );
+        }
+
+        let outcome = run(
+            &registry,
+            &config,
+            &cache,
+            &mut clients,
+            &mut batch_file.quota,
+            job,
+        )
+        .await;
+
+        outcomes.push(outcome.unwrap_or_else(|error| Outcome {
+            mode: String::new(),
+            status: JobStatus::Failed,
+            error: Some(error.to_string()),
+            matched: 0,
+            unmatched: 0,
+            playlist_id: None,
+            items: None,
+        }));
+
+        if terminal {
+            eprintln!();
+        }
+    }
+
+    let jobs = batch_file
+        .jobs
+        .iter()
+        .zip(&outcomes)
+        .map(|(job, outcome)| JobRecord {
+            from: &job.from,
+            to: &job.to,
+            name: job.name.as_deref(),
+            status: outcome.status,
+            error: outcome.error.as_deref(),
+            matched: outcome.matched,
+            unmatched: outcome.unmatched,
+            playlist_id: outcome.playlist_id.as_deref(),
+            report: outcome.items.as_ref().map(|items| {
+                Report::new(
+                    &outcome.mode,
+                    &job.from,
+                    items,
+                    outcome.playlist_id.as_deref(),
+                )
+            }),
+        })
+        .collect();
+    print_batch_report(output, &BatchReport { jobs })?;
+
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.status == JobStatus::Failed)
+        .count();
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} jobs failed", failed, outcomes.len()).into()),
+    }
+}
+
+/// Providers that write through the same API share its quota, which is
+/// given under the name of the pool.
+fn validate_quota(registry: &Registry, pool: &str) -> Result<(), Box<dyn Error>> {
+    let provider = registry
+        .get(pool)
+        .ok_or_else(|| format!("quota for unknown provider {}", pool))?;
+
+    match provider.quota_pool() {
+        shared if shared != pool => Err(format!(
+            "{} shares its quota with {}, give it as {}",
+            provider.display_name(),
+            shared,
+            shared
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Checks every job before any of them runs, so that a typo does not show
+/// up half way through.
+fn validate(registry: &Registry, job: &Job) -> Result<(), Box<dyn Error>> {
+    let (source, _) = registry.resolve(&job.from)?;
+    if !source.capabilities().read {
+        return Err(format!("{} playlists cannot be read", source.display_name()).into());
+    }
+
+    if let Some(fallback) = &job.fallback {
+        registry
+            .get(fallback)
+            .ok_or_else(|| format!("unknown provider {}", fallback))?;
+    }
+
+    match registry.get(&job.to) {
+        Some(target) if !target.capabilities().search => {
+            Err(format!("{} cannot search for tracks", target.display_name()).into())
+        }
+        Some(target) if target.capabilities().write && job.name.is_none() => Err(format!(
+            "{} -> {}: the {} playlist needs a name",
+            job.from,
+            job.to,
+            target.display_name()
+        )
+        .into()),
+        Some(_) if job.format.is_some() && job.from_format.is_some() => Err(format!(
+            "{} -> {}: give the format of the file read once, as from_format",
+            job.from, job.to
+        )
+        .into()),
+        Some(_) => Ok(()),
+        None if job.fallback.is_some() => Err(format!(
+            "{} -> {}: a fallback only applies when the target is a provider",
+            job.from, job.to
+        )
+        .into()),
+        None if job.format.is_none()
+            && PlaylistFileFormat::from_path(Path::new(&job.to)).is_none() =>
+        {
+            Err(format!(
+                "{}: cannot guess the playlist file format from the file name, give a format",
+                job.to
+            )
+            .into())
+        }
+        None => Ok(()),
+    }
+}
+
+async fn run(
+    registry: &Registry,
+    config: &ProviderConfig,
+    cache: &Arc<MatchCache>,
+    clients: &mut Clients,
+    quota: &mut HashMap<String, u64>,
+    job: &Job,
+) -> Result<Outcome, Box<dyn Error>> {
+    let (source, location) = registry.resolve(&job.from)?;
+    let target = registry.get(&job.to);
+
+    let config = ProviderConfig {
+        // `format` describes the target when writing to a file.
+        playlist_file_format: job.from_format.or(target.and(job.format)),
+        search_preference: job.prefer,
+        ..config.clone()
+    };
+
+    let source_key = format!("{}:{:?}", source.name(), config.playlist_file_format);
+    if !clients.sources.contains_key(&source_key) {
+        let mut client = source
+            .source(&config)?
+            .ok_or_else(|| format!("{} playlists cannot be read", source.display_name()))?;
+        client.authenticate().await?;
+        clients.sources.insert(source_key.clone(), client);
+    }
+
+    let mut playlist_items = clients.sources[&source_key]
+        .get_playlist_items(location)
+        .await?;
+
+    if job.enrich {
+        let mut musicbrainz = MusicBrainz::new();
+        musicbrainz.set_reporter(config.reporter.clone());
+        playlist_items = musicbrainz.enrich(playlist_items).await?;
+    }
+
+    let Some(target) = target else {
+        let format = job
+            .format
+            .or_else(|| PlaylistFileFormat::from_path(Path::new(&job.to)))
+            .ok_or("cannot guess the playlist file format from the file name")?;
+
+        let mut file = BufWriter::new(File::create(&job.to)?);
+        write_playlist(format, &playlist_items, &mut file)?;
+        file.flush()?;
+
+        return Ok(Outcome {
+            mode: format!("{} -> file", source.display_name()),
+            status: JobStatus::Done,
+            error: None,
+            matched: playlist_items.len(),
+            unmatched: 0,
+            playlist_id: None,
+            items: None,
+        });
+    };
+
+    let searcher_key = format!("{}:{:?}:{:?}", target.name(), job.fallback, job.prefer);
+    if !clients.searchers.contains_key(&searcher_key) {
+        let fallback = job.fallback.as_deref().and_then(|name| registry.get(name));
+        let searcher = target_searcher(target, fallback, &config, cache)?;
+        clients.searchers.insert(searcher_key.clone(), searcher);
+    }
+
+    let playlist_items = clients.searchers[&searcher_key]
+        .parse_playlist_items(playlist_items)
+        .await?;
+    let matched = playlist_items.matched();
+
+    let mut outcome = Outcome {
+        mode: format!("{} -> {}", source.display_name(), target.display_name()),
+        status: JobStatus::Done,
+        error: None,
+        matched: matched.len(),
+        unmatched: playlist_items.items.len() - matched.len(),
+        playlist_id: None,
+        items: None,
+    };
+
+    if target.capabilities().write {
+        // Jobs that do not fit in what is left of the quota are skipped, but
+        // smaller ones after them may still fit.
+        // Matches a fallback found on another provider are not added.
+        let added = matched
+            .iter()
+            .filter(|playlist_item| target.owns_item(&playlist_item.id))
+            .count();
+        let cost = target.write_cost(added);
+        let left = quota.get(target.quota_pool()).copied();
+
+        match left {
+            Some(left) if cost > left => {
+                outcome.status = JobStatus::Skipped;
+                outcome.error = Some(format!(
+                    "needs {} {} quota units, {} left",
+                    cost,
+                    target.display_name(),
+                    left
+                ));
+            }
+            _ => {
+                if !clients.sinks.contains_key(target.name()) {
+                    let mut sink = target.sink(&config)?.ok_or_else(|| {
+                        format!("{} playlists cannot be written", target.display_name())
+                    })?;
+                    sink.authenticate().await?;
+                    clients.sinks.insert(target.name().to_string(), sink);
+                }
+
+                let options = PlaylistOptions {
+                    name: job.name.clone().unwrap_or_default(),
+                    description: job.description.clone(),
+                    privacy: job.privacy,
+                };
+                outcome.playlist_id = Some(
+                    clients.sinks[target.name()]
+                        .create_playlist(&options, &matched)
+                        .await?,
+                );
+
+                if let Some(left) = left {
+                    quota.insert(target.quota_pool().to_string(), left - cost);
+                }
+            }
+        }
+    }
+
+    outcome.items = Some(playlist_items);
+
+    Ok(outcome)
+}
@@ -0,0 +1,271 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::oauth::data_dir;
+use crate::types::{
+    ItemMatch, MusicClient, ParsedPlaylistItems, PlaylistItem, PlaylistItemId, SearchPreference,
+    TrackSearcher,
+};
+
+const CACHE_FILE: &str = "matches.json";
+
+/// An item matched on a provider, as remembered by a [`MatchCache`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedMatch {
+    /// Name of the provider the item was matched on, e.g. "spotify".
+    pub provider: String,
+    /// What else the search depended on, e.g. "prefer=songs" or "enriched",
+    /// comma separated. Empty for plain searches.
+    #[serde(default)]
+    pub variant: String,
+    pub source: PlaylistItemId,
+    pub target: PlaylistItem,
+    pub confidence: f32,
+}
+
+/// Remembers what items were matched with on each provider, so that
+/// converting them again does not search for them again. Items that were not
+/// found are not remembered, as the provider may have them later, and
+/// neither are items of playlist files and local libraries, whose IDs do not
+/// identify a recording.
+pub struct MatchCache {
+    path: Option<PathBuf>,
+    matches: Mutex<HashMap<MatchKey, CachedMatch>>,
+}
+
+/// Provider, variant and source of a match.
+type MatchKey = (String, String, PlaylistItemId);
+
+impl MatchCache {
+    /// A cache that is lost when dropped.
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            matches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cache kept in linksen's data directory, next to the tokens.
+    pub fn open() -> Result<Self> {
+        Self::load(&data_dir()?.join(CACHE_FILE))
+    }
+
+    /// Reads the cache kept at `path`, which does not have to exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        let cached: Vec<CachedMatch> = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            matches: Mutex::new(
+                cached
+                    .into_iter()
+                    .map(|cached| {
+                        let key = (
+                            cached.provider.clone(),
+                            cached.variant.clone(),
+                            cached.source.clone(),
+                        );
+
+                        (key, cached)
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    pub fn get(
+        &self,
+        provider: &str,
+        variant: &str,
+        source: &PlaylistItemId,
+    ) -> Option<CachedMatch> {
+        if !is_cacheable(source) {
+            return None;
+        }
+
+        self.matches
+            .lock()
+            .unwrap()
+            .get(&(provider.to_string(), variant.to_string(), source.clone()))
+            .cloned()
+    }
+
+    /// Remembers the match, if the item was found and can be recognised
+    /// later.
+    pub fn insert(&self, provider: &str, variant: &str, item_match: &ItemMatch) {
+        let Some(target) = &item_match.target else {
+            return;
+        };
+        if !is_cacheable(&item_match.source.id) {
+            return;
+        }
+
+        self.matches.lock().unwrap().insert(
+            (
+                provider.to_string(),
+                variant.to_string(),
+                item_match.source.id.clone(),
+            ),
+            CachedMatch {
+                provider: provider.to_string(),
+                variant: variant.to_string(),
+                source: item_match.source.id.clone(),
+                target: target.clone(),
+                confidence: item_match.confidence,
+            },
+        );
+    }
+
+    /// Every remembered match, or those of a single provider.
+    pub fn matches(&self, provider: Option<&str>) -> Vec<CachedMatch> {
+        let mut matches = self
+            .matches
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|cached| provider.is_none_or(|provider| cached.provider == provider))
+            .cloned()
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| {
+            (&a.provider, a.source.to_string(), &a.variant).cmp(&(
+                &b.provider,
+                b.source.to_string(),
+                &b.variant,
+            ))
+        });
+
+        matches
+    }
+
+    /// Writes the cache back to its file, if it has one.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let json = serde_json::to_string(&self.matches(None))?;
+        fs::write(path, json)?;
+
+        Ok(())
+    }
+}
+
+impl Default for MatchCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Looks items up in a [`MatchCache`] before searching for them with
+/// `searcher`, and remembers what the search found.
+pub struct CachedSearcher {
+    searcher: Box<dyn TrackSearcher>,
+    provider: String,
+    preference: Option<SearchPreference>,
+    cache: Arc<MatchCache>,
+}
+
+impl CachedSearcher {
+    pub fn new(searcher: Box<dyn TrackSearcher>, provider: &str, cache: Arc<MatchCache>) -> Self {
+        Self {
+            searcher,
+            provider: provider.to_string(),
+            preference: None,
+            cache,
+        }
+    }
+
+    /// The search preference `searcher` was built with, so that matches found
+    /// with another one are not taken for its own.
+    pub fn set_preference(&mut self, preference: Option<SearchPreference>) {
+        self.preference = preference;
+    }
+
+    /// Items enriched by MusicBrainz are searched for by other names, so
+    /// their matches are kept apart from those of plain items.
+    fn variant(&self, playlist_item: &PlaylistItem) -> String {
+        let mut variant = vec![];
+        if let Some(preference) = self.preference {
+            variant.push(format!("prefer={}", preference));
+        }
+        if playlist_item.canonical.is_some() {
+            variant.push("enriched".to_string());
+        }
+
+        variant.join(",")
+    }
+}
+
+#[async_trait]
+impl MusicClient for CachedSearcher {
+    async fn authenticate(&mut self) -> Result<()> {
+        self.searcher.authenticate().await
+    }
+}
+
+#[async_trait]
+impl TrackSearcher for CachedSearcher {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        let mut items = Vec::with_capacity(playlist_items.len());
+        let mut positions = vec![];
+        let mut uncached = vec![];
+
+        for playlist_item in playlist_items {
+            let variant = self.variant(&playlist_item);
+
+            match self.cache.get(&self.provider, &variant, &playlist_item.id) {
+                Some(cached) => items.push(Some(ItemMatch {
+                    source: playlist_item,
+                    target: Some(cached.target),
+                    confidence: cached.confidence,
+                })),
+                None => {
+                    positions.push(items.len());
+                    uncached.push(playlist_item);
+                    items.push(None);
+                }
+            }
+        }
+
+        if !uncached.is_empty() {
+            let searched = self.searcher.parse_playlist_items(uncached).await?;
+
+            for (position, item_match) in positions.into_iter().zip(searched.items) {
+                let variant = self.variant(&item_match.source);
+                self.cache.insert(&self.provider, &variant, &item_match);
+                items[position] = Some(item_match);
+            }
+
+            self.cache.save()?;
+        }
+
+        Ok(ParsedPlaylistItems {
+            items: items.into_iter().flatten().collect(),
+        })
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<String>> {
+        self.searcher.search(query).await
+    }
+}
+
+/// Rows of playlist files without a location all have the same empty local
+/// ID, and paths may name other files later, so local IDs are never cached.
+fn is_cacheable(source: &PlaylistItemId) -> bool {
+    !matches!(source, PlaylistItemId::Local(_))
+}
@@ -0,0 +1,653 @@
+use std::{env, time::Duration};
+
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::events::{own_items, quiet, report_match, searched, Event, SharedReporter};
+use crate::matching::confidence;
+use crate::normalize::spotify_query;
+use crate::oauth::{read_cached_token, wait_for_redirect, write_cached_token};
+use crate::playlist_file::parse_location;
+use crate::registry::{Capabilities, Provider, ProviderConfig};
+use crate::types::{
+    DeezerAccessToken, DeezerAlbum, DeezerCreated, DeezerPage, DeezerPlaylist, DeezerTrack,
+    DeezerUser, ItemMatch, MusicClient, ParsedPlaylistItems, PlaylistItem, PlaylistItemId,
+    PlaylistItemKind, PlaylistOptions, PlaylistSink, PlaylistSource, PlaylistSummary, Privacy,
+    TrackSearcher,
+};
+
+const API_URL: &str = "https://api.deezer.com";
+const CONNECT_URL: &str = "https://connect.deezer.com";
+const REDIRECT_URI: &str = "http://localhost:8888/deezer";
+const TOKEN_CACHE: &str = "deezer.token";
+/// The error code of lookups that found nothing.
+const DATA_NOT_FOUND: u64 = 800;
+
+/// Reads public Deezer playlists and albums, matches items against the
+/// Deezer catalogue and creates playlists for the authorized user.
+pub struct Deezer {
+    http: reqwest::Client,
+    api_url: String,
+    connect_url: String,
+    app_id: Option<String>,
+    app_secret: Option<String>,
+    redirect_uri: String,
+    access_token: Option<String>,
+    reporter: SharedReporter,
+}
+
+impl Deezer {
+    /// Takes the app credentials from `DEEZER_APP_ID` and `DEEZER_APP_SECRET`,
+    /// and an already issued token from `DEEZER_ACCESS_TOKEN`. None of them
+    /// are needed to read public playlists or to search.
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: API_URL.into(),
+            connect_url: CONNECT_URL.into(),
+            app_id: env::var("DEEZER_APP_ID").ok(),
+            app_secret: env::var("DEEZER_APP_SECRET").ok(),
+            redirect_uri: env::var("DEEZER_REDIRECT_URI").unwrap_or(REDIRECT_URI.into()),
+            access_token: env::var("DEEZER_ACCESS_TOKEN").ok(),
+            reporter: quiet(),
+        }
+    }
+
+    pub fn with_credentials(app_id: String, app_secret: String) -> Self {
+        Self {
+            app_id: Some(app_id),
+            app_secret: Some(app_secret),
+            ..Self::new()
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+
+    pub fn set_access_token(&mut self, access_token: String) {
+        self.access_token = Some(access_token);
+    }
+
+    /// The redirect URI registered for the Deezer app, on which linksen
+    /// listens during authorization.
+    pub fn set_redirect_uri(&mut self, redirect_uri: String) {
+        self.redirect_uri = redirect_uri;
+    }
+
+    /// Sends the requests to other servers than Deezer's, e.g. a local
+    /// stand-in of the API.
+    pub fn set_base_urls(&mut self, api_url: String, connect_url: String) {
+        self.api_url = api_url;
+        self.connect_url = connect_url;
+    }
+
+    fn from_config(config: &ProviderConfig) -> Deezer {
+        let mut deezer = Deezer::new();
+        deezer.set_reporter(config.reporter.clone());
+
+        deezer
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T> {
+        deezer_result(self.get_value(url, query).await?)
+    }
+
+    /// The response as it comes, errors in the body included.
+    async fn get_value(&self, url: &str, query: &[(&str, &str)]) -> Result<Value> {
+        Ok(self
+            .http
+            .get(url)
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?)
+    }
+
+    async fn post<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T> {
+        let response = self
+            .http
+            .post(url)
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        deezer_result(response)
+    }
+
+    async fn get_tracks(&self, url: String) -> Result<Vec<DeezerTrack>> {
+        let mut tracks = vec![];
+        let mut next = Some(url);
+
+        while let Some(url) = next {
+            let page: DeezerPage<DeezerTrack> = self.get(&url, &[]).await?;
+
+            tracks.extend(page.data);
+            next = page.next;
+        }
+
+        Ok(tracks)
+    }
+
+    async fn search_track(&self, playlist_item: &PlaylistItem) -> Result<Option<DeezerTrack>> {
+        if let Some(isrc) = &playlist_item.isrc {
+            let url = format!("{}/track/isrc:{}", self.api_url, isrc);
+
+            // Deezer answers with an error when no track has the ISRC.
+            let response = self.get_value(&url, &[]).await?;
+            if !is_no_data(&response) {
+                return Ok(Some(deezer_result(response)?));
+            }
+        }
+
+        // Deezer understands the same field-scoped syntax as Spotify.
+        let artist = playlist_item
+            .credited_artists()
+            .split(", ")
+            .next()
+            .unwrap_or_default();
+        let query = spotify_query(artist, playlist_item.credited_name());
+
+        match self.search_tracks(&query).await? {
+            Some(track) => Ok(Some(track)),
+            None => self.search_tracks(&playlist_item.credited_handle()).await,
+        }
+    }
+
+    async fn search_tracks(&self, query: &str) -> Result<Option<DeezerTrack>> {
+        let url = format!("{}/search/track", self.api_url);
+        let page: DeezerPage<DeezerTrack> = self.get(&url, &[("q", query), ("limit", "1")]).await?;
+
+        Ok(page.data.into_iter().next())
+    }
+}
+
+impl Default for Deezer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct DeezerProvider;
+
+impl Provider for DeezerProvider {
+    fn name(&self) -> &'static str {
+        "deezer"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Deezer"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            search: true,
+            write: true,
+            delete: true,
+            ..Default::default()
+        }
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        location.contains("deezer.com")
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(Some(Box::new(Deezer::from_config(config))))
+    }
+
+    fn searcher(&self, config: &ProviderConfig) -> Result<Option<Box<dyn TrackSearcher>>> {
+        Ok(Some(Box::new(Deezer::from_config(config))))
+    }
+
+    fn sink(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSink>>> {
+        Ok(Some(Box::new(Deezer::from_config(config))))
+    }
+
+    fn playlist_location(&self, playlist_id: &str) -> String {
+        format!("playlist:{}", playlist_id)
+    }
+}
+
+#[async_trait]
+impl MusicClient for Deezer {
+    async fn authenticate(&mut self) -> Result<()> {
+        if self.access_token.is_some() {
+            return Ok(());
+        }
+
+        if let Some(access_token) = read_cached_token(TOKEN_CACHE) {
+            self.access_token = Some(access_token);
+            return Ok(());
+        }
+
+        // Without an app there is nobody to authorize, but public playlists
+        // can still be read.
+        let (app_id, app_secret) = match (&self.app_id, &self.app_secret) {
+            (Some(app_id), Some(app_secret)) => (app_id.clone(), app_secret.clone()),
+            _ => return Ok(()),
+        };
+
+        let authorization_url = Url::parse_with_params(
+            &format!("{}/oauth/auth.php", self.connect_url),
+            &[
+                ("app_id", app_id.as_str()),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("perms", "basic_access,manage_library,offline_access"),
+            ],
+        )
+        .map_err(|_| Error::InvalidUrl(self.connect_url.clone()))?;
+
+        self.reporter.report(&Event::AuthorizationRequired {
+            provider: "Deezer".into(),
+            url: authorization_url.to_string(),
+        });
+
+        let parameters = wait_for_redirect(&self.redirect_uri).await?;
+        let code = parameters.get("code").ok_or_else(|| {
+            Error::UnexpectedResponse(format!(
+                "Deezer authorization failed: {}",
+                parameters
+                    .get("error_reason")
+                    .map_or("no code", |reason| reason.as_str())
+            ))
+        })?;
+
+        let token: DeezerAccessToken = self
+            .get(
+                &format!("{}/oauth/access_token.php", self.connect_url),
+                &[
+                    ("app_id", app_id.as_str()),
+                    ("secret", app_secret.as_str()),
+                    ("code", code.as_str()),
+                    ("output", "json"),
+                ],
+            )
+            .await?;
+
+        write_cached_token(TOKEN_CACHE, &token.access_token)?;
+        self.access_token = Some(token.access_token);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PlaylistSource for Deezer {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::LoadingStarted);
+
+        let tracks = match extract_resource(url).ok_or(Error::InvalidUrl(url.to_string()))? {
+            DeezerResource::Playlist(id) => {
+                self.get_tracks(format!("{}/playlist/{}/tracks?limit=100", self.api_url, id))
+                    .await?
+            }
+            DeezerResource::Album(id) => {
+                let album: DeezerAlbum = self
+                    .get(&format!("{}/album/{}", self.api_url, id), &[])
+                    .await?;
+
+                // Album tracks do not repeat the album they are on.
+                let mut tracks = self
+                    .get_tracks(format!("{}/album/{}/tracks?limit=100", self.api_url, id))
+                    .await?;
+                for track in &mut tracks {
+                    track.album.get_or_insert_with(|| DeezerAlbum {
+                        title: album.title.clone(),
+                    });
+                }
+
+                tracks
+            }
+        };
+
+        let playlist_items = tracks.iter().map(track_playlist_item).collect::<Vec<_>>();
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+
+    async fn get_item(&self, url: &str) -> Result<PlaylistItem> {
+        match parse_location(url) {
+            PlaylistItemId::Deezer(id) => {
+                let url = format!("{}/track/{}", self.api_url, id);
+
+                Ok(track_playlist_item(&self.get(&url, &[]).await?))
+            }
+            _ => Err(Error::InvalidUrl(url.to_string())),
+        }
+    }
+
+    /// Private playlists are only listed for the authorized user. Listings
+    /// leave out the description, see `get_description`.
+    async fn get_playlists(&self, user: Option<&str>) -> Result<Vec<PlaylistSummary>> {
+        let mut query = vec![("limit", "100")];
+        if let Some(access_token) = &self.access_token {
+            query.push(("access_token", access_token));
+        }
+
+        let user_id = match user {
+            Some(user) => {
+                extract_user_id(user).ok_or_else(|| Error::InvalidUrl(user.to_string()))?
+            }
+            None => {
+                if self.access_token.is_none() {
+                    return Err(Error::MissingCredentials("Deezer"));
+                }
+
+                let me: DeezerUser = self
+                    .get(&format!("{}/user/me", self.api_url), &query)
+                    .await?;
+
+                me.id
+            }
+        };
+
+        let mut playlists = vec![];
+        let mut next = Some(
+            Url::parse_with_params(
+                &format!("{}/user/{}/playlists", self.api_url, user_id),
+                &query,
+            )
+            .map_err(|_| Error::InvalidUrl(self.api_url.clone()))?
+            .to_string(),
+        );
+
+        // The next pages keep the query of the first one.
+        while let Some(url) = next {
+            let page: DeezerPage<DeezerPlaylist> = self.get(&url, &[]).await?;
+
+            for listed in page.data {
+                playlists.push(PlaylistSummary {
+                    url: format!("https://www.deezer.com/playlist/{}", listed.id),
+                    name: listed.title,
+                    description: None,
+                    owned: listed.creator.is_some_and(|creator| creator.id == user_id),
+                    item_count: listed.nb_tracks,
+                });
+            }
+
+            next = page.next;
+        }
+
+        Ok(playlists)
+    }
+
+    async fn get_description(&self, url: &str) -> Result<Option<String>> {
+        let id = match extract_resource(url).ok_or(Error::InvalidUrl(url.to_string()))? {
+            DeezerResource::Playlist(id) => id,
+            DeezerResource::Album(_) => return Ok(None),
+        };
+
+        let mut query = vec![];
+        if let Some(access_token) = &self.access_token {
+            query.push(("access_token", access_token.as_str()));
+        }
+
+        let playlist: DeezerPlaylist = self
+            .get(&format!("{}/playlist/{}", self.api_url, id), &query)
+            .await?;
+
+        Ok(playlist
+            .description
+            .filter(|description| !description.is_empty()))
+    }
+}
+
+#[async_trait]
+impl TrackSearcher for Deezer {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        self.reporter.report(&Event::TransformStarted {
+            total: playlist_items.len(),
+        });
+
+        let mut parsed_playlist_items = ParsedPlaylistItems::default();
+
+        for playlist_item in playlist_items.into_iter() {
+            self.reporter.report(&Event::ItemSearching {
+                item: playlist_item.clone(),
+            });
+
+            let item_match = match playlist_item.id {
+                PlaylistItemId::Deezer(_) => ItemMatch {
+                    target: Some(playlist_item.clone()),
+                    source: playlist_item,
+                    confidence: 1.0,
+                },
+                _ => {
+                    let target = searched(
+                        self.reporter.as_ref(),
+                        &playlist_item,
+                        self.search_track(&playlist_item).await,
+                    )
+                    .as_ref()
+                    .map(track_playlist_item);
+                    let confidence = target
+                        .as_ref()
+                        .map_or(0.0, |target| confidence(&playlist_item, target));
+
+                    ItemMatch {
+                        source: playlist_item,
+                        target,
+                        confidence,
+                    }
+                }
+            };
+
+            report_match(self.reporter.as_ref(), &item_match);
+            parsed_playlist_items.items.push(item_match);
+        }
+
+        self.reporter.report(&Event::TransformFinished);
+
+        Ok(parsed_playlist_items)
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<String>> {
+        Ok(self
+            .search_tracks(query)
+            .await?
+            .map(|track| track.id.to_string()))
+    }
+}
+
+#[async_trait]
+impl PlaylistSink for Deezer {
+    async fn create_playlist(
+        &self,
+        options: &PlaylistOptions,
+        playlist_items: &[PlaylistItem],
+    ) -> Result<String> {
+        let access_token = self
+            .access_token
+            .as_deref()
+            .ok_or(Error::MissingCredentials("Deezer"))?;
+
+        self.reporter.report(&Event::CreatingPlaylist {
+            name: options.name.clone(),
+        });
+
+        let created: DeezerCreated = self
+            .post(
+                &format!("{}/user/me/playlists", self.api_url),
+                &[("access_token", access_token), ("title", &options.name)],
+            )
+            .await?;
+        let playlist_id = created.id.to_string();
+        let playlist_url = format!("{}/playlist/{}", self.api_url, playlist_id);
+
+        // Deezer has no unlisted playlists, so those stay private.
+        let public = (options.privacy == Privacy::Public).to_string();
+        let mut settings = vec![("access_token", access_token), ("public", &public)];
+        if let Some(description) = &options.description {
+            settings.push(("description", description));
+        }
+        let _: bool = self.post(&playlist_url, &settings).await?;
+
+        self.add_items(&playlist_id, playlist_items).await?;
+
+        self.reporter.report(&Event::PlaylistCreated {
+            id: playlist_id.clone(),
+        });
+
+        Ok(playlist_id)
+    }
+
+    async fn add_items(&self, playlist_id: &str, playlist_items: &[PlaylistItem]) -> Result<()> {
+        let access_token = self
+            .access_token
+            .as_deref()
+            .ok_or(Error::MissingCredentials("Deezer"))?;
+
+        let playlist_items = own_items(self.reporter.as_ref(), "Deezer", playlist_items, |id| {
+            matches!(id, PlaylistItemId::Deezer(_))
+        });
+
+        for chunk in playlist_items.chunks(50) {
+            let added: Result<bool> = self
+                .post(
+                    &format!("{}/playlist/{}/tracks", self.api_url, playlist_id),
+                    &[("access_token", access_token), ("songs", &song_ids(chunk))],
+                )
+                .await;
+
+            for playlist_item in chunk {
+                match &added {
+                    Ok(_) => self.reporter.report(&Event::ItemInserted {
+                        item: (*playlist_item).clone(),
+                    }),
+                    Err(error) => self.reporter.report(&Event::ItemFailed {
+                        item: (*playlist_item).clone(),
+                        error: format!("not added: {}", error),
+                    }),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn remove_items(&self, playlist_id: &str, playlist_items: &[PlaylistItem]) -> Result<()> {
+        let access_token = self
+            .access_token
+            .as_deref()
+            .ok_or(Error::MissingCredentials("Deezer"))?;
+
+        let playlist_items = playlist_items
+            .iter()
+            .filter(|playlist_item| matches!(playlist_item.id, PlaylistItemId::Deezer(_)))
+            .collect::<Vec<_>>();
+
+        for chunk in playlist_items.chunks(50) {
+            let response = self
+                .http
+                .delete(format!("{}/playlist/{}/tracks", self.api_url, playlist_id))
+                .query(&[("access_token", access_token), ("songs", &song_ids(chunk))])
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Value>()
+                .await?;
+            let _: bool = deezer_result(response)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The comma separated IDs Deezer takes to add or remove tracks.
+fn song_ids(playlist_items: &[&PlaylistItem]) -> String {
+    playlist_items
+        .iter()
+        .filter_map(|playlist_item| match &playlist_item.id {
+            PlaylistItemId::Deezer(id) => Some(id.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+enum DeezerResource {
+    Playlist(String),
+    Album(String),
+}
+
+/// Accepts links such as https://www.deezer.com/en/playlist/<id> as well as
+/// the bare "playlist:<id>" and "album:<id>" forms.
+fn extract_resource(url: &str) -> Option<DeezerResource> {
+    let resource = Regex::new(r"(?:^|/)(playlist|album)[/:](\d+)").unwrap();
+    let captures = resource.captures(url.trim())?;
+    let id = captures[2].to_string();
+
+    match &captures[1] {
+        "playlist" => Some(DeezerResource::Playlist(id)),
+        _ => Some(DeezerResource::Album(id)),
+    }
+}
+
+/// Accepts profile links such as https://www.deezer.com/en/profile/<id> as
+/// well as the bare user ID.
+fn extract_user_id(user: &str) -> Option<u64> {
+    let profile = Regex::new(r"(?:^|/)(?:profile|user)[/:](\d+)|^(\d+)$").unwrap();
+    let captures = profile.captures(user.trim())?;
+
+    captures.get(1).or(captures.get(2))?.as_str().parse().ok()
+}
+
+/// Whether the response is the error Deezer answers lookups of unknown
+/// resources with, rather than a failure.
+fn is_no_data(response: &Value) -> bool {
+    response
+        .pointer("/error/code")
+        .and_then(Value::as_u64)
+        .is_some_and(|code| code == DATA_NOT_FOUND)
+}
+
+/// Deezer reports most errors in the body of a successful response.
+fn deezer_result<T: DeserializeOwned>(response: Value) -> Result<T> {
+    if let Some(error) = response.get("error") {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error");
+
+        return Err(Error::UnexpectedResponse(format!("Deezer: {}", message)));
+    }
+
+    Ok(serde_json::from_value(response)?)
+}
+
+fn track_playlist_item(track: &DeezerTrack) -> PlaylistItem {
+    let name = track.title.clone();
+    let artists = track.artist.name.clone();
+    let handle = format!("{} - {}", name, artists);
+
+    PlaylistItem {
+        id: PlaylistItemId::Deezer(track.id.to_string()),
+        kind: PlaylistItemKind::Track,
+        name,
+        artists,
+        handle,
+        duration: track.duration.map(Duration::from_secs),
+        album: track.album.as_ref().map(|album| album.title.clone()),
+        isrc: track.isrc.clone(),
+        mbid: None,
+        canonical: None,
+    }
+}
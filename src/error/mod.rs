@@ -0,0 +1,94 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The URL does not point at anything the provider can read.
+    InvalidUrl(String),
+    /// The credentials of the given provider are not configured.
+    MissingCredentials(&'static str),
+    /// The client was used before it was authenticated.
+    NotInitialized(&'static str),
+    /// The provider cannot perform the requested operation.
+    Unsupported(&'static str),
+    /// The provider answered with something linksen does not understand.
+    UnexpectedResponse(String),
+    Io(std::io::Error),
+    Http(reqwest::Error),
+    Json(serde_json::Error),
+    Spotify(rspotify::ClientError),
+    YouTube(Box<google_youtube3::Error>),
+    /// An audio file whose tags cannot be read.
+    Tags(lofty::error::LoftyError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidUrl(url) => write!(f, "invalid playlist URL: {}", url),
+            Error::MissingCredentials(provider) => {
+                write!(f, "{} credentials are not configured", provider)
+            }
+            Error::NotInitialized(client) => write!(f, "{} API has not been initialized", client),
+            Error::Unsupported(operation) => write!(f, "unsupported operation: {}", operation),
+            Error::UnexpectedResponse(message) => write!(f, "unexpected response: {}", message),
+            Error::Io(error) => write!(f, "I/O error: {}", error),
+            Error::Http(error) => write!(f, "HTTP error: {}", error),
+            Error::Json(error) => write!(f, "JSON error: {}", error),
+            Error::Spotify(error) => write!(f, "Spotify error: {}", error),
+            Error::YouTube(error) => write!(f, "YouTube error: {}", error),
+            Error::Tags(error) => write!(f, "tag error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Http(error) => Some(error),
+            Error::Json(error) => Some(error),
+            Error::Spotify(error) => Some(error),
+            Error::YouTube(error) => Some(error.as_ref()),
+            Error::Tags(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Error::Http(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Json(error)
+    }
+}
+
+impl From<rspotify::ClientError> for Error {
+    fn from(error: rspotify::ClientError) -> Self {
+        Error::Spotify(error)
+    }
+}
+
+impl From<google_youtube3::Error> for Error {
+    fn from(error: google_youtube3::Error) -> Self {
+        Error::YouTube(Box::new(error))
+    }
+}
+
+impl From<lofty::error::LoftyError> for Error {
+    fn from(error: lofty::error::LoftyError) -> Self {
+        Error::Tags(error)
+    }
+}
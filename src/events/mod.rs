@@ -0,0 +1,194 @@
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+use crate::types::{ItemMatch, PlaylistItem, PlaylistItemId};
+
+/// Progress reported by the clients while they work, so that callers decide
+/// how (and whether) to show it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The user has to open the URL to let linksen act on their behalf.
+    AuthorizationRequired {
+        provider: String,
+        url: String,
+    },
+    LoadingStarted,
+    LoadingFinished {
+        count: usize,
+    },
+    EnrichmentStarted {
+        total: usize,
+    },
+    /// The item has been looked up in MusicBrainz, whether it was found or not.
+    ItemEnriched {
+        item: PlaylistItem,
+    },
+    EnrichmentFinished {
+        enriched: usize,
+    },
+    TransformStarted {
+        total: usize,
+    },
+    ItemSearching {
+        item: PlaylistItem,
+    },
+    ItemMatched {
+        item: PlaylistItem,
+        confidence: f32,
+    },
+    ItemUnmatched {
+        item: PlaylistItem,
+    },
+    TransformFinished,
+    CreatingPlaylist {
+        name: String,
+    },
+    ItemInserted {
+        item: PlaylistItem,
+    },
+    ItemFailed {
+        item: PlaylistItem,
+        error: String,
+    },
+    PlaylistCreated {
+        id: String,
+    },
+    Done {
+        matched: usize,
+        unmatched: usize,
+        playlist_id: Option<String>,
+    },
+}
+
+pub trait Reporter: Send + Sync {
+    fn report(&self, event: &Event);
+}
+
+impl<F> Reporter for F
+where
+    F: Fn(&Event) + Send + Sync,
+{
+    fn report(&self, event: &Event) {
+        self(event)
+    }
+}
+
+pub type SharedReporter = Arc<dyn Reporter>;
+
+/// Drops every event.
+pub struct QuietReporter;
+
+impl Reporter for QuietReporter {
+    fn report(&self, _event: &Event) {}
+}
+
+/// Writes every event as a single line of JSON.
+pub struct JsonLinesReporter<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLinesReporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<W: Write + Send> Reporter for JsonLinesReporter<W> {
+    fn report(&self, event: &Event) {
+        let mut writer = self.writer.lock().unwrap();
+
+        if serde_json::to_writer(&mut *writer, event).is_ok() {
+            let _ = writeln!(writer);
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// Keeps every event in memory, for tests and embedders that inspect them
+/// after the fact.
+#[derive(Default)]
+pub struct RecordingReporter {
+    events: Mutex<Vec<Event>>,
+}
+
+impl RecordingReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl Reporter for RecordingReporter {
+    fn report(&self, event: &Event) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
+
+pub(crate) fn quiet() -> SharedReporter {
+    Arc::new(QuietReporter)
+}
+
+/// The outcome of searching for `item`. A failed search is reported as
+/// `ItemFailed` and taken as no match, so that one error does not lose the
+/// matches of the other items.
+pub(crate) fn searched<T>(
+    reporter: &dyn Reporter,
+    item: &PlaylistItem,
+    result: crate::error::Result<Option<T>>,
+) -> Option<T> {
+    result.unwrap_or_else(|error| {
+        reporter.report(&Event::ItemFailed {
+            item: item.clone(),
+            error: format!("search failed: {}", error),
+        });
+
+        None
+    })
+}
+
+pub(crate) fn report_match(reporter: &dyn Reporter, item_match: &ItemMatch) {
+    match &item_match.target {
+        Some(target) => reporter.report(&Event::ItemMatched {
+            item: target.clone(),
+            confidence: item_match.confidence,
+        }),
+        None => reporter.report(&Event::ItemUnmatched {
+            item: item_match.source.clone(),
+        }),
+    }
+}
+
+/// The items a sink of `provider` can add, which are those `owns` accepts.
+/// The others, e.g. matches found by a fallback on another provider, are
+/// reported as failed rather than silently left out.
+pub(crate) fn own_items<'a>(
+    reporter: &dyn Reporter,
+    provider: &str,
+    playlist_items: &'a [PlaylistItem],
+    owns: impl Fn(&PlaylistItemId) -> bool,
+) -> Vec<&'a PlaylistItem> {
+    playlist_items
+        .iter()
+        .filter(|playlist_item| {
+            let owned = owns(&playlist_item.id);
+            if !owned {
+                reporter.report(&Event::ItemFailed {
+                    item: (*playlist_item).clone(),
+                    error: format!("not on {}", provider),
+                });
+            }
+
+            owned
+        })
+        .collect()
+}
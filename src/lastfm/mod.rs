@@ -0,0 +1,413 @@
+use std::{collections::HashSet, env, time::Duration};
+
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::events::{quiet, Event, SharedReporter};
+use crate::playlist_file::parse_location;
+use crate::registry::{Capabilities, Provider, ProviderConfig};
+use crate::types::{
+    LastfmTrack, LastfmTrackInfo, LastfmTracks, MusicClient, PlaylistItem, PlaylistItemId,
+    PlaylistItemKind, PlaylistSource,
+};
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const PAGE_SIZE: usize = 200;
+const PERIODS: [&str; 6] = ["overall", "7day", "1month", "3month", "6month", "12month"];
+
+/// Turns the charts of a Last.fm user (loved tracks, top tracks of a period
+/// and recent scrobbles) into playlists.
+pub struct Lastfm {
+    http: reqwest::Client,
+    api_url: String,
+    api_key: Option<String>,
+    limit: usize,
+    reporter: SharedReporter,
+}
+
+/// What a location names: `<user>/loved`, `<user>/top[/<period>]` or
+/// `<user>/recent`, or the matching pages of last.fm.
+#[derive(Debug, PartialEq, Eq)]
+enum LastfmChart {
+    Loved { user: String },
+    Top { user: String, period: String },
+    Recent { user: String },
+}
+
+impl Lastfm {
+    /// Takes the API key from `LASTFM_API_KEY`.
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: API_URL.into(),
+            api_key: env::var("LASTFM_API_KEY").ok(),
+            limit: PAGE_SIZE,
+            reporter: quiet(),
+        }
+    }
+
+    pub fn with_api_key(api_key: String) -> Self {
+        Self {
+            api_key: Some(api_key),
+            ..Self::new()
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+
+    /// How many top tracks or recent scrobbles to read, 200 by default. Loved
+    /// tracks are always read in full.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Sends the requests to another server than Last.fm's, e.g. a local
+    /// stand-in of the API.
+    pub fn set_base_url(&mut self, api_url: String) {
+        self.api_url = api_url;
+    }
+
+    fn from_config(config: &ProviderConfig) -> Lastfm {
+        let mut lastfm = Lastfm::new();
+        lastfm.set_reporter(config.reporter.clone());
+
+        lastfm
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .ok_or(Error::MissingCredentials("Last.fm"))?;
+
+        // Errors come with a JSON body too, so the status is not checked.
+        let mut response = self
+            .http
+            .get(&self.api_url)
+            .query(&[("method", method), ("api_key", api_key), ("format", "json")])
+            .query(query)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        if let Some(message) = response.get("message").and_then(Value::as_str) {
+            if response.get("error").is_some() {
+                return Err(Error::UnexpectedResponse(format!(
+                    "Last.fm {}: {}",
+                    method, message
+                )));
+            }
+        }
+
+        Ok(serde_json::from_value(response[key].take())?)
+    }
+
+    async fn get_chart(&self, chart: &LastfmChart) -> Result<Vec<LastfmTrack>> {
+        let (method, key, user, period, limit) = match chart {
+            LastfmChart::Loved { user } => ("user.getLovedTracks", "lovedtracks", user, None, None),
+            LastfmChart::Top { user, period } => (
+                "user.getTopTracks",
+                "toptracks",
+                user,
+                Some(period.as_str()),
+                Some(self.limit),
+            ),
+            LastfmChart::Recent { user } => (
+                "user.getRecentTracks",
+                "recenttracks",
+                user,
+                None,
+                Some(self.limit),
+            ),
+        };
+
+        let mut tracks = vec![];
+        let mut page = 1;
+
+        loop {
+            let page_string = page.to_string();
+            let page_size = PAGE_SIZE.min(limit.unwrap_or(PAGE_SIZE)).to_string();
+            let mut query = vec![
+                ("user", user.as_str()),
+                ("page", &page_string),
+                ("limit", &page_size),
+            ];
+            if let Some(period) = period {
+                query.push(("period", period));
+            }
+
+            let chart: LastfmTracks = self.call(method, key, &query).await?;
+            let count = chart.track.len();
+            tracks.extend(chart.track);
+
+            let total_pages = chart
+                .attr
+                .and_then(|attr| attr.total_pages.parse().ok())
+                .unwrap_or(page);
+            let full = limit.is_some_and(|limit| tracks.len() >= limit);
+
+            if count == 0 || page >= total_pages || full {
+                if let Some(limit) = limit {
+                    tracks.truncate(limit);
+                }
+
+                return Ok(tracks);
+            }
+
+            page += 1;
+        }
+    }
+
+    /// Fills in the duration, MBID and album of a track, which the charts
+    /// mostly leave out. Tracks Last.fm knows nothing about are kept as they
+    /// are.
+    async fn complete(&self, mut playlist_item: PlaylistItem) -> PlaylistItem {
+        let mut query = vec![];
+        match &playlist_item.mbid {
+            Some(mbid) => query.push(("mbid", mbid.as_str())),
+            None => {
+                query.push(("artist", playlist_item.artists.as_str()));
+                query.push(("track", playlist_item.name.as_str()));
+            }
+        }
+
+        let info: LastfmTrackInfo = match self.call("track.getInfo", "track", &query).await {
+            Ok(info) => info,
+            Err(_) => return playlist_item,
+        };
+
+        playlist_item.duration = playlist_item.duration.or(info
+            .duration
+            .and_then(|duration| duration.parse().ok())
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis));
+        playlist_item.mbid = playlist_item
+            .mbid
+            .or(Some(info.mbid).filter(|mbid| !mbid.is_empty()));
+        playlist_item.album = playlist_item.album.or(info.album.map(|album| album.title));
+
+        playlist_item
+    }
+}
+
+impl Default for Lastfm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct LastfmProvider;
+
+impl Provider for LastfmProvider {
+    fn name(&self) -> &'static str {
+        "lastfm"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Last.fm"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            ..Default::default()
+        }
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        location.contains("last.fm/")
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(Some(Box::new(Lastfm::from_config(config))))
+    }
+}
+
+#[async_trait]
+impl MusicClient for Lastfm {
+    async fn authenticate(&mut self) -> Result<()> {
+        match self.api_key {
+            Some(_) => Ok(()),
+            None => Err(Error::MissingCredentials("Last.fm")),
+        }
+    }
+}
+
+#[async_trait]
+impl PlaylistSource for Lastfm {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::LoadingStarted);
+
+        let chart = extract_chart(url).ok_or(Error::InvalidUrl(url.to_string()))?;
+        let tracks = self.get_chart(&chart).await?;
+
+        // Scrobbles repeat whatever was played more than once.
+        let mut seen = HashSet::new();
+        let mut playlist_items = vec![];
+
+        for track in tracks {
+            let now_playing = track
+                .attr
+                .as_ref()
+                .and_then(|attr| attr.nowplaying.as_deref())
+                == Some("true");
+            if now_playing || !seen.insert(track.url.clone()) {
+                continue;
+            }
+
+            let playlist_item = track_playlist_item(track);
+            playlist_items.push(match playlist_item.duration {
+                Some(_) => playlist_item,
+                None => self.complete(playlist_item).await,
+            });
+        }
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+
+    async fn get_item(&self, url: &str) -> Result<PlaylistItem> {
+        let invalid_url = || Error::InvalidUrl(url.to_string());
+
+        let path = match parse_location(url) {
+            PlaylistItemId::Lastfm(path) => path,
+            _ => return Err(invalid_url()),
+        };
+        let (artist, track) = path.split_once("/_/").ok_or_else(invalid_url)?;
+        let (artist, track) = (decode_path_segment(artist), decode_path_segment(track));
+
+        let info: LastfmTrackInfo = self
+            .call(
+                "track.getInfo",
+                "track",
+                &[("artist", &artist), ("track", &track)],
+            )
+            .await?;
+        let path = info
+            .url
+            .split_once("/music/")
+            .map_or(path.as_str(), |(_, path)| path)
+            .to_string();
+        let handle = format!("{} - {}", info.name, info.artist.name);
+
+        Ok(PlaylistItem {
+            id: PlaylistItemId::Lastfm(path),
+            kind: PlaylistItemKind::Track,
+            name: info.name,
+            artists: info.artist.name,
+            handle,
+            duration: info
+                .duration
+                .and_then(|duration| duration.parse().ok())
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+            album: info.album.map(|album| album.title),
+            isrc: None,
+            mbid: Some(info.mbid).filter(|mbid| !mbid.is_empty()),
+            canonical: None,
+        })
+    }
+}
+
+fn extract_chart(url: &str) -> Option<LastfmChart> {
+    let url = url.trim();
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let path = match path.find("last.fm/user/") {
+        Some(start_index) => &path[start_index + "last.fm/user/".len()..],
+        None => path,
+    };
+
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    let user = segments.next()?.to_string();
+
+    let chart = match (segments.next()?, segments.next()) {
+        ("loved", None) => LastfmChart::Loved { user },
+        ("recent" | "library", None) => LastfmChart::Recent { user },
+        ("top", period) => LastfmChart::Top {
+            user,
+            period: period.unwrap_or("overall").to_string(),
+        },
+        // The top tracks page names its period as e.g. ?date_preset=LAST_7_DAYS.
+        ("library", Some("tracks")) => {
+            let date_preset = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("date_preset="))
+                .unwrap_or("ALL");
+            let period = match date_preset {
+                "LAST_7_DAYS" => "7day",
+                "LAST_30_DAYS" => "1month",
+                "LAST_90_DAYS" => "3month",
+                "LAST_180_DAYS" => "6month",
+                "LAST_365_DAYS" => "12month",
+                "ALL" => "overall",
+                _ => return None,
+            };
+
+            LastfmChart::Top {
+                user,
+                period: period.to_string(),
+            }
+        }
+        _ => return None,
+    };
+
+    match &chart {
+        LastfmChart::Top { period, .. } if !PERIODS.contains(&period.as_str()) => None,
+        _ => Some(chart),
+    }
+}
+
+/// Last.fm links encode spaces as "+" and everything else as "%XX".
+fn decode_path_segment(segment: &str) -> String {
+    Url::parse(&format!("http://localhost/?segment={}", segment))
+        .ok()
+        .and_then(|url| {
+            url.query_pairs()
+                .next()
+                .map(|(_, value)| value.into_owned())
+        })
+        .unwrap_or_else(|| segment.to_string())
+}
+
+fn track_playlist_item(track: LastfmTrack) -> PlaylistItem {
+    let path = track
+        .url
+        .split_once("/music/")
+        .map_or(track.url.as_str(), |(_, path)| path)
+        .to_string();
+    let handle = format!("{} - {}", track.name, track.artist.name);
+
+    PlaylistItem {
+        id: PlaylistItemId::Lastfm(path),
+        kind: PlaylistItemKind::Track,
+        name: track.name,
+        artists: track.artist.name,
+        handle,
+        duration: track
+            .duration
+            .and_then(|duration| duration.parse().ok())
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs),
+        album: track
+            .album
+            .map(|album| album.text)
+            .filter(|album| !album.is_empty()),
+        isrc: None,
+        mbid: Some(track.mbid).filter(|mbid| !mbid.is_empty()),
+        canonical: None,
+    }
+}
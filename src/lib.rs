@@ -0,0 +1,41 @@
+pub mod apple_music;
+pub mod cache;
+pub mod deezer;
+pub mod error;
+pub mod events;
+pub mod lastfm;
+pub mod local_library;
+pub mod matching;
+pub mod musicbrainz;
+pub mod normalize;
+mod oauth;
+pub mod playlist_file;
+pub mod registry;
+pub mod soundcloud;
+pub mod spotify;
+pub mod tidal;
+pub mod types;
+pub mod youtube;
+
+pub use apple_music::AppleMusic;
+pub use cache::{CachedMatch, CachedSearcher, MatchCache};
+pub use deezer::Deezer;
+pub use error::{Error, Result};
+pub use events::{
+    Event, JsonLinesReporter, QuietReporter, RecordingReporter, Reporter, SharedReporter,
+};
+pub use lastfm::Lastfm;
+pub use local_library::LocalLibrary;
+pub use matching::FallbackSearcher;
+pub use musicbrainz::MusicBrainz;
+pub use playlist_file::{PlaylistFile, PlaylistFileFormat};
+pub use registry::{Capabilities, Provider, ProviderConfig, Registry};
+pub use soundcloud::SoundCloud;
+pub use spotify::Spotify;
+pub use tidal::Tidal;
+pub use types::{
+    CanonicalTrack, ItemMatch, MusicClient, ParsedPlaylistItems, PlaylistItem, PlaylistItemId,
+    PlaylistItemKind, PlaylistOptions, PlaylistSink, PlaylistSource, PlaylistSummary, Privacy,
+    SearchPreference, TrackSearcher,
+};
+pub use youtube::Youtube;
@@ -0,0 +1,223 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use lofty::prelude::*;
+
+use crate::error::{Error, Result};
+use crate::events::{quiet, Event, SharedReporter};
+use crate::registry::{Capabilities, Provider, ProviderConfig};
+use crate::types::{MusicClient, PlaylistItem, PlaylistItemId, PlaylistItemKind, PlaylistSource};
+
+/// Reads a directory of MP3, FLAC, Ogg and MP4 files as a playlist. The "URL"
+/// of the playlist is the path of the directory, which is walked recursively.
+pub struct LocalLibrary {
+    reporter: SharedReporter,
+}
+
+impl LocalLibrary {
+    pub fn new() -> Self {
+        Self { reporter: quiet() }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+}
+
+impl Default for LocalLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Registers local libraries, which are recognised by being existing
+/// directories.
+pub struct LocalLibraryProvider;
+
+impl Provider for LocalLibraryProvider {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Local library"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            ..Default::default()
+        }
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        Path::new(location).is_dir()
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        let mut local_library = LocalLibrary::new();
+        local_library.set_reporter(config.reporter.clone());
+
+        Ok(Some(Box::new(local_library)))
+    }
+}
+
+impl MusicClient for LocalLibrary {}
+
+#[async_trait]
+impl PlaylistSource for LocalLibrary {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::LoadingStarted);
+
+        let directory = Path::new(url);
+        if !directory.is_dir() {
+            return Err(Error::InvalidUrl(url.to_string()));
+        }
+
+        let mut paths = vec![];
+        collect_audio_files(directory, &mut HashSet::new(), &mut paths)?;
+
+        let playlist_items = paths
+            .iter()
+            .map(|path| audio_file_playlist_item(path))
+            .collect::<Vec<_>>();
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+}
+
+#[derive(Debug, Default)]
+struct AudioTags {
+    title: Option<String>,
+    artists: Vec<String>,
+    album: Option<String>,
+    duration: Option<Duration>,
+    isrc: Option<String>,
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            matches!(
+                extension.to_lowercase().as_str(),
+                "mp3" | "flac" | "ogg" | "oga" | "opus" | "m4a" | "mp4" | "m4b" | "aac"
+            )
+        })
+}
+
+/// Walks `directory` and the directories below it, following links to
+/// directories but reading each of them only once, so that a link back up the
+/// tree does not loop.
+fn collect_audio_files(
+    directory: &Path,
+    visited: &mut HashSet<PathBuf>,
+    paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    if !visited.insert(fs::canonicalize(directory)?) {
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            !path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .is_some_and(|file_name| file_name.starts_with('.'))
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_audio_files(&path, visited, paths)?;
+        } else if is_audio_file(&path) {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn audio_file_playlist_item(path: &Path) -> PlaylistItem {
+    // Files without readable tags still end up in the playlist, named after
+    // the file.
+    let tags = read_tags(path).unwrap_or_default();
+
+    let name = tags.title.unwrap_or_else(|| {
+        path.file_stem()
+            .and_then(|file_stem| file_stem.to_str())
+            .unwrap_or_default()
+            .to_string()
+    });
+    let artists = tags.artists.join(", ");
+
+    let handle = if artists.is_empty() {
+        name.clone()
+    } else {
+        format!("{} - {}", name, artists)
+    };
+
+    PlaylistItem {
+        id: PlaylistItemId::Local(path.to_string_lossy().into_owned()),
+        kind: PlaylistItemKind::Track,
+        name,
+        artists,
+        handle,
+        duration: tags.duration,
+        album: tags.album,
+        isrc: tags.isrc,
+        mbid: None,
+        canonical: None,
+    }
+}
+
+/// Reads the tags of any format lofty knows, along with the duration of the
+/// audio itself, which does not depend on the file having it tagged.
+fn read_tags(path: &Path) -> Result<AudioTags> {
+    let file = lofty::read_from_path(path)?;
+
+    let duration = Some(file.properties().duration()).filter(|duration| !duration.is_zero());
+    let tag = match file.primary_tag().or_else(|| file.first_tag()) {
+        Some(tag) => tag,
+        None => {
+            return Ok(AudioTags {
+                duration,
+                ..Default::default()
+            })
+        }
+    };
+
+    let text = |key: &ItemKey| {
+        tag.get_string(key)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    Ok(AudioTags {
+        title: text(&ItemKey::TrackTitle),
+        artists: tag
+            .get_strings(&ItemKey::TrackArtist)
+            .map(|artist| artist.trim().to_string())
+            .filter(|artist| !artist.is_empty())
+            .collect(),
+        album: text(&ItemKey::AlbumTitle),
+        duration,
+        isrc: text(&ItemKey::Isrc),
+    })
+}
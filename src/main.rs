@@ -1,122 +1,717 @@
-mod spotify;
-mod types;
-mod youtube;
+mod batch;
+mod migrate;
+mod output;
+mod server;
+mod terminal;
+mod watch;
 
-use clap::{Parser, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
-use std::io::Write;
-use types::MusicClient;
+use linksen::{
+    playlist_file::write_playlist, CachedSearcher, Event, FallbackSearcher, ItemMatch,
+    JsonLinesReporter, MatchCache, MusicBrainz, ParsedPlaylistItems, PlaylistFileFormat,
+    PlaylistItem, PlaylistOptions, Privacy, Provider, ProviderConfig, QuietReporter, Registry,
+    SearchPreference, SharedReporter, TrackSearcher,
+};
+use output::{print_link_report, print_report, LinkReport, OutputFormat, Report};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, IsTerminal, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::Arc,
+};
+use terminal::TerminalReporter;
 
 #[derive(Parser)]
 struct Cli {
-    /// Mode to run the tool in
-    #[arg(value_enum)]
-    mode: Mode,
-
-    /// Playlist URL
-    #[arg(short, long)]
-    url: Option<String>,
+    #[command(subcommand)]
+    command: Command,
 
     /// Google OAuth Client ID
-    #[arg(long)]
+    #[arg(long, global = true)]
     google_client_id: Option<String>,
 
     /// Google OAuth Client Secret
-    #[arg(long)]
+    #[arg(long, global = true)]
     google_client_secret: Option<String>,
+
+    /// How to report progress
+    #[arg(long, value_enum, global = true, default_value_t = Progress::Terminal)]
+    progress: Progress,
+
+    /// Search for every item instead of reusing and remembering matches in matches.json
+    #[arg(long, global = true)]
+    no_cache: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a playlist from one provider to another, or to a file
+    Convert(ConvertArgs),
+    /// Find a single track or episode on every other provider
+    Link(LinkArgs),
+    /// Run every conversion listed in a TOML file, sharing authentication and quota
+    Batch(BatchArgs),
+    /// Serve an HTTP API to start conversions, follow them and look links up
+    Serve(ServeArgs),
+    /// Keep target playlists up to date with their sources, as listed in a TOML file
+    Watch(WatchArgs),
+    /// Recreate the playlists of a user on another provider, over as many runs as the quota needs
+    Migrate(MigrateArgs),
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// Playlist to read: a URL, a playlist file, a directory of audio files or <provider>:<location>, e.g. youtube:liked
+    #[arg(long)]
+    from: String,
+
+    /// Where to write the playlist: a provider name such as youtube or spotify, or the path of a playlist file
+    #[arg(long)]
+    to: String,
+
+    /// Format of the playlist file written, or read when the target is a provider (m3u, xspf, csv or json), guessed from the file extension by default
+    #[arg(long)]
+    format: Option<PlaylistFileFormat>,
+
+    /// Format of the playlist file read, for converting one file to another
+    #[arg(long)]
+    from_format: Option<PlaylistFileFormat>,
+
+    /// Provider to search for the items the target does not have, e.g. soundcloud
+    #[arg(long)]
+    fallback: Option<String>,
+
+    /// What to match items with on YouTube: songs of YouTube Music or any videos (the default for youtube; youtube-music prefers songs)
+    #[arg(long)]
+    prefer: Option<SearchPreference>,
+
+    /// Look every item up in MusicBrainz before matching, to match by recording rather than by name
+    #[arg(long)]
+    enrich: bool,
+
+    /// Create the playlist without asking for confirmation
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Name of the created playlist
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Description of the created playlist
+    #[arg(long)]
+    description: Option<String>,
+
+    /// Privacy of the created playlist (public, unlisted or private)
+    #[arg(long, default_value_t = Privacy::Private)]
+    privacy: Privacy,
+
+    /// Format of the final listing
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
+}
+
+#[derive(Args)]
+struct LinkArgs {
+    /// Link to the track or episode, or <provider>:<location>
+    url: String,
+
+    /// Providers to look for it on, comma separated; every provider that can search by default
+    #[arg(long, value_delimiter = ',')]
+    to: Vec<String>,
+
+    /// What to match the item with on YouTube: songs of YouTube Music or any videos
+    #[arg(long)]
+    prefer: Option<SearchPreference>,
+
+    /// Look the item up in MusicBrainz before matching, to match by recording rather than by name
+    #[arg(long)]
+    enrich: bool,
+
+    /// Format of the listing
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
+}
+
+#[derive(Args)]
+struct BatchArgs {
+    /// File listing the conversions to run
+    jobs: PathBuf,
+
+    /// Format of the combined report
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8000")]
+    listen: SocketAddr,
+
+    /// Let clients convert playlist files and audio directories of this machine
+    #[arg(long)]
+    allow_local_files: bool,
+}
+
+#[derive(Args)]
+struct WatchArgs {
+    /// File listing the source and target of every playlist to keep in sync
+    config: PathBuf,
+
+    /// File recording the last sync of every pair, next to the config file by default
+    #[arg(long)]
+    state: Option<PathBuf>,
+
+    /// Sync every pair once and exit, e.g. when run from cron
+    #[arg(long)]
+    once: bool,
+}
+
+#[derive(Args)]
+struct MigrateArgs {
+    /// Provider whose playlists to migrate, for the authenticated user, or a link to the profile of a user
+    #[arg(long)]
+    from: String,
+
+    /// Provider to recreate the playlists on
+    #[arg(long)]
+    to: String,
+
+    /// Only migrate playlists whose name contains this text; may be given more than once
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Leave out playlists whose name contains this text; may be given more than once
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Leave out the playlists the user follows but did not make
+    #[arg(long)]
+    owned: bool,
+
+    /// Migrate every playlist listed without asking which
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Provider to search for the items the target does not have, e.g. soundcloud
+    #[arg(long)]
+    fallback: Option<String>,
+
+    /// What to match items with on YouTube: songs of YouTube Music or any videos
+    #[arg(long)]
+    prefer: Option<SearchPreference>,
+
+    /// Look every item up in MusicBrainz before matching, to match by recording rather than by name
+    #[arg(long)]
+    enrich: bool,
+
+    /// Privacy of the created playlists (public, unlisted or private)
+    #[arg(long, default_value_t = Privacy::Private)]
+    privacy: Privacy,
+
+    /// Units of the target's API quota this run may spend, e.g. 10000 for a day of YouTube
+    #[arg(long)]
+    quota: Option<u64>,
+
+    /// File recording how far the migration got, to continue it in a later run
+    #[arg(long, default_value = "migrate.state.json")]
+    state: PathBuf,
+
+    /// Format of the combined report
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum Mode {
-    SpotifyPlaylistToYoutube,
-    YoutubePlaylistToSpotify,
+enum Progress {
+    Terminal,
+    Quiet,
+    Jsonl,
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
-    match cli.mode {
-        Mode::SpotifyPlaylistToYoutube => {
-            println!("{}", "Welcome to linksen!".on_blue().black());
-            println!("Mode: {}", "Spotify playlist -> YouTube playlist".blue());
-            println!();
+    if let Err(error) = run(cli).await {
+        eprintln!("{}", format!("[ERROR] {}", error).on_red().white());
+
+        exit(1);
+    }
+}
+
+/// The settings every command passes to the providers.
+fn provider_config(cli: &Cli, reporter: SharedReporter) -> ProviderConfig {
+    ProviderConfig {
+        google_client_id: cli.google_client_id.clone(),
+        google_client_secret: cli.google_client_secret.clone(),
+        reporter,
+        ..Default::default()
+    }
+}
+
+/// The format of the playlist file read, if the source is one. `--format`
+/// describes the target when writing to a file, and the source otherwise.
+fn source_format(
+    args: &ConvertArgs,
+    to_provider: bool,
+) -> Result<Option<PlaylistFileFormat>, Box<dyn Error>> {
+    match (
+        args.from_format,
+        to_provider.then_some(args.format).flatten(),
+    ) {
+        (Some(_), Some(_)) => {
+            Err("give the format of the file read once, with --from-format".into())
+        }
+        (from_format, format) => Ok(from_format.or(format)),
+    }
+}
 
-            let url = cli.url.unwrap();
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let terminal = cli.progress == Progress::Terminal;
+    let reporter: SharedReporter = match cli.progress {
+        Progress::Terminal => Arc::new(TerminalReporter::default()),
+        Progress::Quiet => Arc::new(QuietReporter),
+        Progress::Jsonl => Arc::new(JsonLinesReporter::new(std::io::stderr())),
+    };
 
-            let spotify = spotify::Spotify::new();
-            spotify.authenticate().await;
+    match &cli.command {
+        Command::Convert(args) => convert(&cli, args, reporter, terminal).await,
+        Command::Link(args) => link(&cli, args, reporter).await,
+        Command::Batch(args) => {
+            let config = provider_config(&cli, reporter);
 
-            let playlist_items = spotify.get_playlist_items(&url).await;
+            batch::batch(
+                &args.jobs,
+                config,
+                match_cache(&cli)?,
+                args.output,
+                terminal,
+            )
+            .await
+        }
+        Command::Serve(args) => {
+            let config = provider_config(&cli, reporter);
 
-            println!();
+            server::serve(
+                args.listen,
+                args.allow_local_files,
+                config,
+                match_cache(&cli)?,
+            )
+            .await
+        }
+        Command::Watch(args) => {
+            let config = provider_config(&cli, reporter);
+            let state = args
+                .state
+                .clone()
+                .unwrap_or_else(|| args.config.with_extension("state.json"));
 
-            let mut youtube = youtube::Youtube::new();
-            let playlist_items = youtube.parse_playlist_items(playlist_items).await;
+            watch::watch(&args.config, &state, args.once, config, match_cache(&cli)?).await
+        }
+        Command::Migrate(args) => {
+            let config = provider_config(&cli, reporter);
 
-            println!();
-            print!("Do you want to create a playlist? [Y/n] ");
-            let _ = std::io::stdout().flush();
+            migrate::migrate(args, config, match_cache(&cli)?, terminal).await
+        }
+    }
+}
 
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
+/// Reads the source playlist, matches it against the target, lets the user
+/// review the result and finally writes it.
+async fn convert(
+    cli: &Cli,
+    args: &ConvertArgs,
+    reporter: SharedReporter,
+    terminal: bool,
+) -> Result<(), Box<dyn Error>> {
+    let registry = Registry::new();
 
-            if input.trim().to_lowercase() == "yes"
-                || input.trim().to_lowercase() == "y"
-                || input.trim() == ""
-            {
-                println!();
-                println!("{}", "Creating playlist".on_green().black());
+    let (source, location) = registry.resolve(&args.from)?;
+    let target = registry.get(&args.to);
+    let fallback = args
+        .fallback
+        .as_deref()
+        .map(|name| {
+            registry
+                .get(name)
+                .ok_or_else(|| format!("unknown provider {}", name))
+        })
+        .transpose()?;
 
-                youtube
-                    .init_api_hub(cli.google_client_id, cli.google_client_secret)
-                    .await;
-                youtube.create_playlist(&playlist_items).await;
-            } else {
-                println!();
-                println!("{}", "Playlist items".on_green().black());
+    if target.is_none() && fallback.is_some() {
+        return Err("--fallback only applies when the target is a provider".into());
+    }
 
-                for playlist_item in playlist_items {
-                    println!(
-                        "{}: {}",
-                        playlist_item.name.green(),
-                        playlist_item.id.to_string().blue()
-                    );
-                }
+    let config = ProviderConfig {
+        playlist_file_format: source_format(args, target.is_some())?,
+        search_preference: args.prefer,
+        ..provider_config(cli, reporter.clone())
+    };
+
+    let mode = format!(
+        "{} -> {}",
+        source.display_name(),
+        target.map_or("file", |target| target.display_name())
+    );
+
+    if terminal {
+        print_banner(&mode);
+    }
+
+    let mut client = source
+        .source(&config)?
+        .ok_or_else(|| format!("{} playlists cannot be read", source.display_name()))?;
+    client.authenticate().await?;
+    let mut playlist_items = client.get_playlist_items(location).await?;
+
+    if args.enrich {
+        let mut musicbrainz = MusicBrainz::new();
+        musicbrainz.set_reporter(reporter.clone());
+        playlist_items = musicbrainz.enrich(playlist_items).await?;
+    }
+
+    if terminal {
+        eprintln!();
+    }
+
+    let (playlist_items, playlist_id) = match target {
+        Some(target) => {
+            let cache = match_cache(cli)?;
+
+            to_provider(
+                args,
+                target,
+                fallback,
+                &config,
+                &cache,
+                playlist_items,
+                terminal,
+            )
+            .await?
+        }
+        None => {
+            return to_file(
+                args,
+                Path::new(&args.to),
+                reporter,
+                playlist_items,
+                terminal,
+            )
+        }
+    };
+
+    print_report(
+        args.output,
+        &Report::new(&mode, location, &playlist_items, playlist_id.as_deref()),
+    )?;
+
+    let matched = playlist_items.matched().len();
+    reporter.report(&Event::Done {
+        matched,
+        unmatched: playlist_items.items.len() - matched,
+        playlist_id,
+    });
+
+    Ok(())
+}
+
+async fn to_provider(
+    args: &ConvertArgs,
+    target: &dyn Provider,
+    fallback: Option<&dyn Provider>,
+    config: &ProviderConfig,
+    cache: &Arc<MatchCache>,
+    playlist_items: Vec<PlaylistItem>,
+    terminal: bool,
+) -> Result<(ParsedPlaylistItems, Option<String>), Box<dyn Error>> {
+    let searcher = target_searcher(target, fallback, config, cache)?;
+    let playlist_items = searcher.parse_playlist_items(playlist_items).await?;
+
+    let mut sink = match target.sink(config)? {
+        Some(sink) => sink,
+        None => {
+            if terminal {
+                eprintln!();
             }
+
+            return Ok((playlist_items, None));
         }
-        Mode::YoutubePlaylistToSpotify => {
-            println!("{}", "Welcome to linksen!".on_blue().black());
-            println!("Mode: {}", "YouTube playlist -> Spotify playlist".blue());
-            println!();
+    };
 
-            let url = cli.url.unwrap();
+    if !args.yes && !confirm("Do you want to create a playlist? [Y/n] ")? {
+        return Ok((playlist_items, None));
+    }
 
-            let mut youtube = youtube::Youtube::new();
-            youtube
-                .init_api_hub(cli.google_client_id, cli.google_client_secret)
-                .await;
+    if terminal {
+        eprintln!();
+        eprintln!("{}", "Creating playlist".on_green().black());
+    }
 
-            let playlist_items = youtube.get_playlist_items(&url).await;
+    sink.authenticate().await?;
 
-            println!();
+    let options = PlaylistOptions {
+        name: match &args.name {
+            Some(name) => name.clone(),
+            None => prompt_playlist_name()?,
+        },
+        description: args.description.clone(),
+        privacy: args.privacy,
+    };
+
+    let playlist_id = sink
+        .create_playlist(&options, &playlist_items.matched())
+        .await?;
+
+    Ok((playlist_items, Some(playlist_id)))
+}
+
+fn to_file(
+    args: &ConvertArgs,
+    path: &Path,
+    reporter: SharedReporter,
+    playlist_items: Vec<PlaylistItem>,
+    terminal: bool,
+) -> Result<(), Box<dyn Error>> {
+    let format = match args.format {
+        Some(format) => format,
+        None => PlaylistFileFormat::from_path(path)
+            .ok_or("cannot guess the playlist file format from the file name, pass --format")?,
+    };
+
+    let mut file = BufWriter::new(File::create(path)?);
+    write_playlist(format, &playlist_items, &mut file)?;
+    file.flush()?;
+
+    if terminal {
+        eprintln!(
+            "{}",
+            format!(
+                "Exported {} items to {}",
+                playlist_items.len(),
+                path.display()
+            )
+            .green()
+        );
+    }
+
+    reporter.report(&Event::Done {
+        matched: playlist_items.len(),
+        unmatched: 0,
+        playlist_id: None,
+    });
+
+    Ok(())
+}
 
-            let spotify = spotify::Spotify::new();
-            spotify.authenticate().await;
+/// Reads a single item and prints the best match for it on every other
+/// provider, or on the ones asked for.
+async fn link(cli: &Cli, args: &LinkArgs, reporter: SharedReporter) -> Result<(), Box<dyn Error>> {
+    let config = ProviderConfig {
+        search_preference: args.prefer,
+        ..provider_config(cli, reporter)
+    };
+    let cache = match_cache(cli)?;
 
-            let playlist_items = spotify.parse_playlist_items(playlist_items).await;
+    let (item, links) = find_links(
+        &Registry::new(),
+        &config,
+        &cache,
+        &args.url,
+        &args.to,
+        args.enrich,
+    )
+    .await?;
 
-            println!();
-            println!();
-            println!("{}", "Playlist items".on_green().black());
+    print_link_report(args.output, &LinkReport::new(&item, &links))?;
 
-            for playlist_item in playlist_items {
-                println!(
-                    "{}: {}",
-                    playlist_item.name.green(),
-                    playlist_item.id.to_string().blue()
-                );
+    Ok(())
+}
+
+/// Reads the item at `url` and looks for it on the providers named in `to`,
+/// or on every other provider that can search. Providers that are not set up
+/// are only reported when they were asked for.
+async fn find_links(
+    registry: &Registry,
+    config: &ProviderConfig,
+    cache: &Arc<MatchCache>,
+    url: &str,
+    to: &[String],
+    enrich: bool,
+) -> Result<(PlaylistItem, Vec<(&'static str, Result<ItemMatch, String>)>), Box<dyn Error>> {
+    let (source, location) = registry.resolve(url)?;
+
+    let targets = if to.is_empty() {
+        registry
+            .providers()
+            .filter(|provider| provider.capabilities().search && provider.name() != source.name())
+            .collect::<Vec<_>>()
+    } else {
+        to.iter()
+            .map(|name| match registry.get(name) {
+                Some(provider) if provider.capabilities().search => Ok(provider),
+                Some(provider) => Err(format!(
+                    "{} cannot search for tracks",
+                    provider.display_name()
+                )),
+                None => Err(format!("unknown provider {}", name)),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let client = source
+        .source(config)?
+        .ok_or_else(|| format!("{} links cannot be read", source.display_name()))?;
+    let mut item = client.get_item(location).await?;
+
+    if enrich {
+        let mut musicbrainz = MusicBrainz::new();
+        musicbrainz.set_reporter(config.reporter.clone());
+        item = musicbrainz.enrich(vec![item]).await?.remove(0);
+    }
+
+    // The per-item progress of a single search is noise, but authorization
+    // links still have to reach the user.
+    let reporter = config.reporter.clone();
+    let searcher_config = ProviderConfig {
+        reporter: Arc::new(move |event: &Event| {
+            if let Event::AuthorizationRequired { .. } = event {
+                reporter.report(event);
             }
+        }),
+        ..config.clone()
+    };
+
+    let mut links = vec![];
+    for target in targets {
+        let found = match target.searcher(&searcher_config) {
+            Ok(Some(searcher)) => {
+                let mut searcher = CachedSearcher::new(searcher, target.name(), cache.clone());
+                searcher.set_preference(config.search_preference);
+
+                searcher
+                    .parse_playlist_items(vec![item.clone()])
+                    .await
+                    .map(|parsed| parsed.items.into_iter().next())
+            }
+            Ok(None) => Ok(None),
+            Err(error) => Err(error),
+        };
+
+        match found {
+            Ok(Some(item_match)) => links.push((target.display_name(), Ok(item_match))),
+            Ok(None) => {}
+            Err(linksen::Error::MissingCredentials(_)) if to.is_empty() => {}
+            Err(error) => links.push((target.display_name(), Err(error.to_string()))),
         }
     }
+
+    Ok((item, links))
+}
+
+/// The match cache of matches.json, or one that forgets everything with
+/// --no-cache.
+fn match_cache(cli: &Cli) -> Result<Arc<MatchCache>, Box<dyn Error>> {
+    Ok(Arc::new(match cli.no_cache {
+        true => MatchCache::new(),
+        false => MatchCache::open()?,
+    }))
+}
+
+/// The searcher of `target`, backed by `fallback` if there is one, that
+/// remembers what it finds in `cache`.
+fn target_searcher(
+    target: &dyn Provider,
+    fallback: Option<&dyn Provider>,
+    config: &ProviderConfig,
+    cache: &Arc<MatchCache>,
+) -> Result<Box<dyn TrackSearcher>, Box<dyn Error>> {
+    let mut searcher = provider_searcher(target, config, cache)?;
+    if let Some(fallback) = fallback {
+        // The fallback only searches what the target's transform left
+        // unmatched, so it must not start a transform of its own.
+        let reporter = config.reporter.clone();
+        let fallback_config = ProviderConfig {
+            reporter: Arc::new(move |event: &Event| {
+                if !matches!(
+                    event,
+                    Event::TransformStarted { .. } | Event::TransformFinished
+                ) {
+                    reporter.report(event);
+                }
+            }),
+            ..config.clone()
+        };
+
+        searcher = Box::new(FallbackSearcher::new(
+            searcher,
+            provider_searcher(fallback, &fallback_config, cache)?,
+        ));
+    }
+
+    Ok(searcher)
+}
+
+fn provider_searcher(
+    provider: &dyn Provider,
+    config: &ProviderConfig,
+    cache: &Arc<MatchCache>,
+) -> Result<Box<dyn TrackSearcher>, Box<dyn Error>> {
+    let searcher = provider
+        .searcher(config)?
+        .ok_or_else(|| format!("{} cannot search for tracks", provider.display_name()))?;
+
+    let mut searcher = CachedSearcher::new(searcher, provider.name(), cache.clone());
+    searcher.set_preference(config.search_preference);
+
+    Ok(Box::new(searcher))
+}
+
+fn print_banner(mode: &str) {
+    eprintln!("{}", "Welcome to linksen!".on_blue().black());
+    eprintln!("Mode: {}", mode.blue());
+    eprintln!();
+}
+
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    eprintln!();
+    let input = prompt(question)?;
+
+    Ok(matches!(input.to_lowercase().as_str(), "yes" | "y" | ""))
+}
+
+fn prompt_playlist_name() -> Result<String, Box<dyn Error>> {
+    loop {
+        let input = prompt("Playlist name: ")?;
+
+        if input.is_empty() {
+            eprintln!("[ERROR] Playlist name cannot be empty!");
+            continue;
+        }
+
+        return Ok(input);
+    }
+}
+
+fn prompt(question: &str) -> Result<String, Box<dyn Error>> {
+    if !std::io::stdin().is_terminal() {
+        return Err(format!(
+            "cannot ask \"{}\" because stdin is not a terminal; pass --yes and --name to run non-interactively",
+            question.trim()
+        )
+        .into());
+    }
+
+    eprint!("{}", question);
+    let _ = std::io::stderr().flush();
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+
+    Ok(input.trim().to_string())
 }
@@ -0,0 +1,165 @@
+use std::{collections::HashSet, time::Duration};
+
+use async_trait::async_trait;
+
+use crate::error::Result;
+use crate::types::{MusicClient, ParsedPlaylistItems, PlaylistItem, TrackSearcher};
+
+/// Whether two durations plausibly belong to the same recording. Podcast
+/// episodes in particular are often re-uploaded with intros cut or ads added,
+/// so the durations only have to roughly agree.
+pub fn durations_match(a: Duration, b: Duration) -> bool {
+    let tolerance = Duration::from_secs(60).max(a.max(b) / 20);
+
+    a.abs_diff(b) <= tolerance
+}
+
+/// How confident we are that `target` is the same recording as `source`, from
+/// 0.0 (nothing in common) to 1.0 (same title, artists and duration).
+pub fn confidence(source: &PlaylistItem, target: &PlaylistItem) -> f32 {
+    if source.id == target.id {
+        return 1.0;
+    }
+
+    // An ISRC identifies the recording itself, whatever the provider.
+    if let (Some(source_isrc), Some(target_isrc)) = (&source.isrc, &target.isrc) {
+        if source_isrc.eq_ignore_ascii_case(target_isrc) {
+            return 1.0;
+        }
+    }
+
+    // So does a MusicBrainz recording ID.
+    if let (Some(source_mbid), Some(target_mbid)) = (&source.mbid, &target.mbid) {
+        if source_mbid.eq_ignore_ascii_case(target_mbid) {
+            return 1.0;
+        }
+    }
+
+    // Enriched items are also compared the way MusicBrainz credits them,
+    // which is what they were searched for with.
+    let score = names_confidence(&source.name, &source.artists, source, target);
+
+    match &source.canonical {
+        Some(canonical) => score.max(names_confidence(
+            &canonical.title,
+            &canonical.artist_credit,
+            source,
+            target,
+        )),
+        None => score,
+    }
+}
+
+fn names_confidence(
+    source_title: &str,
+    source_artists: &str,
+    source: &PlaylistItem,
+    target: &PlaylistItem,
+) -> f32 {
+    let source_name = tokens(source_title);
+    let target_name = tokens(&target.name);
+    let name_score = overlap(&source_name, &target_name);
+
+    // Uploaders often put the artist in the title rather than the channel name.
+    let source_artists = tokens(source_artists);
+    let target_artists = tokens(&format!("{} {}", target.artists, target.name));
+    let artists_score = containment(&source_artists, &target_artists);
+
+    match (source.duration, target.duration) {
+        (Some(source_duration), Some(target_duration)) => {
+            let duration_score = if durations_match(source_duration, target_duration) {
+                1.0
+            } else {
+                0.0
+            };
+
+            0.55 * name_score + 0.3 * artists_score + 0.15 * duration_score
+        }
+        _ => 0.65 * name_score + 0.35 * artists_score,
+    }
+}
+
+/// Searches with `primary` and, for whatever it does not find, with
+/// `fallback`, e.g. SoundCloud for the mixes missing on YouTube.
+/// The fallback searches after `primary` has finished its transform, so its
+/// reporter should leave out the transform events of its own.
+pub struct FallbackSearcher {
+    primary: Box<dyn TrackSearcher>,
+    fallback: Box<dyn TrackSearcher>,
+}
+
+impl FallbackSearcher {
+    pub fn new(primary: Box<dyn TrackSearcher>, fallback: Box<dyn TrackSearcher>) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+#[async_trait]
+impl MusicClient for FallbackSearcher {
+    async fn authenticate(&mut self) -> Result<()> {
+        self.primary.authenticate().await?;
+        self.fallback.authenticate().await
+    }
+}
+
+#[async_trait]
+impl TrackSearcher for FallbackSearcher {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        let mut parsed_playlist_items = self.primary.parse_playlist_items(playlist_items).await?;
+
+        let (positions, unmatched): (Vec<_>, Vec<_>) = parsed_playlist_items
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item_match)| item_match.target.is_none())
+            .map(|(position, item_match)| (position, item_match.source.clone()))
+            .unzip();
+
+        if unmatched.is_empty() {
+            return Ok(parsed_playlist_items);
+        }
+
+        let fallback_items = self.fallback.parse_playlist_items(unmatched).await?;
+        for (position, item_match) in positions.into_iter().zip(fallback_items.items) {
+            if item_match.target.is_some() {
+                parsed_playlist_items.items[position] = item_match;
+            }
+        }
+
+        Ok(parsed_playlist_items)
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<String>> {
+        match self.primary.search(query).await? {
+            Some(result) => Ok(Some(result)),
+            None => self.fallback.search(query).await,
+        }
+    }
+}
+
+fn tokens(text: &str) -> HashSet<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_string())
+        .collect()
+}
+
+fn overlap(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    a.intersection(b).count() as f32 / a.union(b).count() as f32
+}
+
+fn containment(needle: &HashSet<String>, haystack: &HashSet<String>) -> f32 {
+    if needle.is_empty() {
+        return 1.0;
+    }
+
+    needle.intersection(haystack).count() as f32 / needle.len() as f32
+}
@@ -0,0 +1,470 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fs,
+    io::IsTerminal,
+    sync::{Arc, Mutex},
+};
+
+use colored::Colorize;
+use linksen::{
+    Event, MatchCache, MusicBrainz, ParsedPlaylistItems, PlaylistItemId, PlaylistOptions,
+    PlaylistSink, PlaylistSource, PlaylistSummary, Provider, ProviderConfig, Registry,
+    TrackSearcher,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::output::{print_batch_report, BatchReport, JobRecord, JobStatus, Report};
+use crate::{prompt, target_searcher, MigrateArgs};
+
+/// What earlier runs of a migration did, keyed by
+/// `"<source playlist> -> <target provider>"`.
+#[derive(Default, Serialize, Deserialize)]
+struct MigrationState {
+    playlists: BTreeMap<String, MigratedPlaylist>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct MigratedPlaylist {
+    playlist_id: Option<String>,
+    /// The source items whose match is on the target playlist.
+    added: Vec<PlaylistItemId>,
+    matched: usize,
+    unmatched: usize,
+    /// Whether every match is on the target playlist.
+    done: bool,
+}
+
+/// What became of a playlist in this run.
+struct Outcome {
+    status: JobStatus,
+    error: Option<String>,
+    matched: usize,
+    unmatched: usize,
+    playlist_id: Option<String>,
+    items: Option<ParsedPlaylistItems>,
+}
+
+/// The clients shared by every playlist of the migration.
+struct Clients<'a> {
+    source: Box<dyn PlaylistSource>,
+    searcher: Box<dyn TrackSearcher>,
+    sink: Option<Box<dyn PlaylistSink>>,
+    target: &'a dyn Provider,
+    config: ProviderConfig,
+    /// Target items the sink reported it could not add.
+    failed: Arc<Mutex<HashSet<PlaylistItemId>>>,
+}
+
+/// Lists the playlists of a user on the source, lets them pick those to
+/// migrate and recreates each one on the target, as far as the quota allows.
+/// Playlists that do not fit are continued by the next run with the same
+/// state file.
+pub async fn migrate(
+    args: &MigrateArgs,
+    config: ProviderConfig,
+    cache: Arc<MatchCache>,
+    terminal: bool,
+) -> Result<(), Box<dyn Error>> {
+    let registry = Registry::new();
+
+    // Either a provider name, for the authenticated user, or a link to the
+    // profile of a user.
+    let (source, user) = match registry.get(&args.from) {
+        Some(source) => (source, None),
+        None => {
+            let (source, location) = registry.resolve(&args.from)?;
+            (source, Some(location))
+        }
+    };
+    let target = registry
+        .get(&args.to)
+        .ok_or_else(|| format!("unknown provider {}", args.to))?;
+    if !target.capabilities().write {
+        return Err(format!("{} playlists cannot be written", target.display_name()).into());
+    }
+    let fallback = args
+        .fallback
+        .as_deref()
+        .map(|name| {
+            registry
+                .get(name)
+                .ok_or_else(|| format!("unknown provider {}", name))
+        })
+        .transpose()?;
+
+    let mut state: MigrationState = match fs::read_to_string(&args.state) {
+        Ok(json) => serde_json::from_str(&json)?,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => MigrationState::default(),
+        Err(error) => return Err(error.into()),
+    };
+
+    let config = ProviderConfig {
+        search_preference: args.prefer,
+        ..config
+    };
+
+    let mut source_client = source
+        .source(&config)?
+        .ok_or_else(|| format!("{} playlists cannot be read", source.display_name()))?;
+    source_client.authenticate().await?;
+
+    let playlists = source_client
+        .get_playlists(user)
+        .await?
+        .into_iter()
+        .filter(|playlist| is_wanted(args, playlist))
+        .collect::<Vec<_>>();
+    if playlists.is_empty() {
+        return Err(format!("no {} playlists to migrate", source.display_name()).into());
+    }
+
+    let playlists = match args.yes {
+        true => playlists,
+        false => choose(playlists)?,
+    };
+
+    // The sink reports items it could not add instead of failing, so they
+    // are picked out of its events to be tried again by the next run.
+    let failed = Arc::new(Mutex::new(HashSet::new()));
+    let reporter = config.reporter.clone();
+    let failed_items = failed.clone();
+    let sink_config = ProviderConfig {
+        reporter: Arc::new(move |event: &Event| {
+            if let Event::ItemFailed { item, .. } = event {
+                failed_items.lock().unwrap().insert(item.id.clone());
+            }
+            reporter.report(event);
+        }),
+        ..config.clone()
+    };
+
+    let mut clients = Clients {
+        source: source_client,
+        searcher: target_searcher(target, fallback, &config, &cache)?,
+        sink: None,
+        target,
+        config: sink_config,
+        failed,
+    };
+    let mode = format!("{} -> {}", source.display_name(), target.display_name());
+
+    let mut quota = args.quota;
+    let mut outcomes = vec![];
+
+    for (index, playlist) in playlists.iter().enumerate() {
+        let migrated = state
+            .playlists
+            .entry(format!("{} -> {}", playlist.url, target.name()))
+            .or_default();
+
+        let outcome = if migrated.done {
+            Ok(Outcome {
+                status: JobStatus::Done,
+                error: None,
+                matched: migrated.matched,
+                unmatched: migrated.unmatched,
+                playlist_id: migrated.playlist_id.clone(),
+                items: None,
+            })
+        } else if quota == Some(0) {
+            Ok(Outcome {
+                status: JobStatus::Skipped,
+                error: Some("waiting for quota, run again to continue".into()),
+                matched: migrated.matched,
+                unmatched: migrated.unmatched,
+                playlist_id: migrated.playlist_id.clone(),
+                items: None,
+            })
+        } else {
+            if terminal {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "Playlist {}/{}: {}",
+                        index + 1,
+                        playlists.len(),
+                        playlist.name
+                    )
+                    .on_blue()
+                    .black()
+                );
+            }
+
+            let outcome =
+                migrate_playlist(args, &mut clients, playlist, migrated, &mut quota).await;
+
+            if terminal {
+                eprintln!();
+            }
+
+            outcome
+        };
+
+        outcomes.push(outcome.unwrap_or_else(|error| Outcome {
+            status: JobStatus::Failed,
+            error: Some(error.to_string()),
+            matched: 0,
+            unmatched: 0,
+            playlist_id: None,
+            items: None,
+        }));
+
+        fs::write(&args.state, serde_json::to_string_pretty(&state)?)?;
+    }
+
+    let jobs = playlists
+        .iter()
+        .zip(&outcomes)
+        .map(|(playlist, outcome)| JobRecord {
+            from: &playlist.url,
+            to: target.name(),
+            name: Some(&playlist.name),
+            status: outcome.status,
+            error: outcome.error.as_deref(),
+            matched: outcome.matched,
+            unmatched: outcome.unmatched,
+            playlist_id: outcome.playlist_id.as_deref(),
+            report: outcome.items.as_ref().map(|items| {
+                Report::new(&mode, &playlist.url, items, outcome.playlist_id.as_deref())
+            }),
+        })
+        .collect();
+    print_batch_report(args.output, &BatchReport { jobs })?;
+
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.status == JobStatus::Failed)
+        .count();
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} playlists failed", failed, outcomes.len()).into()),
+    }
+}
+
+fn is_wanted(args: &MigrateArgs, playlist: &PlaylistSummary) -> bool {
+    let name = playlist.name.to_lowercase();
+    let mentions = |pattern: &String| name.contains(&pattern.to_lowercase());
+
+    (playlist.owned || !args.owned)
+        && (args.include.is_empty() || args.include.iter().any(mentions))
+        && !args.exclude.iter().any(mentions)
+}
+
+/// Lists the playlists and asks which of them to migrate.
+fn choose(playlists: Vec<PlaylistSummary>) -> Result<Vec<PlaylistSummary>, Box<dyn Error>> {
+    if !std::io::stdin().is_terminal() {
+        return Err(
+            "cannot ask which playlists to migrate because stdin is not a terminal; pass --yes to migrate every playlist listed"
+                .into(),
+        );
+    }
+
+    for (index, playlist) in playlists.iter().enumerate() {
+        let mut details = vec![];
+        if !playlist.owned {
+            details.push("followed".to_string());
+        }
+        if let Some(item_count) = playlist.item_count {
+            details.push(format!("{} items", item_count));
+        }
+
+        match details.is_empty() {
+            true => eprintln!("{:>4}. {}", index + 1, playlist.name),
+            false => eprintln!(
+                "{:>4}. {} {}",
+                index + 1,
+                playlist.name,
+                format!("({})", details.join(", ")).dimmed()
+            ),
+        }
+    }
+    eprintln!();
+
+    loop {
+        let input = prompt("Playlists to migrate, e.g. 1,3-5 (all by default): ")?;
+
+        match parse_selection(&input, playlists.len()) {
+            Ok(selected) => {
+                return Ok(playlists
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| selected.contains(index))
+                    .map(|(_, playlist)| playlist)
+                    .collect())
+            }
+            Err(error) => eprintln!("[ERROR] {}", error),
+        }
+    }
+}
+
+/// Turns "1,3-5" into the zero-based indices it names; nothing means all.
+fn parse_selection(input: &str, count: usize) -> Result<HashSet<usize>, String> {
+    if input.trim().is_empty() {
+        return Ok((0..count).collect());
+    }
+
+    let mut selected = HashSet::new();
+    for part in input
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (start, end) = part.split_once('-').unwrap_or((part, part));
+        let number = |text: &str| match text.trim().parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => Ok(number),
+            _ => Err(format!(
+                "\"{}\" is not a number from 1 to {}",
+                text.trim(),
+                count
+            )),
+        };
+
+        selected.extend(number(start)? - 1..number(end)?);
+    }
+
+    Ok(selected)
+}
+
+/// Recreates a single playlist, or continues it where an earlier run left
+/// off. Only as many items are added as `quota` allows; the rest is left for
+/// the next run. Items the target refused are tried again by the next run
+/// too, which appends them after those added since, out of the source order.
+async fn migrate_playlist(
+    args: &MigrateArgs,
+    clients: &mut Clients<'_>,
+    playlist: &PlaylistSummary,
+    migrated: &mut MigratedPlaylist,
+    quota: &mut Option<u64>,
+) -> Result<Outcome, Box<dyn Error>> {
+    let target = clients.target;
+
+    let mut playlist_items = clients.source.get_playlist_items(&playlist.url).await?;
+    if args.enrich {
+        let mut musicbrainz = MusicBrainz::new();
+        musicbrainz.set_reporter(clients.config.reporter.clone());
+        playlist_items = musicbrainz.enrich(playlist_items).await?;
+    }
+
+    // Matches are remembered in the match cache, so continuing a playlist
+    // does not search for its items again.
+    let playlist_items = clients
+        .searcher
+        .parse_playlist_items(playlist_items)
+        .await?;
+
+    let added = migrated.added.iter().collect::<HashSet<_>>();
+    let pending = playlist_items
+        .items
+        .iter()
+        .filter(|item_match| item_match.target.is_some() && !added.contains(&item_match.source.id))
+        .collect::<Vec<_>>();
+
+    migrated.matched = playlist_items.matched().len();
+    migrated.unmatched = playlist_items.items.len() - migrated.matched;
+
+    // Creating the playlist costs `write_cost(0)`, and every item added to
+    // it as much again as the first one.
+    let create_cost = match migrated.playlist_id {
+        Some(_) => 0,
+        None => target.write_cost(0),
+    };
+    let item_cost = (target.write_cost(1) - target.write_cost(0)).max(1);
+    let fitting = match *quota {
+        Some(left) if left < create_cost => 0,
+        Some(left) => pending
+            .len()
+            .min(((left - create_cost) / item_cost) as usize),
+        None => pending.len(),
+    };
+
+    let mut outcome = Outcome {
+        status: JobStatus::Done,
+        error: None,
+        matched: migrated.matched,
+        unmatched: migrated.unmatched,
+        playlist_id: migrated.playlist_id.clone(),
+        items: None,
+    };
+
+    if fitting == 0 && !pending.is_empty() {
+        *quota = Some(0);
+        outcome.status = JobStatus::Skipped;
+        outcome.error = Some("waiting for quota, run again to continue".into());
+        outcome.items = Some(playlist_items);
+
+        return Ok(outcome);
+    }
+
+    let batch = &pending[..fitting];
+    let targets = batch
+        .iter()
+        .filter_map(|item_match| item_match.target.clone())
+        .collect::<Vec<_>>();
+
+    if migrated.playlist_id.is_none() || !targets.is_empty() {
+        if clients.sink.is_none() {
+            let mut sink = target
+                .sink(&clients.config)?
+                .ok_or_else(|| format!("{} playlists cannot be written", target.display_name()))?;
+            sink.authenticate().await?;
+            clients.sink = Some(sink);
+        }
+        let sink = clients.sink.as_ref().unwrap();
+
+        clients.failed.lock().unwrap().clear();
+
+        match &migrated.playlist_id {
+            Some(playlist_id) => sink.add_items(playlist_id, &targets).await?,
+            None => {
+                let description = match &playlist.description {
+                    Some(description) => Some(description.clone()),
+                    None => clients.source.get_description(&playlist.url).await?,
+                };
+                let options = PlaylistOptions {
+                    name: playlist.name.clone(),
+                    description,
+                    privacy: args.privacy,
+                };
+                migrated.playlist_id = Some(sink.create_playlist(&options, &targets).await?);
+            }
+        }
+
+        if let Some(left) = quota {
+            *left = left.saturating_sub(create_cost + item_cost * fitting as u64);
+        }
+    }
+
+    let failed = clients.failed.lock().unwrap().clone();
+    let now_added = batch
+        .iter()
+        .filter(|item_match| {
+            item_match
+                .target
+                .as_ref()
+                .is_some_and(|target| !failed.contains(&target.id))
+        })
+        .map(|item_match| item_match.source.id.clone())
+        .collect::<Vec<_>>();
+
+    let left_out = pending.len() - now_added.len();
+    migrated.added.extend(now_added);
+    migrated.done = left_out == 0;
+
+    outcome.playlist_id = migrated.playlist_id.clone();
+    if !migrated.done {
+        outcome.status = JobStatus::Skipped;
+        outcome.error = Some(format!(
+            "{} of {} items still to add, run again to continue",
+            left_out, migrated.matched
+        ));
+
+        // Failures are most likely the quota running out early.
+        if !failed.is_empty() {
+            *quota = Some(0);
+        }
+    }
+    outcome.items = Some(playlist_items);
+
+    Ok(outcome)
+}
@@ -0,0 +1,259 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+use crate::error::Result;
+use crate::events::{quiet, Event, SharedReporter};
+use crate::matching::durations_match;
+use crate::types::{
+    CanonicalTrack, MusicBrainzRecording, MusicBrainzRecordings, PlaylistItem, PlaylistItemKind,
+};
+
+const API_URL: &str = "https://musicbrainz.org/ws/2";
+const USER_AGENT: &str = concat!("linksen/", env!("CARGO_PKG_VERSION"));
+/// MusicBrainz allows one request per second and client.
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// Search results scoring lower than this are too unlike the item to trust.
+const MIN_SCORE: u32 = 90;
+
+/// Looks items up in MusicBrainz and attaches the recording they are a copy
+/// of: its MBID, ISRC and the title, artists and release MusicBrainz
+/// credits. Items are found by MBID or ISRC if they have one, or else by
+/// searching for their artist and title, preferring the duration they have.
+pub struct MusicBrainz {
+    http: reqwest::Client,
+    api_url: String,
+    request_interval: Duration,
+    last_request: Mutex<Option<Instant>>,
+    reporter: SharedReporter,
+}
+
+impl MusicBrainz {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: API_URL.into(),
+            request_interval: REQUEST_INTERVAL,
+            last_request: Mutex::new(None),
+            reporter: quiet(),
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+
+    /// Sends the requests to another server than musicbrainz.org, e.g. a
+    /// mirror or a local stand-in of the API.
+    pub fn set_base_url(&mut self, api_url: String) {
+        self.api_url = api_url;
+    }
+
+    /// How long to wait between requests, one second by default as
+    /// musicbrainz.org asks. Mirrors may allow more.
+    pub fn set_request_interval(&mut self, request_interval: Duration) {
+        self.request_interval = request_interval;
+    }
+
+    /// Enriches every item MusicBrainz knows, and keeps the others as they
+    /// are.
+    pub async fn enrich(&self, playlist_items: Vec<PlaylistItem>) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::EnrichmentStarted {
+            total: playlist_items.len(),
+        });
+
+        let mut enriched_items = Vec::with_capacity(playlist_items.len());
+        let mut enriched = 0;
+
+        for mut playlist_item in playlist_items {
+            if let Some(recording) = self.find_recording(&playlist_item).await? {
+                attach_recording(&mut playlist_item, recording);
+                enriched += 1;
+            }
+
+            self.reporter.report(&Event::ItemEnriched {
+                item: playlist_item.clone(),
+            });
+            enriched_items.push(playlist_item);
+        }
+
+        self.reporter
+            .report(&Event::EnrichmentFinished { enriched });
+
+        Ok(enriched_items)
+    }
+
+    async fn find_recording(
+        &self,
+        playlist_item: &PlaylistItem,
+    ) -> Result<Option<MusicBrainzRecording>> {
+        // Podcast episodes are not in MusicBrainz.
+        if playlist_item.kind == PlaylistItemKind::Episode {
+            return Ok(None);
+        }
+
+        let inc = ("inc", "artist-credits+releases+isrcs");
+
+        if let Some(mbid) = &playlist_item.mbid {
+            let url = format!("{}/recording/{}", self.api_url, mbid);
+
+            if let Some(recording) = self.get(&url, &[inc]).await? {
+                return Ok(Some(recording));
+            }
+        }
+
+        if let Some(isrc) = &playlist_item.isrc {
+            let url = format!("{}/isrc/{}", self.api_url, isrc);
+            let found: Option<MusicBrainzRecordings> = self
+                .get(&url, &[("inc", "artist-credits+releases")])
+                .await?;
+
+            if let Some(recording) = found.and_then(|found| found.recordings.into_iter().next()) {
+                return Ok(Some(recording));
+            }
+        }
+
+        self.search_recording(playlist_item).await
+    }
+
+    async fn search_recording(
+        &self,
+        playlist_item: &PlaylistItem,
+    ) -> Result<Option<MusicBrainzRecording>> {
+        let mut query = format!("recording:\"{}\"", escape_query(&playlist_item.name));
+
+        // Items credit their artists in all sorts of ways; the first one is
+        // the most likely to be spelled the way MusicBrainz does.
+        let artist = playlist_item
+            .artists
+            .split([',', '&'])
+            .next()
+            .unwrap_or_default()
+            .trim();
+        if !artist.is_empty() {
+            query.push_str(&format!(" AND artist:\"{}\"", escape_query(artist)));
+        }
+
+        let url = format!("{}/recording", self.api_url);
+        let found: Option<MusicBrainzRecordings> = self
+            .get(&url, &[("query", &query), ("limit", "10")])
+            .await?;
+
+        let mut recordings = found
+            .map(|found| found.recordings)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|recording| recording.score.unwrap_or_default() >= MIN_SCORE)
+            .collect::<Vec<_>>();
+
+        // Results are sorted by score, so the first one of the right length
+        // is the best.
+        let position = playlist_item.duration.and_then(|duration| {
+            recordings.iter().position(|recording| {
+                recording
+                    .length
+                    .is_some_and(|length| durations_match(Duration::from_millis(length), duration))
+            })
+        });
+
+        Ok(match position {
+            Some(position) => Some(recordings.swap_remove(position)),
+            None if playlist_item.duration.is_none() => recordings.into_iter().next(),
+            None => None,
+        })
+    }
+
+    /// Answers `None` when MusicBrainz does not know what was asked for.
+    async fn get<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Option<T>> {
+        self.throttle().await;
+
+        let response = self
+            .http
+            .get(url)
+            .header("User-Agent", USER_AGENT)
+            .header("Accept", "application/json")
+            .query(&[("fmt", "json")])
+            .query(query)
+            .send()
+            .await?;
+
+        // An MBID or ISRC that is malformed is answered with 400.
+        if matches!(
+            response.status(),
+            StatusCode::NOT_FOUND | StatusCode::BAD_REQUEST
+        ) {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+
+    async fn throttle(&self) {
+        let wait = {
+            let mut last_request = self.last_request.lock().unwrap();
+            let now = Instant::now();
+            let next_request =
+                last_request.map_or(now, |last| (last + self.request_interval).max(now));
+            *last_request = Some(next_request);
+
+            next_request - now
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl Default for MusicBrainz {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn attach_recording(playlist_item: &mut PlaylistItem, recording: MusicBrainzRecording) {
+    let artist_credit = recording
+        .artist_credit
+        .iter()
+        .map(|credit| format!("{}{}", credit.name, credit.joinphrase))
+        .collect::<String>();
+    let release = recording.releases.into_iter().next();
+
+    playlist_item.mbid = Some(recording.id);
+    playlist_item.isrc = playlist_item
+        .isrc
+        .take()
+        .or(recording.isrcs.into_iter().next());
+    playlist_item.duration = playlist_item
+        .duration
+        .or(recording.length.map(Duration::from_millis));
+    playlist_item.canonical = Some(CanonicalTrack {
+        title: recording.title,
+        artist_credit,
+        release_mbid: release.as_ref().map(|release| release.id.clone()),
+        release: release.map(|release| release.title),
+    });
+}
+
+/// Escapes the characters that mean something to the Lucene query syntax of
+/// MusicBrainz searches.
+fn escape_query(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if "+-&|!(){}[]^\"~*?:\\/".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
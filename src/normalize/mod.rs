@@ -0,0 +1,227 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+const NOISE_KEYWORDS: &str = r"official|video|audio|lyrics?|visuali[sz]er|hd|hq|4k|8k|1080p|720p|remaster(ed)?|m/?v|color coded";
+
+static VEVO: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\s*vevo$").unwrap());
+static CAMEL_CASE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([a-z])([A-Z])").unwrap());
+static BRACKETED_NOISE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)\s*[\(\[][^\(\)\[\]]*\b({})\b[^\(\)\[\]]*[\)\]]",
+        NOISE_KEYWORDS
+    ))
+    .unwrap()
+});
+static TRAILING_NOISE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\s*[-|]\s*(official\s+(music\s+)?(video|audio)|lyrics?(\s+video)?|(\d{4}\s+)?remaster(ed)?(\s+\d{4})?|hd|hq|4k)\s*$",
+    )
+    .unwrap()
+});
+static QUALITY_SUFFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\s+\b(hd|hq|4k)$").unwrap());
+static FEATURING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\s*[\(\[]?\b(feat\.?|ft\.?|featuring)\s+([^\(\)\[\]]+?)[\)\]]?\s*$").unwrap()
+});
+static ARTIST_SEPARATOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*(,|&|\band\b)\s*").unwrap());
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedTrack {
+    pub artist: String,
+    pub title: String,
+    pub featured_artists: Vec<String>,
+}
+
+/// Turns a YouTube video title and its channel name into an artist/title pair,
+/// e.g. "Artist - Song (Official Music Video) [4K]" by "ArtistVEVO" becomes
+/// "Artist" and "Song".
+pub fn normalize_youtube_video(video_title: &str, channel_title: &str) -> NormalizedTrack {
+    let mut featured_artists = vec![];
+
+    let video_title = strip_noise(video_title);
+
+    let (artist, title) = match split_artist_title(&video_title) {
+        Some((artist, title)) => (artist.to_string(), title.to_string()),
+        None => (normalize_channel(channel_title), video_title.clone()),
+    };
+
+    let artist = strip_featured_artists(&artist, &mut featured_artists);
+    let title = strip_featured_artists(&title, &mut featured_artists);
+
+    NormalizedTrack {
+        artist: collapse_whitespace(&artist),
+        title: collapse_whitespace(&title),
+        featured_artists,
+    }
+}
+
+/// Removes the "VEVO" and " - Topic" decorations YouTube adds to artist channels.
+pub fn normalize_channel(channel_title: &str) -> String {
+    let channel_title = channel_title.trim();
+
+    if let Some(artist) = channel_title.strip_suffix(" - Topic") {
+        return artist.trim().to_string();
+    }
+
+    if VEVO.is_match(channel_title) {
+        let artist = VEVO.replace(channel_title, "");
+
+        // "TaylorSwiftVEVO" -> "Taylor Swift"
+        if !artist.contains(' ') {
+            return CAMEL_CASE.replace_all(&artist, "$1 $2").to_string();
+        }
+
+        return artist.to_string();
+    }
+
+    channel_title.to_string()
+}
+
+/// Builds a field-scoped Spotify search query, e.g. `track:"Song" artist:"Artist"`.
+pub fn spotify_query(artist: &str, title: &str) -> String {
+    let artist = artist.replace('"', "");
+    let title = title.replace('"', "");
+
+    if artist.trim().is_empty() {
+        return format!("track:\"{}\"", title.trim());
+    }
+
+    format!("track:\"{}\" artist:\"{}\"", title.trim(), artist.trim())
+}
+
+fn strip_noise(title: &str) -> String {
+    let title = BRACKETED_NOISE.replace_all(title, "");
+    let title = TRAILING_NOISE.replace_all(&title, "");
+    let title = QUALITY_SUFFIX.replace_all(&title, "");
+
+    title.trim().to_string()
+}
+
+fn split_artist_title(title: &str) -> Option<(&str, &str)> {
+    for separator in [" - ", " – ", " — ", " -- ", " ~ "] {
+        if let Some((artist, title)) = title.split_once(separator) {
+            if !artist.trim().is_empty() && !title.trim().is_empty() {
+                return Some((artist.trim(), title.trim()));
+            }
+        }
+    }
+
+    None
+}
+
+fn strip_featured_artists(text: &str, featured_artists: &mut Vec<String>) -> String {
+    match FEATURING.captures(text) {
+        Some(captures) => {
+            let artists = captures.get(2).unwrap().as_str();
+            featured_artists.extend(
+                ARTIST_SEPARATOR
+                    .split(artists)
+                    .filter(|artist| !artist.is_empty())
+                    .map(|artist| artist.trim().to_string()),
+            );
+
+            text[..captures.get(0).unwrap().start()].to_string()
+        }
+        None => text.to_string(),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_titles_and_strips_noise() {
+        let track = normalize_youtube_video(
+            "Daft Punk - One More Time (Official Video) [4K]",
+            "DaftPunkVEVO",
+        );
+
+        assert_eq!(track.artist, "Daft Punk");
+        assert_eq!(track.title, "One More Time");
+        assert!(track.featured_artists.is_empty());
+    }
+
+    #[test]
+    fn takes_the_artist_from_the_channel() {
+        let track = normalize_youtube_video("One More Time | Official Audio", "Daft Punk - Topic");
+
+        assert_eq!(track.artist, "Daft Punk");
+        assert_eq!(track.title, "One More Time");
+    }
+
+    #[test]
+    fn collects_featured_artists() {
+        let track = normalize_youtube_video(
+            "Daft Punk ft. Pharrell Williams & Nile Rodgers - Get Lucky",
+            "",
+        );
+        assert_eq!(track.artist, "Daft Punk");
+        assert_eq!(track.title, "Get Lucky");
+        assert_eq!(
+            track.featured_artists,
+            ["Pharrell Williams", "Nile Rodgers"]
+        );
+
+        let track = normalize_youtube_video("Daft Punk - Get Lucky (feat. Pharrell Williams)", "");
+        assert_eq!(track.title, "Get Lucky");
+        assert_eq!(track.featured_artists, ["Pharrell Williams"]);
+    }
+
+    #[test]
+    fn removes_channel_decorations() {
+        assert_eq!(normalize_channel("TaylorSwiftVEVO"), "Taylor Swift");
+        assert_eq!(normalize_channel("Taylor Swift VEVO"), "Taylor Swift");
+        assert_eq!(normalize_channel("Daft Punk - Topic"), "Daft Punk");
+        assert_eq!(normalize_channel("Daft Punk"), "Daft Punk");
+    }
+
+    #[test]
+    fn strips_bracketed_and_trailing_noise() {
+        assert_eq!(
+            strip_noise("Around the World (Official Music Video)"),
+            "Around the World"
+        );
+        assert_eq!(
+            strip_noise("Around the World [HD Remastered]"),
+            "Around the World"
+        );
+        assert_eq!(
+            strip_noise("Around the World - Lyrics Video"),
+            "Around the World"
+        );
+        assert_eq!(strip_noise("Around the World HQ"), "Around the World");
+        assert_eq!(
+            strip_noise("Around the World (Live)"),
+            "Around the World (Live)"
+        );
+    }
+
+    #[test]
+    fn splits_on_dashes_only_between_words() {
+        assert_eq!(
+            split_artist_title("Daft Punk – Around the World"),
+            Some(("Daft Punk", "Around the World"))
+        );
+        assert_eq!(split_artist_title(" - Around the World"), None);
+        assert_eq!(split_artist_title("Around the World"), None);
+    }
+
+    #[test]
+    fn builds_field_scoped_queries() {
+        assert_eq!(
+            spotify_query("Daft Punk", "\"Around\" the World"),
+            "track:\"Around the World\" artist:\"Daft Punk\""
+        );
+        assert_eq!(
+            spotify_query(" ", "Around the World"),
+            "track:\"Around the World\""
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use reqwest::Url;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use crate::error::{Error, Result};
+
+/// The directory linksen keeps its tokens in, created on first use.
+pub(crate) fn data_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_local_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no local data directory"))?
+        .join("linksen");
+
+    fs::create_dir_all(&data_dir)?;
+
+    Ok(data_dir)
+}
+
+pub(crate) fn read_cached_token(name: &str) -> Option<String> {
+    let token = fs::read_to_string(data_dir().ok()?.join(name)).ok()?;
+    let token = token.trim();
+
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+pub(crate) fn write_cached_token(name: &str, token: &str) -> Result<()> {
+    fs::write(data_dir()?.join(name), token)?;
+
+    Ok(())
+}
+
+/// Listens on the address of `redirect_uri` until the provider redirects the
+/// browser back to it, and returns the query parameters of that request.
+pub(crate) async fn wait_for_redirect(redirect_uri: &str) -> Result<HashMap<String, String>> {
+    let redirect_uri =
+        Url::parse(redirect_uri).map_err(|_| Error::InvalidUrl(redirect_uri.to_string()))?;
+
+    let host = redirect_uri.host_str().unwrap_or("localhost");
+    let port = redirect_uri.port_or_known_default().unwrap_or(80);
+    let listener = TcpListener::bind((host, port)).await?;
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+
+        let mut request = vec![0; 8192];
+        let length = stream.read(&mut request).await?;
+        let request = String::from_utf8_lossy(&request[..length]);
+
+        // "GET /callback?code=... HTTP/1.1"
+        let target = request.split_whitespace().nth(1).unwrap_or("/");
+        let url = redirect_uri
+            .join(target)
+            .map_err(|_| Error::UnexpectedResponse(format!("invalid redirect: {}", target)))?;
+
+        if url.path() != redirect_uri.path() {
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .await?;
+            continue;
+        }
+
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\n\
+                  linksen has been authorized, you can close this window.",
+            )
+            .await?;
+
+        return Ok(url.query_pairs().into_owned().collect());
+    }
+}
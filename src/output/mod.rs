@@ -0,0 +1,460 @@
+use clap::ValueEnum;
+use colored::Colorize;
+use linksen::{ItemMatch, ParsedPlaylistItems, PlaylistItem, PlaylistItemKind};
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum OutputFormat {
+    Plain,
+    Table,
+    Json,
+    Jsonl,
+}
+
+/// Everything linksen knows at the end of a conversion.
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub mode: &'a str,
+    pub source_url: &'a str,
+    pub playlist_id: Option<&'a str>,
+    pub items: Vec<ItemRecord<'a>>,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(
+        mode: &'a str,
+        source_url: &'a str,
+        playlist_items: &'a ParsedPlaylistItems,
+        playlist_id: Option<&'a str>,
+    ) -> Self {
+        Self {
+            mode,
+            source_url,
+            playlist_id,
+            items: playlist_items.items.iter().map(ItemRecord::new).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ItemRecord<'a> {
+    pub source: ItemView<'a>,
+    pub matched: bool,
+    pub confidence: f32,
+    pub target: Option<ItemView<'a>>,
+}
+
+impl<'a> ItemRecord<'a> {
+    fn new(item_match: &'a ItemMatch) -> Self {
+        Self {
+            source: ItemView::new(&item_match.source),
+            matched: item_match.target.is_some(),
+            confidence: item_match.confidence,
+            target: item_match.target.as_ref().map(ItemView::new),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ItemView<'a> {
+    #[serde(flatten)]
+    pub item: &'a PlaylistItem,
+    pub url: String,
+}
+
+impl<'a> ItemView<'a> {
+    fn new(item: &'a PlaylistItem) -> Self {
+        Self {
+            item,
+            url: item.id.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonLine<'a> {
+    Item(&'a ItemRecord<'a>),
+    Summary {
+        mode: &'a str,
+        source_url: &'a str,
+        playlist_id: Option<&'a str>,
+        matched: usize,
+        unmatched: usize,
+    },
+}
+
+pub fn print_report(format: OutputFormat, report: &Report) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    match format {
+        OutputFormat::Plain => print_plain(&mut stdout, report),
+        OutputFormat::Table => print_table(&mut stdout, report),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, report)?;
+            writeln!(stdout)
+        }
+        OutputFormat::Jsonl => {
+            for item in &report.items {
+                serde_json::to_writer(&mut stdout, &JsonLine::Item(item))?;
+                writeln!(stdout)?;
+            }
+
+            let matched = report.items.iter().filter(|item| item.matched).count();
+            serde_json::to_writer(
+                &mut stdout,
+                &JsonLine::Summary {
+                    mode: report.mode,
+                    source_url: report.source_url,
+                    playlist_id: report.playlist_id,
+                    matched,
+                    unmatched: report.items.len() - matched,
+                },
+            )?;
+            writeln!(stdout)
+        }
+    }
+}
+
+fn print_plain(out: &mut impl Write, report: &Report) -> io::Result<()> {
+    // A created playlist speaks for itself, only the leftovers are listed.
+    if report.playlist_id.is_none() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Playlist items".on_green().black())?;
+
+        for target in report.items.iter().filter_map(|item| item.target.as_ref()) {
+            writeln!(out, "{}: {}", target.item.name.green(), target.url.blue())?;
+        }
+    }
+
+    let unmatched = report
+        .items
+        .iter()
+        .filter(|item| !item.matched)
+        .collect::<Vec<_>>();
+
+    if unmatched.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", "Unmatched items".on_red().white())?;
+
+    for item in unmatched {
+        let kind = match item.source.item.kind {
+            PlaylistItemKind::Track => "track",
+            PlaylistItemKind::Episode => "episode",
+        };
+
+        writeln!(
+            out,
+            "{} - {} ({}): {}",
+            item.source.item.artists.red(),
+            item.source.item.name.red(),
+            kind,
+            item.source.url.blue()
+        )?;
+    }
+
+    Ok(())
+}
+
+fn print_table(out: &mut impl Write, report: &Report) -> io::Result<()> {
+    let rows = report
+        .items
+        .iter()
+        .map(|item| {
+            [
+                if item.matched { "matched" } else { "unmatched" }.to_string(),
+                format!("{:.2}", item.confidence),
+                format!("{} - {}", item.source.item.artists, item.source.item.name),
+                item.target
+                    .as_ref()
+                    .map(|target| target.url.clone())
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    write_table(out, ["STATUS", "CONFIDENCE", "SOURCE", "TARGET"], &rows)?;
+
+    if let Some(playlist_id) = report.playlist_id {
+        writeln!(out)?;
+        writeln!(out, "Created playlist: {}", playlist_id)?;
+    }
+
+    Ok(())
+}
+
+/// The counterparts of a single link on other providers.
+#[derive(Serialize)]
+pub struct LinkReport<'a> {
+    pub source: ItemView<'a>,
+    pub links: Vec<LinkRecord<'a>>,
+}
+
+impl<'a> LinkReport<'a> {
+    pub fn new(
+        source: &'a PlaylistItem,
+        links: &'a [(&'a str, Result<ItemMatch, String>)],
+    ) -> Self {
+        Self {
+            source: ItemView::new(source),
+            links: links
+                .iter()
+                .map(|(provider, link)| LinkRecord::new(provider, link))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LinkRecord<'a> {
+    pub provider: &'a str,
+    pub matched: bool,
+    pub confidence: f32,
+    pub target: Option<ItemView<'a>>,
+    pub error: Option<&'a str>,
+}
+
+impl<'a> LinkRecord<'a> {
+    fn new(provider: &'a str, link: &'a Result<ItemMatch, String>) -> Self {
+        match link {
+            Ok(item_match) => Self {
+                provider,
+                matched: item_match.target.is_some(),
+                confidence: item_match.confidence,
+                target: item_match.target.as_ref().map(ItemView::new),
+                error: None,
+            },
+            Err(error) => Self {
+                provider,
+                matched: false,
+                confidence: 0.0,
+                target: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+pub fn print_link_report(format: OutputFormat, report: &LinkReport) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    match format {
+        OutputFormat::Plain => {
+            writeln!(
+                stdout,
+                "{} - {}: {}",
+                report.source.item.artists.green(),
+                report.source.item.name.green(),
+                report.source.url.blue()
+            )?;
+
+            for link in &report.links {
+                match (&link.target, link.error) {
+                    (Some(target), _) => writeln!(
+                        stdout,
+                        "{}: {} ({:.0}%)",
+                        link.provider,
+                        target.url.blue(),
+                        link.confidence * 100.0
+                    )?,
+                    (None, Some(error)) => writeln!(stdout, "{}: {}", link.provider, error.red())?,
+                    (None, None) => writeln!(stdout, "{}: {}", link.provider, "no match".red())?,
+                }
+            }
+
+            Ok(())
+        }
+        OutputFormat::Table => {
+            let rows = report
+                .links
+                .iter()
+                .map(|link| {
+                    [
+                        link.provider.to_string(),
+                        format!("{:.2}", link.confidence),
+                        match (&link.target, link.error) {
+                            (Some(target), _) => target.url.clone(),
+                            (None, Some(error)) => error.to_string(),
+                            (None, None) => "no match".to_string(),
+                        },
+                    ]
+                })
+                .collect::<Vec<_>>();
+
+            write_table(&mut stdout, ["PROVIDER", "CONFIDENCE", "TARGET"], &rows)
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, report)?;
+            writeln!(stdout)
+        }
+        OutputFormat::Jsonl => {
+            for link in &report.links {
+                serde_json::to_writer(&mut stdout, link)?;
+                writeln!(stdout)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn write_table<const N: usize>(
+    out: &mut impl Write,
+    header: [&str; N],
+    rows: &[[String; N]],
+) -> io::Result<()> {
+    let header = header.map(String::from);
+
+    let mut widths = header.clone().map(|column| column.chars().count());
+    for row in rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(column, width)| format!("{:<width$}", column, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        writeln!(out, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// The outcome of every job of `linksen batch`.
+#[derive(Serialize)]
+pub struct BatchReport<'a> {
+    pub jobs: Vec<JobRecord<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct JobRecord<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+    pub name: Option<&'a str>,
+    pub status: JobStatus,
+    pub error: Option<&'a str>,
+    pub matched: usize,
+    pub unmatched: usize,
+    pub playlist_id: Option<&'a str>,
+    /// The same as the report of `convert`, for jobs that got as far as
+    /// matching.
+    pub report: Option<Report<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Done,
+    /// Matched, but not written as that would have gone over the quota.
+    Skipped,
+    Failed,
+}
+
+impl JobStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Done => "done",
+            JobStatus::Skipped => "skipped",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+pub fn print_batch_report(format: OutputFormat, report: &BatchReport) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    match format {
+        OutputFormat::Plain => {
+            for job in &report.jobs {
+                let status = match job.status {
+                    JobStatus::Done => job.status.as_str().green(),
+                    JobStatus::Skipped => job.status.as_str().yellow(),
+                    JobStatus::Failed => job.status.as_str().red(),
+                };
+                write!(
+                    stdout,
+                    "{} {} -> {}: {} matched, {} unmatched",
+                    status, job.from, job.to, job.matched, job.unmatched
+                )?;
+                if let Some(playlist_id) = job.playlist_id {
+                    write!(stdout, ", playlist {}", playlist_id.blue())?;
+                }
+                match job.error {
+                    Some(error) => writeln!(stdout, " ({})", error.red())?,
+                    None => writeln!(stdout)?,
+                }
+            }
+
+            let count = |status| {
+                report
+                    .jobs
+                    .iter()
+                    .filter(|job| job.status == status)
+                    .count()
+            };
+            writeln!(stdout)?;
+            writeln!(
+                stdout,
+                "{} jobs: {} done, {} skipped, {} failed",
+                report.jobs.len(),
+                count(JobStatus::Done),
+                count(JobStatus::Skipped),
+                count(JobStatus::Failed)
+            )
+        }
+        OutputFormat::Table => {
+            let rows = report
+                .jobs
+                .iter()
+                .map(|job| {
+                    [
+                        job.status.as_str().to_string(),
+                        job.from.to_string(),
+                        job.to.to_string(),
+                        job.matched.to_string(),
+                        job.unmatched.to_string(),
+                        job.error
+                            .or(job.playlist_id)
+                            .unwrap_or_default()
+                            .to_string(),
+                    ]
+                })
+                .collect::<Vec<_>>();
+
+            write_table(
+                &mut stdout,
+                [
+                    "STATUS",
+                    "SOURCE",
+                    "TARGET",
+                    "MATCHED",
+                    "UNMATCHED",
+                    "RESULT",
+                ],
+                &rows,
+            )
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, report)?;
+            writeln!(stdout)
+        }
+        OutputFormat::Jsonl => {
+            for job in &report.jobs {
+                serde_json::to_writer(&mut stdout, job)?;
+                writeln!(stdout)?;
+            }
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,641 @@
+use std::{fs, io::Write, path::Path, str::FromStr, time::Duration};
+
+use async_trait::async_trait;
+use quick_xml::{escape::escape, events::Event as XmlEvent, Reader as XmlReader};
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::events::{quiet, Event, SharedReporter};
+use crate::registry::{Capabilities, Provider, ProviderConfig};
+use crate::types::{MusicClient, PlaylistItem, PlaylistItemId, PlaylistItemKind, PlaylistSource};
+
+/// Reads playlists from local M3U, XSPF, CSV and JSON files. The "URL" of a
+/// playlist is the path of the file.
+pub struct PlaylistFile {
+    format: Option<PlaylistFileFormat>,
+    reporter: SharedReporter,
+}
+
+impl PlaylistFile {
+    pub fn new() -> Self {
+        Self {
+            format: None,
+            reporter: quiet(),
+        }
+    }
+
+    /// Reads every file in the given format instead of guessing it from the
+    /// file extension.
+    pub fn with_format(format: PlaylistFileFormat) -> Self {
+        Self {
+            format: Some(format),
+            reporter: quiet(),
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+}
+
+impl Default for PlaylistFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Registers playlist files, which are recognised by being existing files.
+pub struct PlaylistFileProvider;
+
+impl Provider for PlaylistFileProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Playlist file"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            ..Default::default()
+        }
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        Path::new(location).is_file()
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        let mut playlist_file = match config.playlist_file_format {
+            Some(format) => PlaylistFile::with_format(format),
+            None => PlaylistFile::new(),
+        };
+        playlist_file.set_reporter(config.reporter.clone());
+
+        Ok(Some(Box::new(playlist_file)))
+    }
+}
+
+impl MusicClient for PlaylistFile {}
+
+#[async_trait]
+impl PlaylistSource for PlaylistFile {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::LoadingStarted);
+
+        let path = Path::new(url);
+        let format = self
+            .format
+            .or_else(|| PlaylistFileFormat::from_path(path))
+            .ok_or_else(|| Error::InvalidUrl(url.to_string()))?;
+
+        let contents = fs::read_to_string(path)?;
+        let playlist_items = read_playlist(format, &contents)?;
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistFileFormat {
+    M3u,
+    Xspf,
+    Csv,
+    Json,
+}
+
+impl PlaylistFileFormat {
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+
+        match extension.to_lowercase().as_str() {
+            "m3u" | "m3u8" => Some(PlaylistFileFormat::M3u),
+            "xspf" => Some(PlaylistFileFormat::Xspf),
+            "csv" => Some(PlaylistFileFormat::Csv),
+            "json" => Some(PlaylistFileFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for PlaylistFileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "m3u" | "m3u8" => Ok(PlaylistFileFormat::M3u),
+            "xspf" => Ok(PlaylistFileFormat::Xspf),
+            "csv" => Ok(PlaylistFileFormat::Csv),
+            "json" => Ok(PlaylistFileFormat::Json),
+            _ => Err(format!("unknown playlist file format \"{}\"", s)),
+        }
+    }
+}
+
+pub fn read_playlist(format: PlaylistFileFormat, contents: &str) -> Result<Vec<PlaylistItem>> {
+    match format {
+        PlaylistFileFormat::M3u => Ok(read_m3u(contents)),
+        PlaylistFileFormat::Xspf => read_xspf(contents),
+        PlaylistFileFormat::Csv => read_csv(contents),
+        PlaylistFileFormat::Json => Ok(serde_json::from_str(contents)?),
+    }
+}
+
+pub fn write_playlist(
+    format: PlaylistFileFormat,
+    playlist_items: &[PlaylistItem],
+    out: &mut impl Write,
+) -> Result<()> {
+    match format {
+        PlaylistFileFormat::M3u => write_m3u(playlist_items, out),
+        PlaylistFileFormat::Xspf => write_xspf(playlist_items, out),
+        PlaylistFileFormat::Csv => write_csv(playlist_items, out),
+        PlaylistFileFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, playlist_items)?;
+            writeln!(out)?;
+
+            Ok(())
+        }
+    }
+}
+
+fn write_m3u(playlist_items: &[PlaylistItem], out: &mut impl Write) -> Result<()> {
+    writeln!(out, "#EXTM3U")?;
+
+    for playlist_item in playlist_items {
+        let seconds = playlist_item
+            .duration
+            .map_or(-1, |duration| duration.as_secs() as i64);
+
+        if playlist_item.artists.is_empty() {
+            writeln!(out, "#EXTINF:{},{}", seconds, playlist_item.name)?;
+        } else {
+            writeln!(
+                out,
+                "#EXTINF:{},{} - {}",
+                seconds, playlist_item.artists, playlist_item.name
+            )?;
+        }
+        // Neither directive is part of any M3U standard, but players skip
+        // comments they do not know.
+        if let Some(album) = &playlist_item.album {
+            writeln!(out, "#EXTALB:{}", album)?;
+        }
+        if let Some(isrc) = &playlist_item.isrc {
+            writeln!(out, "#EXTISRC:{}", isrc)?;
+        }
+        writeln!(out, "{}", playlist_item.id)?;
+    }
+
+    Ok(())
+}
+
+fn write_xspf(playlist_items: &[PlaylistItem], out: &mut impl Write) -> Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<playlist version="1" xmlns="http://xspf.org/ns/0/">"#
+    )?;
+    writeln!(out, "  <trackList>")?;
+
+    for playlist_item in playlist_items {
+        writeln!(out, "    <track>")?;
+        writeln!(
+            out,
+            "      <location>{}</location>",
+            escape(xspf_location(&playlist_item.id))
+        )?;
+        writeln!(out, "      <title>{}</title>", escape(&playlist_item.name))?;
+        writeln!(
+            out,
+            "      <creator>{}</creator>",
+            escape(&playlist_item.artists)
+        )?;
+        if let Some(album) = &playlist_item.album {
+            writeln!(out, "      <album>{}</album>", escape(album))?;
+        }
+        if let Some(duration) = playlist_item.duration {
+            writeln!(out, "      <duration>{}</duration>", duration.as_millis())?;
+        }
+        if let Some(isrc) = &playlist_item.isrc {
+            writeln!(
+                out,
+                "      <identifier>urn:isrc:{}</identifier>",
+                escape(isrc)
+            )?;
+        }
+        if playlist_item.kind == PlaylistItemKind::Episode {
+            writeln!(out, "      <annotation>episode</annotation>")?;
+        }
+        writeln!(out, "    </track>")?;
+    }
+
+    writeln!(out, "  </trackList>")?;
+    writeln!(out, "</playlist>")?;
+
+    Ok(())
+}
+
+fn write_csv(playlist_items: &[PlaylistItem], out: &mut impl Write) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer
+        .write_record([
+            "url",
+            "kind",
+            "name",
+            "artists",
+            "duration_ms",
+            "handle",
+            "album",
+            "isrc",
+        ])
+        .map_err(std::io::Error::from)?;
+
+    for playlist_item in playlist_items {
+        let kind = match playlist_item.kind {
+            PlaylistItemKind::Track => "track",
+            PlaylistItemKind::Episode => "episode",
+        };
+        let duration = playlist_item
+            .duration
+            .map(|duration| duration.as_millis().to_string())
+            .unwrap_or_default();
+
+        writer
+            .write_record([
+                playlist_item.id.to_string().as_str(),
+                kind,
+                &playlist_item.name,
+                &playlist_item.artists,
+                &duration,
+                &playlist_item.handle,
+                playlist_item.album.as_deref().unwrap_or_default(),
+                playlist_item.isrc.as_deref().unwrap_or_default(),
+            ])
+            .map_err(std::io::Error::from)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn read_m3u(contents: &str) -> Vec<PlaylistItem> {
+    let mut playlist_items = vec![];
+    let mut extinf: Option<(Option<Duration>, String)> = None;
+    let mut album = None;
+    let mut isrc = None;
+
+    for line in contents.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            extinf = info.split_once(',').map(|(seconds, title)| {
+                let duration = seconds.trim().parse::<u64>().ok().map(Duration::from_secs);
+
+                (duration, title.trim().to_string())
+            });
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("#EXTALB:") {
+            album = Some(name.trim().to_string());
+            continue;
+        }
+
+        if let Some(code) = line.strip_prefix("#EXTISRC:") {
+            isrc = Some(code.trim().to_string());
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (duration, title) = match extinf.take() {
+            Some(extinf) => extinf,
+            None => (None, location_title(line)),
+        };
+
+        let (artists, name) = match title.split_once(" - ") {
+            Some((artists, name)) => (artists.trim().to_string(), name.trim().to_string()),
+            None => (String::new(), title),
+        };
+
+        playlist_items.push(PlaylistItem {
+            album: album.take(),
+            isrc: isrc.take(),
+            ..playlist_item(line, name, artists, duration, None)
+        });
+    }
+
+    playlist_items
+}
+
+fn read_xspf(contents: &str) -> Result<Vec<PlaylistItem>> {
+    let mut reader = XmlReader::from_str(contents);
+    reader.config_mut().trim_text(true);
+
+    let mut playlist_items = vec![];
+    // The fields of the track being read, by element name, and the element
+    // whose text is read next. Elements nested deeper, like those of an
+    // <extension>, are skipped.
+    let mut track: Option<Vec<(String, String)>> = None;
+    let mut element: Option<String> = None;
+    let mut depth = 0;
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            XmlEvent::Start(start) => {
+                depth += 1;
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+
+                match &track {
+                    None if name == "track" => {
+                        track = Some(vec![]);
+                        depth = 0;
+                    }
+                    Some(_) if depth == 1 => element = Some(name),
+                    _ => element = None,
+                }
+            }
+            XmlEvent::End(end) => {
+                element = None;
+
+                if track.is_some() && depth == 0 && end.local_name().as_ref() == b"track" {
+                    playlist_items.push(xspf_item(&track.take().unwrap_or_default()));
+                }
+                depth -= 1;
+            }
+            XmlEvent::Text(text) => {
+                if let (Some(track), Some(element)) = (&mut track, &element) {
+                    let text = text.unescape().map_err(xml_error)?;
+                    track.push((element.clone(), text.into_owned()));
+                }
+            }
+            XmlEvent::CData(text) => {
+                if let (Some(track), Some(element)) = (&mut track, &element) {
+                    let text = String::from_utf8_lossy(&text.into_inner())
+                        .trim()
+                        .to_string();
+                    track.push((element.clone(), text));
+                }
+            }
+            XmlEvent::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(playlist_items)
+}
+
+fn xspf_item(fields: &[(String, String)]) -> PlaylistItem {
+    // Only the first of repeated elements counts, like the first of several
+    // <location>s.
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(element, _)| element == name)
+            .map(|(_, text)| text.clone())
+    };
+
+    let location = field("location")
+        .map(|location| xspf_path(&location))
+        .unwrap_or_default();
+    let name = field("title").unwrap_or_else(|| location_title(&location));
+    let artists = field("creator").unwrap_or_default();
+    let duration = field("duration")
+        .and_then(|duration| duration.parse::<u64>().ok())
+        .map(Duration::from_millis);
+    let kind = match field("annotation").as_deref() {
+        Some("episode") => Some(PlaylistItemKind::Episode),
+        _ => None,
+    };
+    let isrc = fields
+        .iter()
+        .filter(|(element, _)| element == "identifier")
+        .find_map(|(_, text)| text.strip_prefix("urn:isrc:"))
+        .map(|isrc| isrc.trim().to_string());
+
+    PlaylistItem {
+        album: field("album"),
+        isrc,
+        ..playlist_item(&location, name, artists, duration, kind)
+    }
+}
+
+/// Turns `file://` locations back into the paths they point to.
+fn xspf_path(location: &str) -> String {
+    if !location.starts_with("file:") {
+        return location.to_string();
+    }
+
+    Url::parse(location)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .and_then(|path| path.to_str().map(str::to_string))
+        .unwrap_or_else(|| location.to_string())
+}
+
+/// XSPF locations are URIs, so absolute local paths are written as `file://`
+/// URIs. Relative paths have no such form and are kept as they are.
+fn xspf_location(id: &PlaylistItemId) -> String {
+    match id {
+        PlaylistItemId::Local(path) => Url::from_file_path(path)
+            .map(String::from)
+            .unwrap_or_else(|()| path.clone()),
+        _ => id.to_string(),
+    }
+}
+
+fn xml_error(error: impl std::error::Error + Send + Sync + 'static) -> Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error).into()
+}
+
+fn read_csv(contents: &str) -> Result<Vec<PlaylistItem>> {
+    // Rows of other tools may have fewer fields than the header.
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+
+    let header = reader
+        .headers()
+        .map_err(std::io::Error::from)?
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect::<Vec<_>>();
+
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|column| names.contains(&column.as_str()))
+    };
+
+    let location_column = column(&["url", "location", "uri", "track uri", "spotify uri"]);
+    let name_column = column(&["name", "title", "track name", "track"]);
+    let artists_column = column(&["artists", "artist", "artist name(s)", "creator"]);
+    let album_column = column(&["album", "album name"]);
+    let isrc_column = column(&["isrc"]);
+    let duration_column = column(&["duration_ms", "duration (ms)", "track duration (ms)"]);
+    let kind_column = column(&["kind"]);
+
+    let mut playlist_items = vec![];
+
+    for row in reader.records() {
+        let row = row.map_err(std::io::Error::from)?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| row.get(column))
+                .unwrap_or_default()
+                .to_string()
+        };
+        let optional_field = |column: Option<usize>| Some(field(column)).filter(|f| !f.is_empty());
+
+        let location = field(location_column);
+        let name = match field(name_column) {
+            name if name.is_empty() => location_title(&location),
+            name => name,
+        };
+        let duration = field(duration_column)
+            .parse::<u64>()
+            .ok()
+            .map(Duration::from_millis);
+        let kind = match field(kind_column).as_str() {
+            "episode" => Some(PlaylistItemKind::Episode),
+            _ => None,
+        };
+
+        playlist_items.push(PlaylistItem {
+            album: optional_field(album_column),
+            isrc: optional_field(isrc_column),
+            ..playlist_item(&location, name, field(artists_column), duration, kind)
+        });
+    }
+
+    Ok(playlist_items)
+}
+
+fn playlist_item(
+    location: &str,
+    name: String,
+    artists: String,
+    duration: Option<Duration>,
+    kind: Option<PlaylistItemKind>,
+) -> PlaylistItem {
+    let id = parse_location(location);
+    let kind = kind.unwrap_or(match id {
+        PlaylistItemId::SpotifyEpisode(_) => PlaylistItemKind::Episode,
+        _ => PlaylistItemKind::Track,
+    });
+
+    let handle = if artists.is_empty() {
+        name.clone()
+    } else {
+        format!("{} - {}", name, artists)
+    };
+
+    PlaylistItem {
+        id,
+        kind,
+        name,
+        artists,
+        handle,
+        duration,
+        album: None,
+        isrc: None,
+        mbid: None,
+        canonical: None,
+    }
+}
+
+/// Recognises links to the providers linksen knows, anything else is kept as
+/// a local location.
+pub(crate) fn parse_location(location: &str) -> PlaylistItemId {
+    let id_after = |prefix: &str| {
+        location.find(prefix).map(|start_index| {
+            location[start_index + prefix.len()..]
+                .split(['?', '&', '#', '/'])
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        })
+    };
+
+    if let Some(id) = id_after("open.spotify.com/track/").or_else(|| id_after("spotify:track:")) {
+        return PlaylistItemId::Spotify(id);
+    }
+
+    if let Some(id) = id_after("open.spotify.com/episode/").or_else(|| id_after("spotify:episode:"))
+    {
+        return PlaylistItemId::SpotifyEpisode(id);
+    }
+
+    if let Some(id) = id_after("youtube.com/watch?v=").or_else(|| id_after("youtu.be/")) {
+        return PlaylistItemId::YouTube(id);
+    }
+
+    if let Some(id) = id_after("deezer.com/track/").or_else(|| {
+        // Localised links look like deezer.com/en/track/<id>.
+        Regex::new(r"deezer\.com/[a-z]{2}/track/(\d+)")
+            .unwrap()
+            .captures(location)
+            .map(|captures| captures[1].to_string())
+    }) {
+        return PlaylistItemId::Deezer(id);
+    }
+
+    if let Some(id) = id_after("tidal.com/browse/track/").or_else(|| id_after("tidal.com/track/")) {
+        return PlaylistItemId::Tidal(id);
+    }
+
+    if location.contains("music.apple.com") {
+        // Songs are linked either directly or as ?i=<id> on their album.
+        let song = Regex::new(r"(?:/song/(?:[^/?#]+/)?|[?&]i=)(\d+)").unwrap();
+
+        if let Some(captures) = song.captures(location) {
+            return PlaylistItemId::AppleMusic(captures[1].to_string());
+        }
+    }
+
+    // Tracks are last.fm/music/<artist>/_/<track>, localised or not.
+    let lastfm = Regex::new(r"last\.fm/(?:[a-z]{2}/)?music/([^/?#]+/_/[^/?#]+)").unwrap();
+    if let Some(captures) = lastfm.captures(location) {
+        return PlaylistItemId::Lastfm(captures[1].to_string());
+    }
+
+    // Tracks are soundcloud.com/<user>/<track>; sets and likes live under
+    // the same user, so they are told apart by their second segment.
+    let track = Regex::new(r"soundcloud\.com/([^/?#]+/[^/?#]+)").unwrap();
+    if let Some(captures) = track.captures(location) {
+        let permalink = &captures[1];
+
+        if !matches!(
+            permalink.split('/').nth(1),
+            Some("sets" | "likes" | "tracks" | "albums" | "reposts")
+        ) {
+            return PlaylistItemId::SoundCloud(permalink.to_string());
+        }
+    }
+
+    PlaylistItemId::Local(location.to_string())
+}
+
+fn location_title(location: &str) -> String {
+    Path::new(location)
+        .file_stem()
+        .and_then(|file_stem| file_stem.to_str())
+        .unwrap_or(location)
+        .to_string()
+}
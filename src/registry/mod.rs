@@ -0,0 +1,178 @@
+use serde::Serialize;
+
+use crate::apple_music::AppleMusicProvider;
+use crate::deezer::DeezerProvider;
+use crate::error::{Error, Result};
+use crate::events::{quiet, SharedReporter};
+use crate::lastfm::LastfmProvider;
+use crate::local_library::LocalLibraryProvider;
+use crate::playlist_file::{PlaylistFileFormat, PlaylistFileProvider};
+use crate::soundcloud::SoundCloudProvider;
+use crate::spotify::SpotifyProvider;
+use crate::tidal::TidalProvider;
+use crate::types::{PlaylistItemId, PlaylistSink, PlaylistSource, SearchPreference, TrackSearcher};
+use crate::youtube::{YoutubeMusicProvider, YoutubeProvider};
+
+/// What a provider can do with playlists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    pub read: bool,
+    pub search: bool,
+    pub write: bool,
+    pub delete: bool,
+    pub reorder: bool,
+}
+
+/// Everything the registry may need to build a client. Providers ignore the
+/// settings that are not theirs.
+#[derive(Clone)]
+pub struct ProviderConfig {
+    pub google_client_id: Option<String>,
+    pub google_client_secret: Option<String>,
+    pub playlist_file_format: Option<PlaylistFileFormat>,
+    /// Overrides what YouTube targets look for when matching items.
+    pub search_preference: Option<SearchPreference>,
+    pub reporter: SharedReporter,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            google_client_id: None,
+            google_client_secret: None,
+            playlist_file_format: None,
+            search_preference: None,
+            reporter: quiet(),
+        }
+    }
+}
+
+/// Describes a music provider and builds its client.
+pub trait Provider: Send + Sync {
+    /// Short lowercase name used on the command line, e.g. "spotify".
+    fn name(&self) -> &'static str;
+
+    /// Name shown to people, e.g. "Spotify".
+    fn display_name(&self) -> &'static str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Whether the URL (or path) points at something this provider reads.
+    fn owns(&self, location: &str) -> bool;
+
+    /// Whether the item is one of this provider's, i.e. one its sink can add.
+    fn owns_item(&self, id: &PlaylistItemId) -> bool {
+        self.owns(&id.to_string())
+    }
+
+    /// Builds a client that reads playlists, if the provider can. Like the
+    /// other clients, it has to be authenticated before it is used.
+    fn source(&self, _config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(None)
+    }
+
+    /// Builds a client that matches items from other providers, if the
+    /// provider can.
+    fn searcher(&self, _config: &ProviderConfig) -> Result<Option<Box<dyn TrackSearcher>>> {
+        Ok(None)
+    }
+
+    /// Builds a client that creates playlists, if the provider can.
+    fn sink(&self, _config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSink>>> {
+        Ok(None)
+    }
+
+    /// How much of the provider's API quota creating a playlist of `items`
+    /// items costs, in its own units. By default a request per item and one
+    /// for the playlist.
+    fn write_cost(&self, items: usize) -> u64 {
+        items as u64 + 1
+    }
+
+    /// The name of the API quota `write_cost` is spent from, shared by the
+    /// providers that write through the same API.
+    fn quota_pool(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Whether the provider reads files of the machine linksen runs on rather
+    /// than a service.
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    /// A location the provider's source reads the playlist with the ID its
+    /// sink returned from. The ID itself by default.
+    fn playlist_location(&self, playlist_id: &str) -> String {
+        playlist_id.to_string()
+    }
+}
+
+/// The providers linksen knows, in the order they are asked whether they own
+/// a location.
+pub struct Registry {
+    providers: Vec<Box<dyn Provider>>,
+}
+
+impl Registry {
+    /// A registry without any provider.
+    pub fn empty() -> Self {
+        Self { providers: vec![] }
+    }
+
+    /// A registry with every provider built into linksen.
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(SpotifyProvider);
+        registry.register(YoutubeProvider);
+        registry.register(YoutubeMusicProvider);
+        registry.register(DeezerProvider);
+        registry.register(AppleMusicProvider);
+        registry.register(TidalProvider);
+        registry.register(SoundCloudProvider);
+        registry.register(LastfmProvider);
+        registry.register(PlaylistFileProvider);
+        registry.register(LocalLibraryProvider);
+
+        registry
+    }
+
+    pub fn register(&mut self, provider: impl Provider + 'static) {
+        self.providers.push(Box::new(provider));
+    }
+
+    pub fn providers(&self) -> impl Iterator<Item = &dyn Provider> {
+        self.providers.iter().map(|provider| provider.as_ref())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Provider> {
+        self.providers()
+            .find(|provider| provider.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn detect(&self, location: &str) -> Option<&dyn Provider> {
+        self.providers().find(|provider| provider.owns(location))
+    }
+
+    /// Finds the provider of a location, which is either owned by one of the
+    /// providers or explicitly prefixed with a provider name, e.g.
+    /// "youtube:liked". Returns the provider along with the location without
+    /// the prefix.
+    pub fn resolve<'a>(&self, location: &'a str) -> Result<(&dyn Provider, &'a str)> {
+        if let Some((name, rest)) = location.split_once(':') {
+            if let Some(provider) = self.get(name) {
+                return Ok((provider, rest));
+            }
+        }
+
+        self.detect(location)
+            .map(|provider| (provider, location))
+            .ok_or_else(|| Error::InvalidUrl(location.to_string()))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    error::Error,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use linksen::{
+    CachedMatch, Event, MatchCache, MusicBrainz, ParsedPlaylistItems, PlaylistOptions, Privacy,
+    ProviderConfig, Registry, SearchPreference,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::output::{LinkReport, Report};
+use crate::{find_links, target_searcher};
+
+/// How many finished conversions are kept to be asked about. Older ones are
+/// forgotten, running ones never are.
+const MAX_FINISHED_JOBS: usize = 100;
+
+struct AppState {
+    registry: Registry,
+    /// Whether clients may read the files of the machine the server runs on.
+    allow_local_files: bool,
+    config: ProviderConfig,
+    cache: Arc<MatchCache>,
+    jobs: Mutex<Vec<Job>>,
+}
+
+/// A conversion started through the API, as reported by it.
+#[derive(Clone, Serialize)]
+struct Job {
+    id: usize,
+    from: String,
+    to: String,
+    status: JobStatus,
+    /// Where the user has to go to let linksen act on their behalf, while
+    /// the conversion waits for them.
+    authorization_url: Option<String>,
+    loaded: Option<usize>,
+    searched: usize,
+    total: Option<usize>,
+    error: Option<String>,
+    /// The report of `convert --output=json`, once done.
+    result: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JobStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+/// What to convert: the same as the options of `convert`, except that the
+/// target has to be a provider. A playlist is only created if it is named.
+#[derive(Deserialize)]
+struct ConversionRequest {
+    from: String,
+    to: String,
+    fallback: Option<String>,
+    prefer: Option<SearchPreference>,
+    #[serde(default)]
+    enrich: bool,
+    name: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    privacy: Privacy,
+}
+
+#[derive(Deserialize)]
+struct LinkQuery {
+    url: String,
+    /// Comma separated provider names.
+    to: Option<String>,
+    prefer: Option<SearchPreference>,
+    #[serde(default)]
+    enrich: bool,
+}
+
+#[derive(Deserialize)]
+struct MatchQuery {
+    provider: Option<String>,
+    /// Link of the source item.
+    source: Option<String>,
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<Box<dyn Error>> for ApiError {
+    fn from(error: Box<dyn Error>) -> Self {
+        ApiError(StatusCode::BAD_REQUEST, error.to_string())
+    }
+}
+
+/// Serves the API on `address` until the process is stopped. Progress is not
+/// printed but kept with each job; authorization links are printed to stderr
+/// as well, for whoever runs the server. Playlist files and audio directories
+/// are only read with `allow_local_files`, as the API has no authentication.
+pub async fn serve(
+    address: SocketAddr,
+    allow_local_files: bool,
+    config: ProviderConfig,
+    cache: Arc<MatchCache>,
+) -> Result<(), Box<dyn Error>> {
+    let state = Arc::new(AppState {
+        registry: Registry::new(),
+        allow_local_files,
+        config,
+        cache,
+        jobs: Mutex::new(vec![]),
+    });
+
+    let app = Router::new()
+        .route("/conversions", get(list_jobs).post(start_conversion))
+        .route("/conversions/:id", get(get_job))
+        .route("/links", get(get_links))
+        .route("/matches", get(get_matches))
+        .with_state(state);
+
+    eprintln!("Listening on http://{}", address);
+
+    axum::Server::try_bind(&address)?
+        .serve(app.into_make_service())
+        .await?;
+
+    Ok(())
+}
+
+async fn list_jobs(State(state): State<Arc<AppState>>) -> Json<Vec<Job>> {
+    Json(state.jobs.lock().unwrap().clone())
+}
+
+async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<usize>,
+) -> Result<Json<Job>, ApiError> {
+    state
+        .jobs
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|job| job.id == id)
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("no conversion {}", id)))
+}
+
+async fn start_conversion(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ConversionRequest>,
+) -> Result<(StatusCode, Json<Job>), ApiError> {
+    // Mistakes in the request are answered right away rather than left for
+    // the job to fail with.
+    check_source(&state, &request.from)?;
+    for name in std::iter::once(&request.to).chain(&request.fallback) {
+        match state.registry.get(name) {
+            Some(provider) if provider.capabilities().search => {}
+            Some(provider) => {
+                return Err(bad_request(format!(
+                    "{} cannot search for tracks",
+                    provider.display_name()
+                )))
+            }
+            None => return Err(bad_request(format!("unknown provider {}", name))),
+        }
+    }
+
+    let job = {
+        let mut jobs = state.jobs.lock().unwrap();
+        let job = Job {
+            id: jobs.last().map_or(0, |job| job.id + 1),
+            from: request.from.clone(),
+            to: request.to.clone(),
+            status: JobStatus::Running,
+            authorization_url: None,
+            loaded: None,
+            searched: 0,
+            total: None,
+            error: None,
+            result: None,
+        };
+        jobs.push(job.clone());
+
+        job
+    };
+
+    let id = job.id;
+    let task_state = state.clone();
+    tokio::spawn(async move {
+        let outcome = convert(&task_state, id, &request)
+            .await
+            .map_err(|error| error.to_string());
+
+        update_job(&task_state, id, |job| match outcome {
+            Ok(result) => {
+                job.status = JobStatus::Done;
+                job.authorization_url = None;
+                job.result = Some(result);
+            }
+            Err(error) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(error);
+            }
+        });
+        forget_finished_jobs(&task_state);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Runs a conversion the way `convert --yes` does, and answers its report.
+async fn convert(
+    state: &Arc<AppState>,
+    id: usize,
+    request: &ConversionRequest,
+) -> Result<Value, Box<dyn Error>> {
+    let reporter_state = state.clone();
+    let config = ProviderConfig {
+        search_preference: request.prefer,
+        reporter: Arc::new(move |event: &Event| job_event(&reporter_state, id, event)),
+        ..state.config.clone()
+    };
+
+    let (source, location) = state.registry.resolve(&request.from)?;
+    let target = state
+        .registry
+        .get(&request.to)
+        .ok_or_else(|| format!("unknown provider {}", request.to))?;
+    let fallback = request
+        .fallback
+        .as_deref()
+        .and_then(|name| state.registry.get(name));
+
+    let mut client = source
+        .source(&config)?
+        .ok_or_else(|| format!("{} playlists cannot be read", source.display_name()))?;
+    client.authenticate().await?;
+    let mut playlist_items = client.get_playlist_items(location).await?;
+
+    if request.enrich {
+        let mut musicbrainz = MusicBrainz::new();
+        musicbrainz.set_reporter(config.reporter.clone());
+        playlist_items = musicbrainz.enrich(playlist_items).await?;
+    }
+
+    let searcher = target_searcher(target, fallback, &config, &state.cache)?;
+    let playlist_items: ParsedPlaylistItems = searcher.parse_playlist_items(playlist_items).await?;
+
+    let mut playlist_id = None;
+    if let (Some(name), Some(mut sink)) = (&request.name, target.sink(&config)?) {
+        sink.authenticate().await?;
+
+        let options = PlaylistOptions {
+            name: name.clone(),
+            description: request.description.clone(),
+            privacy: request.privacy,
+        };
+        playlist_id = Some(
+            sink.create_playlist(&options, &playlist_items.matched())
+                .await?,
+        );
+    }
+
+    let mode = format!("{} -> {}", source.display_name(), target.display_name());
+
+    Ok(serde_json::to_value(Report::new(
+        &mode,
+        location,
+        &playlist_items,
+        playlist_id.as_deref(),
+    ))?)
+}
+
+fn job_event(state: &AppState, id: usize, event: &Event) {
+    if let Event::AuthorizationRequired { provider, url } = event {
+        eprintln!("Conversion {}: authorize {} at {}", id, provider, url);
+    }
+
+    update_job(state, id, |job| match event {
+        Event::AuthorizationRequired { url, .. } => job.authorization_url = Some(url.clone()),
+        Event::LoadingFinished { count } => job.loaded = Some(*count),
+        Event::TransformStarted { total } => {
+            job.searched = 0;
+            job.total = Some(*total);
+        }
+        Event::ItemMatched { .. } | Event::ItemUnmatched { .. } => job.searched += 1,
+        _ => {}
+    });
+}
+
+fn update_job(state: &AppState, id: usize, update: impl FnOnce(&mut Job)) {
+    if let Some(job) = state
+        .jobs
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .find(|job| job.id == id)
+    {
+        update(job);
+    }
+}
+
+/// Drops the oldest finished jobs beyond [`MAX_FINISHED_JOBS`].
+fn forget_finished_jobs(state: &AppState) {
+    let mut jobs = state.jobs.lock().unwrap();
+
+    let finished = jobs
+        .iter()
+        .filter(|job| job.status != JobStatus::Running)
+        .count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+
+    jobs.retain(|job| {
+        let forget = excess > 0 && job.status != JobStatus::Running;
+        if forget {
+            excess -= 1;
+        }
+
+        !forget
+    });
+}
+
+/// Resolves the location a client asks to read, which must not be a file of
+/// this machine unless the server was started to allow it.
+fn check_source(state: &AppState, location: &str) -> Result<(), ApiError> {
+    let (source, _) = state.registry.resolve(location).map_err(bad_request)?;
+
+    if source.is_local() && !state.allow_local_files {
+        return Err(ApiError(
+            StatusCode::FORBIDDEN,
+            format!(
+                "{} locations are only read with --allow-local-files",
+                source.display_name()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn get_links(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LinkQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let to: Vec<String> = query
+        .to
+        .as_deref()
+        .map(|to| to.split(',').map(|name| name.trim().to_string()).collect())
+        .unwrap_or_default();
+    check_source(&state, &query.url)?;
+    let config = ProviderConfig {
+        search_preference: query.prefer,
+        ..state.config.clone()
+    };
+
+    let (item, links) = find_links(
+        &state.registry,
+        &config,
+        &state.cache,
+        &query.url,
+        &to,
+        query.enrich,
+    )
+    .await?;
+
+    Ok(Json(
+        serde_json::to_value(LinkReport::new(&item, &links)).map_err(bad_request)?,
+    ))
+}
+
+async fn get_matches(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<MatchQuery>,
+) -> Json<Vec<CachedMatch>> {
+    let mut matches = state.cache.matches(query.provider.as_deref());
+    if let Some(source) = &query.source {
+        matches.retain(|cached| cached.source.to_string() == *source);
+    }
+
+    Json(matches)
+}
+
+fn bad_request(error: impl ToString) -> ApiError {
+    ApiError(StatusCode::BAD_REQUEST, error.to_string())
+}
@@ -0,0 +1,377 @@
+use std::{env, sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, Result};
+use crate::events::{quiet, report_match, searched, Event, SharedReporter};
+use crate::matching::confidence;
+use crate::playlist_file::parse_location;
+use crate::registry::{Capabilities, Provider, ProviderConfig};
+use crate::types::{
+    ItemMatch, MusicClient, ParsedPlaylistItems, PlaylistItem, PlaylistItemId, PlaylistItemKind,
+    PlaylistSource, SoundCloudAccessToken, SoundCloudPage, SoundCloudResource, SoundCloudTrack,
+    TrackSearcher,
+};
+
+const API_URL: &str = "https://api.soundcloud.com";
+const AUTH_URL: &str = "https://secure.soundcloud.com";
+const PAGE_SIZE: &str = "200";
+
+/// Reads SoundCloud sets and the tracks a user liked, and searches SoundCloud
+/// for tracks, e.g. for mixes and remixes that exist nowhere else.
+pub struct SoundCloud {
+    http: reqwest::Client,
+    api_url: String,
+    auth_url: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    access_token: Mutex<Option<String>>,
+    reporter: SharedReporter,
+}
+
+impl SoundCloud {
+    /// Takes the app credentials from `SOUNDCLOUD_CLIENT_ID` and
+    /// `SOUNDCLOUD_CLIENT_SECRET`, or an already issued token from
+    /// `SOUNDCLOUD_ACCESS_TOKEN`.
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: API_URL.into(),
+            auth_url: AUTH_URL.into(),
+            client_id: env::var("SOUNDCLOUD_CLIENT_ID").ok(),
+            client_secret: env::var("SOUNDCLOUD_CLIENT_SECRET").ok(),
+            access_token: Mutex::new(env::var("SOUNDCLOUD_ACCESS_TOKEN").ok()),
+            reporter: quiet(),
+        }
+    }
+
+    pub fn with_credentials(client_id: String, client_secret: String) -> Self {
+        Self {
+            client_id: Some(client_id),
+            client_secret: Some(client_secret),
+            ..Self::new()
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+
+    pub fn set_access_token(&mut self, access_token: String) {
+        self.access_token = Mutex::new(Some(access_token));
+    }
+
+    /// Sends the requests to other servers than SoundCloud's, e.g. a local
+    /// stand-in of the API.
+    pub fn set_base_urls(&mut self, api_url: String, auth_url: String) {
+        self.api_url = api_url;
+        self.auth_url = auth_url;
+    }
+
+    fn from_config(config: &ProviderConfig) -> SoundCloud {
+        let mut soundcloud = SoundCloud::new();
+        soundcloud.set_reporter(config.reporter.clone());
+
+        soundcloud
+    }
+
+    /// The app's token, requested with the client credentials on first use.
+    async fn access_token(&self) -> Result<String> {
+        if let Some(access_token) = self.access_token.lock().unwrap().clone() {
+            return Ok(access_token);
+        }
+
+        let (Some(client_id), Some(client_secret)) = (&self.client_id, &self.client_secret) else {
+            return Err(Error::MissingCredentials("SoundCloud"));
+        };
+
+        let token: SoundCloudAccessToken = self
+            .http
+            .post(format!("{}/oauth/token", self.auth_url))
+            .basic_auth(client_id, Some(client_secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        *self.access_token.lock().unwrap() = Some(token.access_token.clone());
+
+        Ok(token.access_token)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T> {
+        let access_token = self.access_token().await?;
+
+        Ok(self
+            .http
+            .get(url)
+            .header("Authorization", format!("OAuth {}", access_token))
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn resolve(&self, url: &str) -> Result<SoundCloudResource> {
+        self.get(&format!("{}/resolve", self.api_url), &[("url", url)])
+            .await
+    }
+
+    async fn get_tracks(&self, url: String) -> Result<Vec<SoundCloudTrack>> {
+        let mut tracks = vec![];
+        let mut page: SoundCloudPage<SoundCloudTrack> = self
+            .get(
+                &url,
+                &[("limit", PAGE_SIZE), ("linked_partitioning", "true")],
+            )
+            .await?;
+
+        loop {
+            tracks.extend(page.collection);
+
+            // next_href already carries every parameter of the first request.
+            match page.next_href {
+                Some(next) => page = self.get(&next, &[]).await?,
+                None => return Ok(tracks),
+            }
+        }
+    }
+
+    async fn search_track(&self, playlist_item: &PlaylistItem) -> Result<Option<SoundCloudTrack>> {
+        let query = playlist_item.credited_handle();
+
+        match self.search_tracks(&query).await? {
+            Some(track) => Ok(Some(track)),
+            // Uploaders rarely credit anyone but themselves in the title.
+            None if playlist_item.credited_name() != query => {
+                self.search_tracks(playlist_item.credited_name()).await
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn search_tracks(&self, query: &str) -> Result<Option<SoundCloudTrack>> {
+        let page: SoundCloudPage<SoundCloudTrack> = self
+            .get(
+                &format!("{}/tracks", self.api_url),
+                &[
+                    ("q", query),
+                    ("limit", "1"),
+                    ("linked_partitioning", "true"),
+                ],
+            )
+            .await?;
+
+        Ok(page.collection.into_iter().next())
+    }
+}
+
+impl Default for SoundCloud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct SoundCloudProvider;
+
+impl Provider for SoundCloudProvider {
+    fn name(&self) -> &'static str {
+        "soundcloud"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "SoundCloud"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            search: true,
+            ..Default::default()
+        }
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        location.contains("soundcloud.com")
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(Some(Box::new(SoundCloud::from_config(config))))
+    }
+
+    fn searcher(&self, config: &ProviderConfig) -> Result<Option<Box<dyn TrackSearcher>>> {
+        Ok(Some(Box::new(SoundCloud::from_config(config))))
+    }
+}
+
+#[async_trait]
+impl MusicClient for SoundCloud {
+    async fn authenticate(&mut self) -> Result<()> {
+        self.access_token().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PlaylistSource for SoundCloud {
+    /// Reads a set (soundcloud.com/<user>/sets/<set>) or the likes of a user
+    /// (soundcloud.com/<user>/likes).
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::LoadingStarted);
+
+        let url = url.trim().trim_end_matches('/');
+        let tracks = match url.strip_suffix("/likes") {
+            Some(user_url) => {
+                let user = self.resolve(user_url).await?;
+                if user.kind != "user" {
+                    return Err(Error::InvalidUrl(url.to_string()));
+                }
+
+                self.get_tracks(format!("{}/users/{}/likes/tracks", self.api_url, user.id))
+                    .await?
+            }
+            None => {
+                let set = self.resolve(url).await?;
+                if set.kind != "playlist" {
+                    return Err(Error::InvalidUrl(url.to_string()));
+                }
+
+                self.get_tracks(format!("{}/playlists/{}/tracks", self.api_url, set.id))
+                    .await?
+            }
+        };
+
+        let playlist_items = tracks
+            .iter()
+            .filter_map(track_playlist_item)
+            .collect::<Vec<_>>();
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+
+    async fn get_item(&self, url: &str) -> Result<PlaylistItem> {
+        let invalid_url = || Error::InvalidUrl(url.to_string());
+
+        match parse_location(url) {
+            PlaylistItemId::SoundCloud(_) => {
+                let track: SoundCloudTrack = self
+                    .get(&format!("{}/resolve", self.api_url), &[("url", url)])
+                    .await?;
+
+                track_playlist_item(&track).ok_or_else(invalid_url)
+            }
+            _ => Err(invalid_url()),
+        }
+    }
+}
+
+#[async_trait]
+impl TrackSearcher for SoundCloud {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        self.reporter.report(&Event::TransformStarted {
+            total: playlist_items.len(),
+        });
+
+        let mut parsed_playlist_items = ParsedPlaylistItems::default();
+
+        for playlist_item in playlist_items.into_iter() {
+            self.reporter.report(&Event::ItemSearching {
+                item: playlist_item.clone(),
+            });
+
+            let item_match = match playlist_item.id {
+                PlaylistItemId::SoundCloud(_) => ItemMatch {
+                    target: Some(playlist_item.clone()),
+                    source: playlist_item,
+                    confidence: 1.0,
+                },
+                _ => {
+                    let target = searched(
+                        self.reporter.as_ref(),
+                        &playlist_item,
+                        self.search_track(&playlist_item).await,
+                    )
+                    .as_ref()
+                    .and_then(track_playlist_item);
+                    let confidence = target
+                        .as_ref()
+                        .map_or(0.0, |target| confidence(&playlist_item, target));
+
+                    ItemMatch {
+                        source: playlist_item,
+                        target,
+                        confidence,
+                    }
+                }
+            };
+
+            report_match(self.reporter.as_ref(), &item_match);
+            parsed_playlist_items.items.push(item_match);
+        }
+
+        self.reporter.report(&Event::TransformFinished);
+
+        Ok(parsed_playlist_items)
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<String>> {
+        Ok(self
+            .search_tracks(query)
+            .await?
+            .and_then(|track| permalink(&track)))
+    }
+}
+
+/// The ID of a track, which is the path of its permalink, e.g.
+/// "artist/track". Permalinks that are not soundcloud.com links have none.
+fn permalink(track: &SoundCloudTrack) -> Option<String> {
+    Some(
+        track
+            .permalink_url
+            .split_once("soundcloud.com/")?
+            .1
+            .trim_end_matches('/')
+            .to_string(),
+    )
+}
+
+/// The artist is the uploader. An artist the publisher credits instead goes
+/// into the handle, which is what searches fall back to. Tracks whose
+/// permalink is not a soundcloud.com link are skipped.
+fn track_playlist_item(track: &SoundCloudTrack) -> Option<PlaylistItem> {
+    let permalink = permalink(track)?;
+
+    let metadata = track.publisher_metadata.as_ref();
+    let artists = track.user.username.clone();
+    let credited = metadata
+        .and_then(|metadata| metadata.artist.as_deref())
+        .filter(|artist| !artist.is_empty())
+        .unwrap_or(&artists);
+    let handle = format!("{} - {}", track.title, credited);
+
+    Some(PlaylistItem {
+        id: PlaylistItemId::SoundCloud(permalink),
+        kind: PlaylistItemKind::Track,
+        name: track.title.clone(),
+        artists,
+        handle,
+        duration: track.duration.map(Duration::from_millis),
+        album: metadata.and_then(|metadata| metadata.album_title.clone()),
+        isrc: metadata.and_then(|metadata| metadata.isrc.clone()),
+        mbid: None,
+        canonical: None,
+    })
+}
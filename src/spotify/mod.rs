@@ -1,127 +1,207 @@
-use crate::types::{MusicClient, PlaylistItem, PlaylistItemId};
+use std::{env, sync::OnceLock};
+
+use crate::error::{Error, Result};
+use crate::events::{quiet, report_match, searched, Event, SharedReporter};
+use crate::matching::confidence;
+use crate::normalize::spotify_query;
+use crate::oauth::{data_dir, wait_for_redirect};
+use crate::playlist_file::parse_location;
+use crate::registry::{Capabilities, Provider, ProviderConfig};
+use crate::types::{
+    ItemMatch, MusicClient, ParsedPlaylistItems, PlaylistItem, PlaylistItemId, PlaylistItemKind,
+    PlaylistSource, PlaylistSummary, TrackSearcher,
+};
 use async_trait::async_trait;
-use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle};
 use rspotify::{
-    model::{PlayableItem, PlaylistId, SearchResult},
-    prelude::BaseClient,
-    ClientCredsSpotify, Credentials,
+    http::Query,
+    model::{
+        EpisodeId, FullEpisode, FullPlaylist, FullTrack, Page, PlayableItem, PlaylistId,
+        PlaylistItem as SpotifyPlaylistItem, PlaylistTracksRef, PublicUser, SearchResult, TrackId,
+        UserId,
+    },
+    prelude::{BaseClient, Id, OAuthClient},
+    scopes, AuthCodeSpotify, ClientCredsSpotify, Config, Credentials, OAuth,
 };
+use serde::Deserialize;
+
+const REDIRECT_URI: &str = "http://localhost:8888/spotify";
+const TOKEN_CACHE: &str = "spotify.token.json";
 
 pub struct Spotify {
     client: ClientCredsSpotify,
+    /// Acts on behalf of the user once they authorized linksen, which only
+    /// listing their own playlists asks them to.
+    user_client: OnceLock<AuthCodeSpotify>,
+    redirect_uri: String,
+    reporter: SharedReporter,
 }
 
 impl Spotify {
-    pub fn new() -> Spotify {
-        let creds = Credentials::from_env().unwrap();
+    pub fn new() -> Result<Spotify> {
+        let creds = Credentials::from_env().ok_or(Error::MissingCredentials("Spotify"))?;
+
+        Ok(Spotify::with_credentials(creds))
+    }
+
+    pub fn with_credentials(creds: Credentials) -> Spotify {
         let spotify = ClientCredsSpotify::new(creds);
 
-        Spotify { client: spotify }
+        Spotify {
+            client: spotify,
+            user_client: OnceLock::new(),
+            redirect_uri: env::var("RSPOTIFY_REDIRECT_URI").unwrap_or(REDIRECT_URI.into()),
+            reporter: quiet(),
+        }
     }
 
-    pub async fn authenticate(&self) {
-        self.client.request_token().await.unwrap();
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
     }
-}
 
-#[async_trait]
-impl MusicClient for Spotify {
-    async fn get_playlist_items(&self, url: &str) -> Vec<PlaylistItem> {
-        print!("{}", "Loading playlist... ".yellow());
+    /// The redirect URI registered for the Spotify app, on which linksen
+    /// listens during authorization.
+    pub fn set_redirect_uri(&mut self, redirect_uri: String) {
+        self.redirect_uri = redirect_uri;
+    }
 
-        let playlist_id = extract_playlist_id(url).unwrap();
-        let playlist_id = PlaylistId::from_id(playlist_id).unwrap();
+    fn from_config(config: &ProviderConfig) -> Result<Spotify> {
+        let mut spotify = Spotify::new()?;
+        spotify.set_reporter(config.reporter.clone());
 
-        let playlist = self
-            .client
-            .playlist_items_manual(playlist_id, None, None, None, None)
-            .await;
-
-        let playlist_items = playlist
-            .iter()
-            .flat_map(|page| {
-                return page.items.iter().map(|playlist_item| {
-                    if let Some(playable) = &playlist_item.track {
-                        match playable {
-                            PlayableItem::Track(track) => {
-                                let id = if let Some(track_id) = &track.id {
-                                    PlaylistItemId::Spotify(track_id.to_string())
-                                } else {
-                                    return None;
-                                };
-
-                                let name = track.name.clone();
-                                let artists = track
-                                    .artists
-                                    .iter()
-                                    .map(|artist| artist.name.clone())
-                                    .collect::<Vec<_>>()
-                                    .join(", ");
-
-                                let handle = format!("{} - {}", name, artists);
-
-                                return Some(PlaylistItem {
-                                    id,
-                                    name,
-                                    artists,
-                                    handle,
-                                });
-                            }
-                            PlayableItem::Episode(_) => None,
-                        }
-                    } else {
-                        return None;
-                    }
-                });
-            })
-            .flat_map(|playlist_item| playlist_item)
-            .collect::<Vec<_>>();
+        Ok(spotify)
+    }
+
+    async fn search_track(&self, playlist_item: &PlaylistItem) -> Result<Option<PlaylistItem>> {
+        if let Some(isrc) = &playlist_item.isrc {
+            if let Some(track) = self.search_tracks(&format!("isrc:{}", isrc)).await? {
+                return Ok(track_playlist_item(&track));
+            }
+        }
+
+        // Enriched items are searched for the way MusicBrainz credits them.
+        let artist = playlist_item
+            .credited_artists()
+            .split(", ")
+            .next()
+            .unwrap_or_default();
+        let query = spotify_query(artist, playlist_item.credited_name());
 
-        println!("{}", "Playlist loaded!".green());
+        let track = match self.search_tracks(&query).await? {
+            Some(track) => Some(track),
+            None => self.search_tracks(&playlist_item.credited_handle()).await?,
+        };
 
-        return playlist_items;
+        Ok(track.as_ref().and_then(track_playlist_item))
     }
 
-    async fn parse_playlist_items(&self, playlist_items: Vec<PlaylistItem>) -> Vec<PlaylistItem> {
-        println!("{}", "Transforming playlist...".yellow());
+    /// Client credentials need no user interaction, so matching requests them
+    /// on its own instead of relying on an earlier authenticate call.
+    async fn ensure_token(&self) -> Result<()> {
+        if self.client.get_token().lock().await.unwrap().is_none() {
+            self.client.request_token().await?;
+        }
 
-        let pb = ProgressBar::new(playlist_items.len() as u64);
-        pb.set_style(
-            ProgressStyle::with_template("{bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-                .unwrap()
-                .progress_chars("##-"),
-        );
+        Ok(())
+    }
 
-        let mut new_playlist_items = vec![];
+    /// Asks the user to authorize linksen in the browser, unless a token of an
+    /// earlier authorization is cached in the data directory.
+    async fn authorize_user(&self) -> Result<&AuthCodeSpotify> {
+        if let Some(user_client) = self.user_client.get() {
+            return Ok(user_client);
+        }
 
-        for playlist_item in playlist_items.iter() {
-            pb.set_message(format!("{}", playlist_item.name));
+        let oauth = OAuth {
+            redirect_uri: self.redirect_uri.clone(),
+            scopes: scopes!("playlist-read-private", "playlist-read-collaborative"),
+            ..Default::default()
+        };
+        let config = Config {
+            cache_path: data_dir()?.join(TOKEN_CACHE),
+            token_cached: true,
+            token_refreshing: true,
+            ..Default::default()
+        };
+        let user_client = AuthCodeSpotify::with_config(self.client.creds.clone(), oauth, config);
 
-            if let PlaylistItemId::Spotify(_) = playlist_item.id {
-                new_playlist_items.push(playlist_item.clone());
-            } else {
-                let video_id = self.search(&playlist_item.handle).await;
+        let cached = match user_client.read_token_cache(true).await {
+            Ok(Some(token)) => {
+                let expired = token.is_expired();
+                *user_client.token.lock().await.unwrap() = Some(token);
 
-                if let Some(video_id) = video_id {
-                    new_playlist_items.push(PlaylistItem {
-                        id: PlaylistItemId::Spotify(video_id),
-                        name: playlist_item.name.clone(),
-                        handle: playlist_item.handle.clone(),
-                        artists: playlist_item.artists.clone(),
-                    })
-                }
+                !expired || user_client.refresh_token().await.is_ok()
             }
+            _ => false,
+        };
+
+        if !cached {
+            self.reporter.report(&Event::AuthorizationRequired {
+                provider: "Spotify".into(),
+                url: user_client.get_authorize_url(false)?,
+            });
+
+            let parameters = wait_for_redirect(&self.redirect_uri).await?;
+            let code = parameters
+                .get("code")
+                .filter(|_| parameters.get("state") == Some(&user_client.oauth.state))
+                .ok_or_else(|| {
+                    Error::UnexpectedResponse(format!(
+                        "Spotify authorization failed: {}",
+                        parameters
+                            .get("error")
+                            .map_or("no code", |error| error.as_str())
+                    ))
+                })?;
 
-            pb.inc(1);
+            user_client.request_token(code).await?;
         }
 
-        pb.finish_with_message(format!("{}", "Transformed playlist!".green()));
+        Ok(self.user_client.get_or_init(|| user_client))
+    }
+
+    /// Reads the playlist on behalf of the user once they authorized linksen,
+    /// so that their private playlists can be read as well.
+    async fn full_playlist(&self, url: &str) -> Result<FullPlaylist> {
+        let playlist_id = extract_playlist_id(url)
+            .and_then(|playlist_id| PlaylistId::from_id(playlist_id).ok())
+            .ok_or_else(|| Error::InvalidUrl(url.to_string()))?;
+
+        let playlist = match self.user_client.get() {
+            Some(user_client) => user_client.playlist(playlist_id, None, None).await?,
+            None => self.client.playlist(playlist_id, None, None).await?,
+        };
+
+        Ok(playlist)
+    }
+
+    async fn get_own_playlists(&self) -> Result<Vec<PlaylistSummary>> {
+        let user_client = self.authorize_user().await?;
+        let me = user_client.me().await?;
+
+        let mut playlists = vec![];
+
+        loop {
+            let page = user_client
+                .current_user_playlists_manual(Some(50), Some(playlists.len() as u32))
+                .await?;
 
-        return new_playlist_items;
+            playlists.extend(page.items.iter().map(|listed| PlaylistSummary {
+                url: format!("https://open.spotify.com/playlist/{}", listed.id.id()),
+                name: listed.name.clone(),
+                description: None,
+                owned: listed.owner.id == me.id,
+                item_count: Some(listed.tracks.total as usize),
+            }));
+
+            if page.next.is_none() || page.items.is_empty() {
+                return Ok(playlists);
+            }
+        }
     }
 
-    async fn search(&self, query: &String) -> Option<String> {
+    async fn search_tracks(&self, query: &str) -> Result<Option<FullTrack>> {
+        self.ensure_token().await?;
+
         let search_result = self
             .client
             .search(
@@ -132,30 +212,300 @@ impl MusicClient for Spotify {
                 Some(1),
                 None,
             )
-            .await;
-
-        if let Ok(search_result) = search_result {
-            return match search_result {
-                SearchResult::Tracks(tracks_page) => {
-                    if let Some(item) = tracks_page.items.first() {
-                        if let Some(id) = &item.id {
-                            Some(id.to_string().replace("spotify:track:", ""))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
+            .await?;
+
+        match search_result {
+            SearchResult::Tracks(tracks_page) => Ok(tracks_page.items.into_iter().next()),
+            _ => Ok(None),
+        }
+    }
+}
+
+pub struct SpotifyProvider;
+
+impl Provider for SpotifyProvider {
+    fn name(&self) -> &'static str {
+        "spotify"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Spotify"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            search: true,
+            ..Default::default()
+        }
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        location.contains("open.spotify.com")
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(Some(Box::new(Spotify::from_config(config)?)))
+    }
+
+    fn searcher(&self, config: &ProviderConfig) -> Result<Option<Box<dyn TrackSearcher>>> {
+        Ok(Some(Box::new(Spotify::from_config(config)?)))
+    }
+}
+
+#[async_trait]
+impl MusicClient for Spotify {
+    async fn authenticate(&mut self) -> Result<()> {
+        self.client.request_token().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PlaylistSource for Spotify {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::LoadingStarted);
+
+        let playlist_id = extract_playlist_id(url)
+            .and_then(|playlist_id| PlaylistId::from_id(playlist_id).ok())
+            .ok_or_else(|| Error::InvalidUrl(url.to_string()))?;
+
+        let mut playlist_items = vec![];
+        let mut offset = 0;
+
+        loop {
+            let page = match self.user_client.get() {
+                Some(user_client) => items_page(user_client, &playlist_id, offset).await?,
+                None => items_page(&self.client, &playlist_id, offset).await?,
+            };
+            offset += page.items.len() as u32;
+
+            playlist_items.extend(page.items.iter().filter_map(|playlist_item| {
+                match playlist_item.track.as_ref()? {
+                    PlayableItem::Track(track) => track_playlist_item(track),
+                    PlayableItem::Episode(episode) => Some(episode_playlist_item(episode)),
+                }
+            }));
+
+            if page.next.is_none() || page.items.is_empty() {
+                break;
+            }
+        }
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+
+    async fn get_snapshot(&self, url: &str) -> Result<Option<String>> {
+        Ok(Some(self.full_playlist(url).await?.snapshot_id))
+    }
+
+    async fn get_item(&self, url: &str) -> Result<PlaylistItem> {
+        self.ensure_token().await?;
+
+        let invalid_url = || Error::InvalidUrl(url.to_string());
+
+        match parse_location(url) {
+            PlaylistItemId::Spotify(id) => {
+                let track_id = TrackId::from_id(&id).map_err(|_| invalid_url())?;
+                let track = self.client.track(track_id).await?;
+
+                track_playlist_item(&track).ok_or_else(invalid_url)
+            }
+            PlaylistItemId::SpotifyEpisode(id) => {
+                let episode_id = EpisodeId::from_id(&id).map_err(|_| invalid_url())?;
+                let episode = self.client.get_an_episode(episode_id, None).await?;
+
+                Ok(episode_playlist_item(&episode))
+            }
+            _ => Err(invalid_url()),
+        }
+    }
+
+    /// The playlists of the authorized user include their private ones and
+    /// those they follow, but leave out the descriptions. The playlists of a
+    /// user given by a link are only the public ones.
+    async fn get_playlists(&self, user: Option<&str>) -> Result<Vec<PlaylistSummary>> {
+        let user = match user {
+            Some(user) => user,
+            None => return self.get_own_playlists().await,
+        };
+
+        self.ensure_token().await?;
+
+        let user_id = extract_user_id(user)
+            .and_then(|user_id| UserId::from_id(user_id).ok())
+            .ok_or_else(|| Error::InvalidUrl(user.to_string()))?;
+
+        let mut playlists = vec![];
+
+        loop {
+            let limit = "50".to_string();
+            let offset = playlists.len().to_string();
+            let query = Query::from([("limit", limit.as_str()), ("offset", offset.as_str())]);
+            let response = self
+                .client
+                .api_get(&format!("users/{}/playlists", user_id.id()), &query)
+                .await?;
+            let page: Page<ListedPlaylist> = serde_json::from_str(&response)?;
+
+            playlists.extend(page.items.iter().map(|listed| {
+                PlaylistSummary {
+                    url: format!("https://open.spotify.com/playlist/{}", listed.id.id()),
+                    name: listed.name.clone(),
+                    description: listed
+                        .description
+                        .clone()
+                        .filter(|description| !description.is_empty()),
+                    owned: listed.owner.id.id() == user_id.id(),
+                    item_count: Some(listed.tracks.total as usize),
+                }
+            }));
+
+            if page.next.is_none() || page.items.is_empty() {
+                return Ok(playlists);
+            }
+        }
+    }
+
+    async fn get_description(&self, url: &str) -> Result<Option<String>> {
+        Ok(self
+            .full_playlist(url)
+            .await?
+            .description
+            .filter(|description| !description.is_empty()))
+    }
+}
+
+#[async_trait]
+impl TrackSearcher for Spotify {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        self.reporter.report(&Event::TransformStarted {
+            total: playlist_items.len(),
+        });
+
+        let mut parsed_playlist_items = ParsedPlaylistItems::default();
+
+        for playlist_item in playlist_items.into_iter() {
+            self.reporter.report(&Event::ItemSearching {
+                item: playlist_item.clone(),
+            });
+
+            let item_match = match playlist_item.id {
+                PlaylistItemId::Spotify(_) | PlaylistItemId::SpotifyEpisode(_) => ItemMatch {
+                    target: Some(playlist_item.clone()),
+                    source: playlist_item,
+                    confidence: 1.0,
+                },
+                _ => {
+                    let target = searched(
+                        self.reporter.as_ref(),
+                        &playlist_item,
+                        self.search_track(&playlist_item).await,
+                    );
+                    let confidence = target
+                        .as_ref()
+                        .map_or(0.0, |target| confidence(&playlist_item, target));
+
+                    ItemMatch {
+                        source: playlist_item,
+                        target,
+                        confidence,
                     }
                 }
-                _ => None,
             };
+
+            report_match(self.reporter.as_ref(), &item_match);
+            parsed_playlist_items.items.push(item_match);
         }
 
-        return None;
+        self.reporter.report(&Event::TransformFinished);
+
+        Ok(parsed_playlist_items)
     }
 
-    async fn create_playlist(&self, _playlist_items: &Vec<PlaylistItem>) {
-        unimplemented!()
+    async fn search(&self, query: &str) -> Result<Option<String>> {
+        Ok(self
+            .search_tracks(query)
+            .await?
+            .and_then(|track| track.id)
+            .map(|id| id.id().to_string()))
+    }
+}
+
+/// A playlist as the listings of a user return it. rspotify's
+/// `SimplifiedPlaylist` leaves out the description they include.
+#[derive(Deserialize)]
+struct ListedPlaylist {
+    id: PlaylistId<'static>,
+    name: String,
+    description: Option<String>,
+    owner: PublicUser,
+    tracks: PlaylistTracksRef,
+}
+
+/// A page of the items of a playlist, read by either of the clients.
+async fn items_page(
+    client: &impl BaseClient,
+    playlist_id: &PlaylistId<'_>,
+    offset: u32,
+) -> Result<Page<SpotifyPlaylistItem>> {
+    Ok(client
+        .playlist_items_manual(playlist_id.as_ref(), None, None, Some(100), Some(offset))
+        .await?)
+}
+
+fn track_playlist_item(track: &FullTrack) -> Option<PlaylistItem> {
+    let id = PlaylistItemId::Spotify(track.id.as_ref()?.id().to_string());
+
+    let name = track.name.clone();
+    let artists = track
+        .artists
+        .iter()
+        .map(|artist| artist.name.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let handle = format!("{} - {}", name, artists);
+
+    Some(PlaylistItem {
+        id,
+        kind: PlaylistItemKind::Track,
+        name,
+        artists,
+        handle,
+        duration: track.duration.to_std().ok(),
+        album: Some(track.album.name.clone()),
+        isrc: track.external_ids.get("isrc").cloned(),
+        mbid: None,
+        canonical: None,
+    })
+}
+
+fn episode_playlist_item(episode: &FullEpisode) -> PlaylistItem {
+    let name = episode.name.clone();
+    let artists = episode.show.name.clone();
+
+    let handle = format!("{} {}", artists, name);
+
+    PlaylistItem {
+        id: PlaylistItemId::SpotifyEpisode(episode.id.id().to_string()),
+        kind: PlaylistItemKind::Episode,
+        name,
+        artists,
+        handle,
+        duration: episode.duration.to_std().ok(),
+        album: None,
+        isrc: None,
+        mbid: None,
+        canonical: None,
     }
 }
 
@@ -173,5 +523,24 @@ fn extract_playlist_id(url: &str) -> Option<&str> {
         }
     }
 
-    return None;
+    None
+}
+
+/// Accepts profile links such as https://open.spotify.com/user/<id> as well
+/// as the bare user ID.
+fn extract_user_id(user: &str) -> Option<&str> {
+    let user = user.trim();
+    let prefix = "open.spotify.com/user/";
+
+    let user_id = match user.find(prefix) {
+        Some(start_index) => {
+            let rest_of_string = &user[start_index + prefix.len()..];
+
+            rest_of_string.split(['/', '?']).next()?
+        }
+        None if !user.contains('/') => user,
+        None => return None,
+    };
+
+    (!user_id.is_empty()).then_some(user_id)
 }
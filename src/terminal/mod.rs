@@ -0,0 +1,93 @@
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use linksen::{Event, Reporter};
+use std::{io::Write, sync::Mutex};
+
+/// Shows progress the way linksen always has: coloured status lines and a
+/// progress bar while the playlist is transformed.
+#[derive(Default)]
+pub struct TerminalReporter {
+    pb: Mutex<Option<ProgressBar>>,
+}
+
+impl Reporter for TerminalReporter {
+    fn report(&self, event: &Event) {
+        match event {
+            Event::AuthorizationRequired { provider, url } => {
+                eprintln!(
+                    "{}",
+                    format!("Open this URL to authorize linksen with {}:", provider).yellow()
+                );
+                eprintln!("{}", url);
+            }
+            Event::LoadingStarted => {
+                eprint!("{}", "Loading playlist... ".yellow());
+                let _ = std::io::stderr().flush();
+            }
+            Event::LoadingFinished { .. } => eprintln!("{}", "Playlist loaded!".green()),
+            Event::EnrichmentStarted { total } => {
+                eprintln!("{}", "Looking up tracks on MusicBrainz...".yellow());
+
+                *self.pb.lock().unwrap() = Some(progress_bar(*total));
+            }
+            Event::ItemEnriched { item } => {
+                if let Some(pb) = self.pb.lock().unwrap().as_ref() {
+                    pb.set_message(item.name.clone());
+                    pb.inc(1);
+                }
+            }
+            Event::EnrichmentFinished { enriched } => {
+                if let Some(pb) = self.pb.lock().unwrap().take() {
+                    pb.finish_with_message(format!(
+                        "{}",
+                        format!("Found {} tracks on MusicBrainz!", enriched).green()
+                    ));
+                }
+            }
+            Event::TransformStarted { total } => {
+                eprintln!("{}", "Transforming playlist...".yellow());
+
+                *self.pb.lock().unwrap() = Some(progress_bar(*total));
+            }
+            Event::ItemSearching { item } => {
+                if let Some(pb) = self.pb.lock().unwrap().as_ref() {
+                    pb.set_message(item.name.clone());
+                }
+            }
+            Event::ItemMatched { .. } | Event::ItemUnmatched { .. } => {
+                if let Some(pb) = self.pb.lock().unwrap().as_ref() {
+                    pb.inc(1);
+                }
+            }
+            Event::TransformFinished => {
+                if let Some(pb) = self.pb.lock().unwrap().take() {
+                    pb.finish_with_message(format!("{}", "Transformed playlist!".green()));
+                }
+            }
+            Event::CreatingPlaylist { .. } => {
+                eprint!("{}", "Creating playlist... ".yellow());
+                let _ = std::io::stderr().flush();
+            }
+            Event::ItemInserted { .. } => {}
+            Event::ItemFailed { item, error } => {
+                eprintln!(
+                    "{}",
+                    format!("[ERROR] {}: {}", item.name, error).on_red().white()
+                );
+            }
+            Event::PlaylistCreated { .. } => eprintln!("{}", "Created playlist!".green()),
+            Event::Done { .. } => {}
+        }
+    }
+}
+
+fn progress_bar(total: usize) -> ProgressBar {
+    let pb = ProgressBar::new(total as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
+            .unwrap()
+            .progress_chars("##-"),
+    );
+
+    pb
+}
@@ -0,0 +1,765 @@
+use std::{
+    collections::HashSet,
+    env,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::header::{HeaderValue, ETAG, IF_NONE_MATCH};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::events::{own_items, quiet, report_match, searched, Event, SharedReporter};
+use crate::matching::confidence;
+use crate::oauth::{read_cached_token, write_cached_token};
+use crate::playlist_file::parse_location;
+use crate::registry::{Capabilities, Provider, ProviderConfig};
+use crate::types::{
+    ItemMatch, MusicClient, ParsedPlaylistItems, PlaylistItem, PlaylistItemId, PlaylistItemKind,
+    PlaylistOptions, PlaylistSink, PlaylistSource, PlaylistSummary, TidalDeviceAuthorization,
+    TidalPage, TidalPlaylist, TidalResources, TidalToken, TidalTrack, TidalUserPlaylist,
+    TrackSearcher,
+};
+
+const API_URL: &str = "https://api.tidal.com/v1";
+const OPENAPI_URL: &str = "https://openapi.tidal.com/v2";
+const AUTH_URL: &str = "https://auth.tidal.com/v1";
+const COUNTRY_CODE: &str = "US";
+const TOKEN_CACHE: &str = "tidal.token";
+const PAGE_SIZE: usize = 100;
+
+/// Reads and creates Tidal playlists and matches items against the Tidal
+/// catalogue, preferring exact ISRC matches. Every request needs a user token,
+/// which is obtained with the device code flow.
+pub struct Tidal {
+    http: reqwest::Client,
+    api_url: String,
+    openapi_url: String,
+    auth_url: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    country_code: String,
+    credentials: Mutex<Option<TidalCredentials>>,
+    reporter: SharedReporter,
+}
+
+/// What is kept of a token between runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TidalCredentials {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: u64,
+    user_id: u64,
+    country_code: String,
+}
+
+impl Tidal {
+    /// Takes the client credentials from `TIDAL_CLIENT_ID` and
+    /// `TIDAL_CLIENT_SECRET`, and the country whose catalogue is searched
+    /// from `TIDAL_COUNTRY_CODE` until the user's own is known.
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_url: API_URL.into(),
+            openapi_url: OPENAPI_URL.into(),
+            auth_url: AUTH_URL.into(),
+            client_id: env::var("TIDAL_CLIENT_ID").ok(),
+            client_secret: env::var("TIDAL_CLIENT_SECRET").ok(),
+            country_code: env::var("TIDAL_COUNTRY_CODE").unwrap_or(COUNTRY_CODE.into()),
+            credentials: Mutex::new(None),
+            reporter: quiet(),
+        }
+    }
+
+    pub fn with_credentials(client_id: String, client_secret: Option<String>) -> Self {
+        Self {
+            client_id: Some(client_id),
+            client_secret,
+            ..Self::new()
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+
+    /// Uses an already issued token of the given user instead of asking for
+    /// one.
+    pub fn set_access_token(&mut self, access_token: String, user_id: u64) {
+        self.credentials = Mutex::new(Some(TidalCredentials {
+            access_token,
+            refresh_token: None,
+            expires_at: u64::MAX,
+            user_id,
+            country_code: self.country_code.clone(),
+        }));
+    }
+
+    /// Sends the requests to other servers than Tidal's, e.g. a local
+    /// stand-in of the APIs.
+    pub fn set_base_urls(&mut self, api_url: String, openapi_url: String, auth_url: String) {
+        self.api_url = api_url;
+        self.openapi_url = openapi_url;
+        self.auth_url = auth_url;
+    }
+
+    fn from_config(config: &ProviderConfig) -> Tidal {
+        let mut tidal = Tidal::new();
+        tidal.set_reporter(config.reporter.clone());
+
+        tidal
+    }
+
+    /// The user's token, which is taken from the cache, refreshed or asked
+    /// for on first use, so that matching needs no earlier authenticate call.
+    async fn credentials(&self) -> Result<TidalCredentials> {
+        if let Some(credentials) = self.credentials.lock().unwrap().clone() {
+            return Ok(credentials);
+        }
+
+        let cached = read_cached_token(TOKEN_CACHE)
+            .and_then(|cached| serde_json::from_str::<TidalCredentials>(&cached).ok());
+
+        if let Some(cached) = cached {
+            if cached.expires_at > now() + 60 {
+                *self.credentials.lock().unwrap() = Some(cached.clone());
+                return Ok(cached);
+            }
+
+            if let Some(token) = self.refresh(&cached).await {
+                return self.store_token(token, Some(cached));
+            }
+        }
+
+        let token = self.authorize_device().await?;
+
+        self.store_token(token, None)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T> {
+        let credentials = self.credentials().await?;
+
+        Ok(self
+            .http
+            .get(url)
+            .bearer_auth(&credentials.access_token)
+            .query(&[("countryCode", credentials.country_code.as_str())])
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn request_token(&self, form: &[(&str, &str)]) -> Result<reqwest::Response> {
+        let client_id = self
+            .client_id
+            .as_deref()
+            .ok_or(Error::MissingCredentials("Tidal"))?;
+
+        let mut form = form.to_vec();
+        form.push(("client_id", client_id));
+
+        let mut request = self
+            .http
+            .post(format!("{}/oauth2/token", self.auth_url))
+            .form(&form);
+
+        if let Some(client_secret) = &self.client_secret {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        Ok(request.send().await?)
+    }
+
+    async fn authorize_device(&self) -> Result<TidalToken> {
+        let client_id = self
+            .client_id
+            .as_deref()
+            .ok_or(Error::MissingCredentials("Tidal"))?;
+
+        let authorization: TidalDeviceAuthorization = self
+            .http
+            .post(format!("{}/oauth2/device_authorization", self.auth_url))
+            .form(&[("client_id", client_id), ("scope", "r_usr w_usr")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Tidal leaves the scheme out of the verification link.
+        let url = match authorization.verification_uri_complete.contains("://") {
+            true => authorization.verification_uri_complete.clone(),
+            false => format!("https://{}", authorization.verification_uri_complete),
+        };
+        self.reporter.report(&Event::AuthorizationRequired {
+            provider: "Tidal".into(),
+            url,
+        });
+
+        let mut interval = Duration::from_secs(authorization.interval.max(1));
+        let expires_at = now() + authorization.expires_in;
+
+        loop {
+            tokio::time::sleep(interval).await;
+
+            if now() > expires_at {
+                return Err(Error::UnexpectedResponse(
+                    "Tidal authorization was not completed in time".into(),
+                ));
+            }
+
+            let response = self
+                .request_token(&[
+                    ("device_code", authorization.device_code.as_str()),
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                    ("scope", "r_usr w_usr"),
+                ])
+                .await?;
+
+            if response.status().is_success() {
+                return Ok(response.json().await?);
+            }
+
+            let error: Value = response.json().await?;
+            match error["error"].as_str() {
+                Some("authorization_pending") => {}
+                Some("slow_down") => interval += Duration::from_secs(5),
+                _ => {
+                    return Err(Error::UnexpectedResponse(format!(
+                        "Tidal authorization failed: {}",
+                        error["error_description"]
+                            .as_str()
+                            .or(error["error"].as_str())
+                            .unwrap_or("unknown error")
+                    )))
+                }
+            }
+        }
+    }
+
+    async fn refresh(&self, credentials: &TidalCredentials) -> Option<TidalToken> {
+        let refresh_token = credentials.refresh_token.as_deref()?;
+
+        let response = self
+            .request_token(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .await
+            .ok()?;
+
+        response.error_for_status().ok()?.json().await.ok()
+    }
+
+    fn store_token(
+        &self,
+        token: TidalToken,
+        previous: Option<TidalCredentials>,
+    ) -> Result<TidalCredentials> {
+        // Refreshed tokens come without the user they belong to.
+        let (user_id, country_code) = match (token.user, previous.as_ref()) {
+            (Some(user), _) => (user.user_id, user.country_code),
+            (None, Some(previous)) => (previous.user_id, previous.country_code.clone()),
+            (None, None) => {
+                return Err(Error::UnexpectedResponse(
+                    "Tidal token does not name its user".into(),
+                ))
+            }
+        };
+
+        let credentials = TidalCredentials {
+            access_token: token.access_token,
+            refresh_token: token
+                .refresh_token
+                .or(previous.and_then(|previous| previous.refresh_token)),
+            expires_at: now() + token.expires_in,
+            user_id,
+            country_code,
+        };
+
+        write_cached_token(TOKEN_CACHE, &serde_json::to_string(&credentials)?)?;
+        *self.credentials.lock().unwrap() = Some(credentials.clone());
+
+        Ok(credentials)
+    }
+
+    /// Every change to a playlist has to name the version it applies to.
+    async fn playlist_etag(&self, playlist_url: &str) -> Result<Option<HeaderValue>> {
+        let credentials = self.credentials().await?;
+
+        Ok(self
+            .http
+            .get(playlist_url)
+            .bearer_auth(&credentials.access_token)
+            .query(&[("countryCode", credentials.country_code.as_str())])
+            .send()
+            .await?
+            .error_for_status()?
+            .headers()
+            .get(ETAG)
+            .cloned())
+    }
+
+    async fn get_tracks(&self, playlist_id: &str) -> Result<Vec<TidalTrack>> {
+        let url = format!("{}/playlists/{}/tracks", self.api_url, playlist_id);
+        let mut tracks = vec![];
+
+        loop {
+            let offset = tracks.len().to_string();
+            let page: TidalPage<TidalTrack> = self
+                .get(
+                    &url,
+                    &[("limit", &PAGE_SIZE.to_string()), ("offset", &offset)],
+                )
+                .await?;
+
+            let count = page.items.len();
+            tracks.extend(page.items);
+
+            let total = page.total_number_of_items.unwrap_or(tracks.len());
+            if count < PAGE_SIZE || tracks.len() >= total {
+                return Ok(tracks);
+            }
+        }
+    }
+
+    async fn search_track(&self, playlist_item: &PlaylistItem) -> Result<Option<TidalTrack>> {
+        if let Some(isrc) = &playlist_item.isrc {
+            // Only the newer API filters by ISRC; the track itself is then
+            // read from the same API as everything else.
+            let resources: TidalResources = self
+                .get(
+                    &format!("{}/tracks", self.openapi_url),
+                    &[("filter[isrc]", isrc)],
+                )
+                .await?;
+
+            if let Some(resource) = resources.data.first() {
+                let url = format!("{}/tracks/{}", self.api_url, resource.id);
+
+                return Ok(Some(self.get(&url, &[]).await?));
+            }
+        }
+
+        let query = format!(
+            "{} {}",
+            playlist_item.credited_artists(),
+            playlist_item.credited_name()
+        );
+
+        let handle = playlist_item.credited_handle();
+        match self.search_tracks(&query).await? {
+            Some(track) => Ok(Some(track)),
+            None if query != handle => self.search_tracks(&handle).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn search_tracks(&self, query: &str) -> Result<Option<TidalTrack>> {
+        let page: TidalPage<TidalTrack> = self
+            .get(
+                &format!("{}/search/tracks", self.api_url),
+                &[("query", query), ("limit", "1")],
+            )
+            .await?;
+
+        Ok(page.items.into_iter().next())
+    }
+}
+
+impl Default for Tidal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct TidalProvider;
+
+impl Provider for TidalProvider {
+    fn name(&self) -> &'static str {
+        "tidal"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Tidal"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            search: true,
+            write: true,
+            delete: true,
+            ..Default::default()
+        }
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        location.contains("tidal.com")
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(Some(Box::new(Tidal::from_config(config))))
+    }
+
+    fn searcher(&self, config: &ProviderConfig) -> Result<Option<Box<dyn TrackSearcher>>> {
+        Ok(Some(Box::new(Tidal::from_config(config))))
+    }
+
+    fn sink(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSink>>> {
+        Ok(Some(Box::new(Tidal::from_config(config))))
+    }
+}
+
+#[async_trait]
+impl MusicClient for Tidal {
+    async fn authenticate(&mut self) -> Result<()> {
+        self.credentials().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PlaylistSource for Tidal {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.reporter.report(&Event::LoadingStarted);
+
+        let playlist_id = extract_playlist_id(url).ok_or(Error::InvalidUrl(url.to_string()))?;
+        let playlist_items = self
+            .get_tracks(&playlist_id)
+            .await?
+            .iter()
+            .map(track_playlist_item)
+            .collect::<Vec<_>>();
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+
+    async fn get_item(&self, url: &str) -> Result<PlaylistItem> {
+        match parse_location(url) {
+            PlaylistItemId::Tidal(id) => {
+                let url = format!("{}/tracks/{}", self.api_url, id);
+
+                Ok(track_playlist_item(&self.get(&url, &[]).await?))
+            }
+            _ => Err(Error::InvalidUrl(url.to_string())),
+        }
+    }
+
+    /// The playlists the user created or added to their favourites. Those of
+    /// other users cannot be listed.
+    async fn get_playlists(&self, user: Option<&str>) -> Result<Vec<PlaylistSummary>> {
+        if user.is_some() {
+            return Err(Error::Unsupported(
+                "listing the playlists of another Tidal user",
+            ));
+        }
+
+        let user_id = self.credentials().await?.user_id;
+        let url = format!(
+            "{}/users/{}/playlistsAndFavoritePlaylists",
+            self.api_url, user_id
+        );
+        let mut playlists = vec![];
+
+        loop {
+            let offset = playlists.len().to_string();
+            let page: TidalPage<TidalUserPlaylist> = self
+                .get(
+                    &url,
+                    &[("limit", &PAGE_SIZE.to_string()), ("offset", &offset)],
+                )
+                .await?;
+
+            let count = page.items.len();
+            playlists.extend(page.items.into_iter().map(|listed| {
+                let playlist = listed.playlist;
+
+                PlaylistSummary {
+                    url: format!("https://tidal.com/browse/playlist/{}", playlist.uuid),
+                    name: playlist.title.unwrap_or_default(),
+                    description: playlist
+                        .description
+                        .filter(|description| !description.is_empty()),
+                    owned: playlist
+                        .creator
+                        .is_some_and(|creator| creator.id == user_id),
+                    item_count: playlist.number_of_tracks,
+                }
+            }));
+
+            let total = page.total_number_of_items.unwrap_or(playlists.len());
+            if count < PAGE_SIZE || playlists.len() >= total {
+                return Ok(playlists);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TrackSearcher for Tidal {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        self.reporter.report(&Event::TransformStarted {
+            total: playlist_items.len(),
+        });
+
+        let mut parsed_playlist_items = ParsedPlaylistItems::default();
+
+        for playlist_item in playlist_items.into_iter() {
+            self.reporter.report(&Event::ItemSearching {
+                item: playlist_item.clone(),
+            });
+
+            let item_match = match playlist_item.id {
+                PlaylistItemId::Tidal(_) => ItemMatch {
+                    target: Some(playlist_item.clone()),
+                    source: playlist_item,
+                    confidence: 1.0,
+                },
+                _ => {
+                    let target = searched(
+                        self.reporter.as_ref(),
+                        &playlist_item,
+                        self.search_track(&playlist_item).await,
+                    )
+                    .as_ref()
+                    .map(track_playlist_item);
+                    let confidence = target
+                        .as_ref()
+                        .map_or(0.0, |target| confidence(&playlist_item, target));
+
+                    ItemMatch {
+                        source: playlist_item,
+                        target,
+                        confidence,
+                    }
+                }
+            };
+
+            report_match(self.reporter.as_ref(), &item_match);
+            parsed_playlist_items.items.push(item_match);
+        }
+
+        self.reporter.report(&Event::TransformFinished);
+
+        Ok(parsed_playlist_items)
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<String>> {
+        Ok(self
+            .search_tracks(query)
+            .await?
+            .map(|track| track.id.to_string()))
+    }
+}
+
+#[async_trait]
+impl PlaylistSink for Tidal {
+    /// New Tidal playlists are private, so the privacy is ignored.
+    async fn create_playlist(
+        &self,
+        options: &PlaylistOptions,
+        playlist_items: &[PlaylistItem],
+    ) -> Result<String> {
+        let credentials = self.credentials().await?;
+
+        self.reporter.report(&Event::CreatingPlaylist {
+            name: options.name.clone(),
+        });
+
+        let playlist: TidalPlaylist = self
+            .http
+            .post(format!(
+                "{}/users/{}/playlists",
+                self.api_url, credentials.user_id
+            ))
+            .bearer_auth(&credentials.access_token)
+            .query(&[("countryCode", credentials.country_code.as_str())])
+            .form(&[
+                ("title", options.name.as_str()),
+                (
+                    "description",
+                    options.description.as_deref().unwrap_or_default(),
+                ),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        self.add_items(&playlist.uuid, playlist_items).await?;
+
+        self.reporter.report(&Event::PlaylistCreated {
+            id: playlist.uuid.clone(),
+        });
+
+        Ok(playlist.uuid)
+    }
+
+    async fn add_items(&self, playlist_id: &str, playlist_items: &[PlaylistItem]) -> Result<()> {
+        let credentials = self.credentials().await?;
+        let playlist_url = format!("{}/playlists/{}", self.api_url, playlist_id);
+        let mut etag = self.playlist_etag(&playlist_url).await?;
+
+        let playlist_items = own_items(self.reporter.as_ref(), "Tidal", playlist_items, |id| {
+            matches!(id, PlaylistItemId::Tidal(_))
+        });
+        if playlist_items.is_empty() {
+            return Ok(());
+        }
+
+        // Items are inserted at an index, which for appending is the end.
+        let mut position = self.get_tracks(playlist_id).await?.len();
+        for chunk in playlist_items.chunks(50) {
+            let track_ids = track_ids(chunk);
+
+            let mut request = self
+                .http
+                .post(format!("{}/items", playlist_url))
+                .bearer_auth(&credentials.access_token)
+                .query(&[("countryCode", credentials.country_code.as_str())])
+                .form(&[
+                    ("trackIds", track_ids.as_str()),
+                    ("toIndex", &position.to_string()),
+                    ("onArtifactNotFound", "SKIP"),
+                ]);
+            if let Some(etag) = &etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+
+            let added = match request.send().await {
+                Ok(response) => response.error_for_status().map_err(Error::from),
+                Err(error) => Err(Error::from(error)),
+            };
+
+            if let Ok(response) = &added {
+                etag = response.headers().get(ETAG).cloned().or(etag);
+                position += chunk.len();
+            }
+
+            for playlist_item in chunk {
+                match &added {
+                    Ok(_) => self.reporter.report(&Event::ItemInserted {
+                        item: (*playlist_item).clone(),
+                    }),
+                    Err(error) => self.reporter.report(&Event::ItemFailed {
+                        item: (*playlist_item).clone(),
+                        error: format!("not added: {}", error),
+                    }),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tidal removes items by their index, so the playlist is read first.
+    async fn remove_items(&self, playlist_id: &str, playlist_items: &[PlaylistItem]) -> Result<()> {
+        let credentials = self.credentials().await?;
+        let playlist_url = format!("{}/playlists/{}", self.api_url, playlist_id);
+
+        let removed = playlist_items
+            .iter()
+            .filter_map(|playlist_item| match &playlist_item.id {
+                PlaylistItemId::Tidal(id) => Some(id.as_str()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        let indices = self
+            .get_tracks(playlist_id)
+            .await?
+            .iter()
+            .enumerate()
+            .filter(|(_, track)| removed.contains(track.id.to_string().as_str()))
+            .map(|(index, _)| index.to_string())
+            .collect::<Vec<_>>();
+        if indices.is_empty() {
+            return Ok(());
+        }
+
+        let mut request = self
+            .http
+            .delete(format!("{}/items/{}", playlist_url, indices.join(",")))
+            .bearer_auth(&credentials.access_token)
+            .query(&[("countryCode", credentials.country_code.as_str())]);
+        if let Some(etag) = self.playlist_etag(&playlist_url).await? {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        request.send().await?.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+fn track_ids(playlist_items: &[&PlaylistItem]) -> String {
+    playlist_items
+        .iter()
+        .filter_map(|playlist_item| match &playlist_item.id {
+            PlaylistItemId::Tidal(id) => Some(id.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Accepts links such as https://tidal.com/browse/playlist/<uuid> and
+/// https://listen.tidal.com/playlist/<uuid>, as well as the bare UUID.
+fn extract_playlist_id(url: &str) -> Option<String> {
+    let playlist = Regex::new(
+        r"(?:playlist[/:])?([0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12})",
+    )
+    .unwrap();
+
+    playlist
+        .captures(url.trim())
+        .map(|captures| captures[1].to_string())
+}
+
+fn track_playlist_item(track: &TidalTrack) -> PlaylistItem {
+    let name = match &track.version {
+        Some(version) if !version.is_empty() => format!("{} ({})", track.title, version),
+        _ => track.title.clone(),
+    };
+    let artists = track
+        .artists
+        .iter()
+        .map(|artist| artist.name.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let handle = format!("{} - {}", name, artists);
+
+    PlaylistItem {
+        id: PlaylistItemId::Tidal(track.id.to_string()),
+        kind: PlaylistItemKind::Track,
+        name,
+        artists,
+        handle,
+        duration: track.duration.map(Duration::from_secs),
+        album: track.album.as_ref().map(|album| album.title.clone()),
+        isrc: track.isrc.clone(),
+        mbid: None,
+        canonical: None,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -1,112 +1,838 @@
+use crate::error::{Error, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use std::{fmt, str::FromStr, time::Duration};
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponse {
+pub(crate) struct YoutubeResponse {
     pub contents: YoutubeResponseContents,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseContents {
+pub(crate) struct YoutubeResponseContents {
     #[serde(rename = "twoColumnSearchResultsRenderer")]
     pub two_column_search_results_renderer: YoutubeResponseTwoColumnSearchResultsRenderer,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseTwoColumnSearchResultsRenderer {
+pub(crate) struct YoutubeResponseTwoColumnSearchResultsRenderer {
     #[serde(rename = "primaryContents")]
     pub primary_contents: YoutubeResponsePrimaryContents,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponsePrimaryContents {
+pub(crate) struct YoutubeResponsePrimaryContents {
     #[serde(rename = "sectionListRenderer")]
     pub section_list_renderer: YoutubeResponseSectionListRenderer,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseSectionListRenderer {
+pub(crate) struct YoutubeResponseSectionListRenderer {
     #[serde(rename = "contents")]
     pub contents: Vec<YoutubeResponseSectionListRendererContent>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
-pub enum YoutubeResponseSectionListRendererContent {
+pub(crate) enum YoutubeResponseSectionListRendererContent {
     ItemSectionRenderer(YoutubeResponseSectionListRendererContentItemSectionRenderer),
     ContinuationItemRenderer(YoutubeResponseSectionListRendererContentContinuationItemRenderer),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseSectionListRendererContentItemSectionRenderer {
+pub(crate) struct YoutubeResponseSectionListRendererContentItemSectionRenderer {
     #[serde(rename = "itemSectionRenderer")]
     pub item_section_renderer: YoutubeResponseItemSectionRenderer,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseSectionListRendererContentContinuationItemRenderer {
+pub(crate) struct YoutubeResponseSectionListRendererContentContinuationItemRenderer {
     #[serde(rename = "continuationItemRenderer")]
     continuation_item_renderer: YoutubeResponseItemSectionContinuationRenderer,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseItemSectionContinuationRenderer {
+pub(crate) struct YoutubeResponseItemSectionContinuationRenderer {
     trigger: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseItemSectionRenderer {
+pub(crate) struct YoutubeResponseItemSectionRenderer {
     #[serde(rename = "contents")]
     pub contents: Vec<YoutubeResponseItemSectionRendererContent>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
-pub enum YoutubeResponseItemSectionRendererContent {
+pub(crate) enum YoutubeResponseItemSectionRendererContent {
     VideoRenderer(YoutubeResponseItemSectionRendererContentVideoRenderer),
     OtherRenderer(Map<String, Value>),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseItemSectionRendererContentVideoRenderer {
+pub(crate) struct YoutubeResponseItemSectionRendererContentVideoRenderer {
     #[serde(rename = "videoRenderer")]
     pub video_renderer: YoutubeResponseVideoRenderer,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct YoutubeResponseVideoRenderer {
+pub(crate) struct YoutubeResponseVideoRenderer {
     #[serde(rename = "videoId")]
     pub video_id: String,
+    pub title: Option<YoutubeResponseRuns>,
+    #[serde(rename = "ownerText")]
+    pub owner_text: Option<YoutubeResponseRuns>,
+    #[serde(rename = "lengthText")]
+    pub length_text: Option<YoutubeResponseSimpleText>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct YoutubeResponseRuns {
+    pub runs: Vec<YoutubeResponseRun>,
+}
+
+impl YoutubeResponseRuns {
+    pub fn text(&self) -> String {
+        self.runs.iter().map(|run| run.text.as_str()).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct YoutubeResponseRun {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct YoutubeResponseSimpleText {
+    #[serde(rename = "simpleText")]
+    pub simple_text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponse {
+    pub contents: YoutubeMusicResponseContents,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponseContents {
+    pub tabbed_search_results_renderer: YoutubeMusicResponseTabs,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct YoutubeMusicResponseTabs {
+    pub tabs: Vec<YoutubeMusicResponseTab>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponseTab {
+    pub tab_renderer: YoutubeMusicResponseTabRenderer,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct YoutubeMusicResponseTabRenderer {
+    pub content: Option<YoutubeMusicResponseTabContent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponseTabContent {
+    pub section_list_renderer: YoutubeMusicResponseSectionList,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct YoutubeMusicResponseSectionList {
+    #[serde(default)]
+    pub contents: Vec<YoutubeMusicResponseSection>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponseSection {
+    pub music_shelf_renderer: Option<YoutubeMusicResponseShelf>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct YoutubeMusicResponseShelf {
+    #[serde(default)]
+    pub contents: Vec<YoutubeMusicResponseShelfItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponseShelfItem {
+    pub music_responsive_list_item_renderer: Option<YoutubeMusicResponseListItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponseListItem {
+    pub playlist_item_data: Option<YoutubeMusicResponsePlaylistItemData>,
+    #[serde(default)]
+    pub flex_columns: Vec<YoutubeMusicResponseFlexColumn>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponsePlaylistItemData {
+    pub video_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YoutubeMusicResponseFlexColumn {
+    pub music_responsive_list_item_flex_column_renderer: YoutubeMusicResponseFlexColumnRenderer,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct YoutubeMusicResponseFlexColumnRenderer {
+    pub text: Option<YoutubeResponseRuns>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct YoutubeOembed {
+    pub title: String,
+    pub author_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeezerPage<T> {
+    pub data: Vec<T>,
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeezerTrack {
+    pub id: u64,
+    pub title: String,
+    pub duration: Option<u64>,
+    pub isrc: Option<String>,
+    pub artist: DeezerArtist,
+    pub album: Option<DeezerAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeezerArtist {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeezerAlbum {
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeezerPlaylist {
+    pub id: u64,
+    pub title: String,
+    /// Only given when the playlist is read on its own, not in listings.
+    pub description: Option<String>,
+    pub nb_tracks: Option<usize>,
+    pub creator: Option<DeezerUser>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeezerUser {
+    pub id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeezerCreated {
+    pub id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeezerAccessToken {
+    pub access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AppleMusicPage<T> {
+    pub data: Vec<T>,
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AppleMusicSong {
+    pub id: String,
+    pub attributes: Option<AppleMusicSongAttributes>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AppleMusicSongAttributes {
+    pub name: String,
+    pub artist_name: String,
+    pub album_name: Option<String>,
+    pub duration_in_millis: Option<u64>,
+    pub isrc: Option<String>,
+    pub play_params: Option<AppleMusicPlayParams>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AppleMusicPlayParams {
+    pub catalog_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AppleMusicSearch {
+    pub results: AppleMusicSearchResults,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AppleMusicSearchResults {
+    pub songs: Option<AppleMusicPage<AppleMusicSong>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TidalPage<T> {
+    pub items: Vec<T>,
+    pub total_number_of_items: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TidalTrack {
+    pub id: u64,
+    pub title: String,
+    pub version: Option<String>,
+    pub duration: Option<u64>,
+    pub isrc: Option<String>,
+    pub artists: Vec<TidalArtist>,
+    pub album: Option<TidalAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TidalArtist {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TidalAlbum {
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TidalPlaylist {
+    pub uuid: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub number_of_tracks: Option<usize>,
+    pub creator: Option<TidalCreator>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TidalCreator {
+    pub id: u64,
+}
+
+/// An entry of the playlists a user created or added to their favourites.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TidalUserPlaylist {
+    pub playlist: TidalPlaylist,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TidalDeviceAuthorization {
+    pub device_code: String,
+    pub verification_uri_complete: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TidalToken {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_in: u64,
+    pub user: Option<TidalUser>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TidalUser {
+    pub user_id: u64,
+    pub country_code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TidalResources {
+    pub data: Vec<TidalResource>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TidalResource {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SoundCloudPage<T> {
+    pub collection: Vec<T>,
+    pub next_href: Option<String>,
+}
+
+/// What `/resolve` turns a link into: a set ("playlist") or a user.
+#[derive(Debug, Deserialize)]
+pub(crate) struct SoundCloudResource {
+    pub kind: String,
+    pub id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SoundCloudTrack {
+    pub title: String,
+    /// Milliseconds.
+    pub duration: Option<u64>,
+    pub permalink_url: String,
+    pub user: SoundCloudUser,
+    pub publisher_metadata: Option<SoundCloudPublisherMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SoundCloudUser {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SoundCloudPublisherMetadata {
+    pub artist: Option<String>,
+    pub album_title: Option<String>,
+    pub isrc: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SoundCloudAccessToken {
+    pub access_token: String,
+}
+
+/// The tracks of any Last.fm user chart. A chart with a single track has it
+/// as an object rather than a list.
+#[derive(Debug, Deserialize)]
+pub(crate) struct LastfmTracks {
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub track: Vec<LastfmTrack>,
+    #[serde(rename = "@attr")]
+    pub attr: Option<LastfmPageAttr>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LastfmPageAttr {
+    #[serde(rename = "totalPages")]
+    pub total_pages: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LastfmTrack {
+    pub name: String,
+    #[serde(default)]
+    pub mbid: String,
+    /// Seconds, and only in top tracks.
+    pub duration: Option<String>,
+    pub url: String,
+    pub artist: LastfmArtist,
+    pub album: Option<LastfmText>,
+    #[serde(rename = "@attr")]
+    pub attr: Option<LastfmTrackAttr>,
+}
+
+/// Recent tracks name their artist in "#text", the other charts in "name".
+#[derive(Debug, Deserialize)]
+pub(crate) struct LastfmArtist {
+    #[serde(alias = "#text")]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LastfmText {
+    #[serde(rename = "#text")]
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LastfmTrackAttr {
+    pub nowplaying: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LastfmTrackInfo {
+    pub name: String,
+    pub url: String,
+    pub artist: LastfmArtist,
+    #[serde(default)]
+    pub mbid: String,
+    /// Milliseconds.
+    pub duration: Option<String>,
+    pub album: Option<LastfmAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LastfmAlbum {
+    pub title: String,
+}
+
+fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MusicBrainzRecordings {
+    #[serde(default)]
+    pub recordings: Vec<MusicBrainzRecording>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MusicBrainzRecording {
+    pub id: String,
+    pub title: String,
+    /// Milliseconds.
+    pub length: Option<u64>,
+    /// How well a search result matches the query, up to 100.
+    pub score: Option<u32>,
+    #[serde(rename = "artist-credit", default)]
+    pub artist_credit: Vec<MusicBrainzArtistCredit>,
+    #[serde(default)]
+    pub releases: Vec<MusicBrainzRelease>,
+    #[serde(default)]
+    pub isrcs: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MusicBrainzArtistCredit {
+    pub name: String,
+    #[serde(default)]
+    pub joinphrase: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MusicBrainzRelease {
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "provider", content = "id", rename_all = "snake_case")]
 pub enum PlaylistItemId {
     Spotify(String),
+    SpotifyEpisode(String),
     YouTube(String),
+    Deezer(String),
+    AppleMusic(String),
+    Tidal(String),
+    /// SoundCloud tracks are linked by their permalink, e.g. "artist/track".
+    SoundCloud(String),
+    /// Last.fm tracks are linked by artist and title, e.g. "Artist/_/Track".
+    Lastfm(String),
+    /// A file path or link that does not belong to any provider.
+    Local(String),
 }
 
-impl ToString for PlaylistItemId {
-    fn to_string(&self) -> std::string::String {
+impl fmt::Display for PlaylistItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            PlaylistItemId::Spotify(id) => format!("https://open.spotify.com/track/{}", id),
-            PlaylistItemId::YouTube(id) => format!("https://www.youtube.com/watch?v={}", id),
+            PlaylistItemId::Spotify(id) => write!(f, "https://open.spotify.com/track/{}", id),
+            PlaylistItemId::SpotifyEpisode(id) => {
+                write!(f, "https://open.spotify.com/episode/{}", id)
+            }
+            PlaylistItemId::YouTube(id) => write!(f, "https://www.youtube.com/watch?v={}", id),
+            PlaylistItemId::Deezer(id) => write!(f, "https://www.deezer.com/track/{}", id),
+            PlaylistItemId::AppleMusic(id) => write!(f, "https://music.apple.com/song/{}", id),
+            PlaylistItemId::Tidal(id) => write!(f, "https://tidal.com/browse/track/{}", id),
+            PlaylistItemId::SoundCloud(permalink) => {
+                write!(f, "https://soundcloud.com/{}", permalink)
+            }
+            PlaylistItemId::Lastfm(path) => write!(f, "https://www.last.fm/music/{}", path),
+            PlaylistItemId::Local(location) => write!(f, "{}", location),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistItemKind {
+    Track,
+    Episode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlaylistItem {
     pub id: PlaylistItemId,
+    pub kind: PlaylistItemKind,
     pub name: String,
+    /// Performing artists for tracks, the show name for podcast episodes.
     pub artists: String,
     pub handle: String,
+    #[serde(rename = "duration_ms", with = "duration_ms", default)]
+    pub duration: Option<Duration>,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub isrc: Option<String>,
+    /// MusicBrainz recording ID.
+    #[serde(default)]
+    pub mbid: Option<String>,
+    /// How MusicBrainz names the recording, once the item has been enriched.
+    #[serde(default)]
+    pub canonical: Option<CanonicalTrack>,
+}
+
+impl PlaylistItem {
+    /// The title to search for: as MusicBrainz credits it once the item has
+    /// been enriched, as its provider names it otherwise.
+    pub fn credited_name(&self) -> &str {
+        self.canonical
+            .as_ref()
+            .map_or(&self.name, |canonical| &canonical.title)
+    }
+
+    /// The artists to search for, credited like [`Self::credited_name`].
+    pub fn credited_artists(&self) -> &str {
+        self.canonical
+            .as_ref()
+            .map_or(&self.artists, |canonical| &canonical.artist_credit)
+    }
+
+    /// The free-text query for the item, credited like
+    /// [`Self::credited_name`].
+    pub fn credited_handle(&self) -> String {
+        match &self.canonical {
+            Some(canonical) => format!("{} - {}", canonical.title, canonical.artist_credit),
+            None => self.handle.clone(),
+        }
+    }
+}
+
+/// The title, artists and release of a recording as MusicBrainz credits
+/// them, which are the same whichever provider the item comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanonicalTrack {
+    pub title: String,
+    pub artist_credit: String,
+    pub release: Option<String>,
+    pub release_mbid: Option<String>,
+}
+
+mod duration_ms {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => s.serialize_some(&(duration.as_millis() as u64)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u64>::deserialize(d)?.map(Duration::from_millis))
+    }
+}
+
+/// The outcome of looking up a single source item on the target provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemMatch {
+    pub source: PlaylistItem,
+    pub target: Option<PlaylistItem>,
+    pub confidence: f32,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParsedPlaylistItems {
+    pub items: Vec<ItemMatch>,
+}
+
+impl ParsedPlaylistItems {
+    /// The target items of every successful match, in source order.
+    pub fn matched(&self) -> Vec<PlaylistItem> {
+        self.items
+            .iter()
+            .filter_map(|item_match| item_match.target.clone())
+            .collect()
+    }
+
+    /// The source items that have no counterpart on the target provider.
+    pub fn unmatched(&self) -> Vec<PlaylistItem> {
+        self.items
+            .iter()
+            .filter(|item_match| item_match.target.is_none())
+            .map(|item_match| item_match.source.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Privacy {
+    Public,
+    Unlisted,
+    #[default]
+    Private,
+}
+
+impl fmt::Display for Privacy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Privacy::Public => write!(f, "public"),
+            Privacy::Unlisted => write!(f, "unlisted"),
+            Privacy::Private => write!(f, "private"),
+        }
+    }
+}
+
+impl FromStr for Privacy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "public" => Ok(Privacy::Public),
+            "unlisted" => Ok(Privacy::Unlisted),
+            "private" => Ok(Privacy::Private),
+            _ => Err(format!("unknown privacy \"{}\"", s)),
+        }
+    }
+}
+
+/// What to look for when matching items on YouTube: any video, or the songs
+/// of YouTube Music (the official audio uploaded to "Topic" channels).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchPreference {
+    #[default]
+    Videos,
+    Songs,
+}
+
+impl fmt::Display for SearchPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchPreference::Videos => write!(f, "videos"),
+            SearchPreference::Songs => write!(f, "songs"),
+        }
+    }
+}
+
+impl FromStr for SearchPreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "videos" => Ok(SearchPreference::Videos),
+            "songs" => Ok(SearchPreference::Songs),
+            _ => Err(format!("unknown search preference \"{}\"", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaylistOptions {
+    pub name: String,
+    pub description: Option<String>,
+    pub privacy: Privacy,
+}
+
+/// A playlist as listed among those of a user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaylistSummary {
+    /// Link the items of the playlist can be read from.
+    pub url: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Whether the user made the playlist, rather than following it.
+    pub owned: bool,
+    pub item_count: Option<usize>,
+}
+
+/// What every client shares, whatever it can do with playlists.
+#[async_trait]
+pub trait MusicClient: Send + Sync {
+    /// Prepares the client for reading and writing, e.g. by requesting tokens.
+    /// Clients without credentials have nothing to do.
+    async fn authenticate(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A client that reads the items of existing playlists.
 #[async_trait]
-pub trait MusicClient {
-    async fn get_playlist_items(&self, url: &str) -> Vec<PlaylistItem>;
-    async fn parse_playlist_items(&self, playlist_items: Vec<PlaylistItem>) -> Vec<PlaylistItem>;
-    async fn search(&self, query: &String) -> Option<String>;
-    async fn create_playlist(&self, playlist_items: &Vec<PlaylistItem>);
+pub trait PlaylistSource: MusicClient {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>>;
+
+    /// A version of the playlist that changes whenever its items do, if the
+    /// provider keeps one, e.g. the snapshot ID of Spotify playlists.
+    async fn get_snapshot(&self, _url: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Reads the single track (or episode) a link points at.
+    async fn get_item(&self, url: &str) -> Result<PlaylistItem> {
+        Err(Error::InvalidUrl(url.to_string()))
+    }
+
+    /// The playlists a user owns or follows, in the order the provider lists
+    /// them: those of the authenticated user, or of the user whose profile
+    /// `user` links to.
+    async fn get_playlists(&self, _user: Option<&str>) -> Result<Vec<PlaylistSummary>> {
+        Err(Error::Unsupported("listing the playlists of a user"))
+    }
+
+    /// The description of a playlist, for providers whose listings leave it
+    /// out.
+    async fn get_description(&self, _url: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// A client that finds its own counterparts of items from other providers.
+#[async_trait]
+pub trait TrackSearcher: MusicClient {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems>;
+    async fn search(&self, query: &str) -> Result<Option<String>>;
+}
+
+/// A client that creates playlists.
+#[async_trait]
+pub trait PlaylistSink: MusicClient {
+    async fn create_playlist(
+        &self,
+        options: &PlaylistOptions,
+        playlist_items: &[PlaylistItem],
+    ) -> Result<String>;
+
+    /// Appends items to an existing playlist, e.g. one `create_playlist`
+    /// made. Items of other providers, and those the provider refuses, are
+    /// reported as `ItemFailed` rather than failing the whole call.
+    async fn add_items(&self, _playlist_id: &str, _playlist_items: &[PlaylistItem]) -> Result<()> {
+        Err(Error::Unsupported("adding items to a playlist"))
+    }
+
+    /// Removes every occurrence of the items from an existing playlist.
+    async fn remove_items(
+        &self,
+        _playlist_id: &str,
+        _playlist_items: &[PlaylistItem],
+    ) -> Result<()> {
+        Err(Error::Unsupported("removing items from a playlist"))
+    }
 }
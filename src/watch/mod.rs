@@ -0,0 +1,419 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use colored::Colorize;
+use linksen::{
+    Event, MatchCache, MusicBrainz, PlaylistItem, PlaylistItemId, PlaylistOptions, Privacy,
+    ProviderConfig, Registry, SearchPreference,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::target_searcher;
+
+/// The playlists to keep in sync, read from a TOML file:
+///
+/// ```toml
+/// interval = 60
+///
+/// [[pair]]
+/// from = "https://open.spotify.com/playlist/<ID>"
+/// to = "youtube"
+/// name = "From Spotify"
+/// ```
+#[derive(Deserialize)]
+struct WatchConfig {
+    /// Minutes between two reads of the sources.
+    #[serde(default = "default_interval")]
+    interval: u64,
+    #[serde(default, rename = "pair")]
+    pairs: Vec<Pair>,
+}
+
+/// A source playlist and the target provider it is mirrored to. The target
+/// playlist is either created on the first sync, with `name`, or an existing
+/// one given by its ID in `playlist`, whose items are not added again.
+#[derive(Deserialize)]
+struct Pair {
+    from: String,
+    to: String,
+    name: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    privacy: Privacy,
+    playlist: Option<String>,
+    prefer: Option<SearchPreference>,
+    #[serde(default)]
+    enrich: bool,
+}
+
+impl Pair {
+    fn key(&self) -> String {
+        format!("{} -> {}", self.from, self.to)
+    }
+}
+
+/// What the last sync of every pair left behind, keyed by `Pair::key`.
+#[derive(Default, Serialize, Deserialize)]
+struct WatchState {
+    pairs: BTreeMap<String, PairState>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PairState {
+    playlist_id: Option<String>,
+    /// The version of the source the provider reported, if it keeps one.
+    snapshot: Option<String>,
+    /// The source items as of the last sync, with their match on the target.
+    items: Vec<SyncedItem>,
+    /// Seconds since the Unix epoch.
+    last_synced: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SyncedItem {
+    source: PlaylistItemId,
+    target: Option<PlaylistItem>,
+}
+
+/// What a sync changed on the target.
+#[derive(Default)]
+struct SyncSummary {
+    added: usize,
+    removed: usize,
+    unmatched: usize,
+    /// Matches the target could not add, tried again by the next sync.
+    failed: usize,
+    /// Items gone from the source that the target cannot remove.
+    kept: usize,
+}
+
+fn default_interval() -> u64 {
+    60
+}
+
+/// Syncs every pair of `config_path`, then again every interval unless
+/// `once`. The state is saved to `state_path` after each pair.
+pub async fn watch(
+    config_path: &Path,
+    state_path: &Path,
+    once: bool,
+    config: ProviderConfig,
+    cache: Arc<MatchCache>,
+) -> Result<(), Box<dyn Error>> {
+    let watch_config: WatchConfig = toml::from_str(&fs::read_to_string(config_path)?)?;
+
+    let registry = Registry::new();
+    for pair in &watch_config.pairs {
+        validate(&registry, pair)?;
+    }
+
+    let mut state: WatchState = match fs::read_to_string(state_path) {
+        Ok(json) => serde_json::from_str(&json)?,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => WatchState::default(),
+        Err(error) => return Err(error.into()),
+    };
+    loop {
+        for pair in &watch_config.pairs {
+            let pair_state = state.pairs.entry(pair.key()).or_default();
+
+            match sync(&registry, &config, &cache, pair, pair_state).await {
+                Ok(summary) => {
+                    pair_state.last_synced = Some(now());
+                    println!("{}: {}", pair.key(), describe(&summary));
+                }
+                Err(error) => eprintln!(
+                    "{}",
+                    format!("[ERROR] {}: {}", pair.key(), error)
+                        .on_red()
+                        .white()
+                ),
+            }
+
+            fs::write(state_path, serde_json::to_string_pretty(&state)?)?;
+        }
+
+        if once {
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_secs(watch_config.interval * 60)).await;
+    }
+}
+
+fn validate(registry: &Registry, pair: &Pair) -> Result<(), Box<dyn Error>> {
+    let (source, _) = registry.resolve(&pair.from)?;
+    if !source.capabilities().read {
+        return Err(format!("{} playlists cannot be read", source.display_name()).into());
+    }
+
+    let target = registry
+        .get(&pair.to)
+        .ok_or_else(|| format!("unknown provider {}", pair.to))?;
+    if !target.capabilities().write {
+        return Err(format!("{} playlists cannot be written", target.display_name()).into());
+    }
+
+    if pair.name.is_none() && pair.playlist.is_none() {
+        return Err(format!(
+            "{}: give the target playlist a name or a playlist",
+            pair.key()
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Reads the source and brings the target up to date with it: items new to
+/// the source are searched for and appended, and the matches of items gone
+/// from it are removed if the target can remove items. The order of the
+/// target is left as it is.
+async fn sync(
+    registry: &Registry,
+    config: &ProviderConfig,
+    cache: &Arc<MatchCache>,
+    pair: &Pair,
+    state: &mut PairState,
+) -> Result<SyncSummary, Box<dyn Error>> {
+    let (source, location) = registry.resolve(&pair.from)?;
+    let target = registry
+        .get(&pair.to)
+        .ok_or_else(|| format!("unknown provider {}", pair.to))?;
+    let config = ProviderConfig {
+        search_preference: pair.prefer,
+        ..config.clone()
+    };
+
+    if state.playlist_id.is_none() {
+        state.playlist_id = pair.playlist.clone();
+    }
+
+    let mut client = source
+        .source(&config)?
+        .ok_or_else(|| format!("{} playlists cannot be read", source.display_name()))?;
+    client.authenticate().await?;
+
+    // An unchanged source is only read again when some of its items are not
+    // on the target yet.
+    let snapshot = client.get_snapshot(location).await?;
+    if snapshot.is_some()
+        && snapshot == state.snapshot
+        && state.playlist_id.is_some()
+        && state.items.iter().all(|synced| synced.target.is_some())
+    {
+        return Ok(SyncSummary::default());
+    }
+
+    let playlist_items = client.get_playlist_items(location).await?;
+
+    // Nothing is written to the state until the target is up to date, so
+    // that a failed sync is tried again in full.
+    let mut matches = state
+        .items
+        .iter()
+        .map(|synced| (synced.source.clone(), synced.target.clone()))
+        .collect::<HashMap<_, _>>();
+
+    // Items not found before are searched for again, as the target may have
+    // them by now.
+    let mut new_items = playlist_items
+        .iter()
+        .filter(|playlist_item| {
+            matches
+                .get(&playlist_item.id)
+                .and_then(Option::as_ref)
+                .is_none()
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    if pair.enrich && !new_items.is_empty() {
+        let mut musicbrainz = MusicBrainz::new();
+        musicbrainz.set_reporter(config.reporter.clone());
+        new_items = musicbrainz.enrich(new_items).await?;
+    }
+
+    let searched = match new_items.is_empty() {
+        true => vec![],
+        false => {
+            target_searcher(target, None, &config, cache)?
+                .parse_playlist_items(new_items)
+                .await?
+                .items
+        }
+    };
+
+    let current_ids = playlist_items
+        .iter()
+        .map(|playlist_item| &playlist_item.id)
+        .collect::<HashSet<_>>();
+    let gone = matches
+        .iter()
+        .filter(|(source, _)| !current_ids.contains(source))
+        .filter_map(|(_, target)| target.clone())
+        .collect::<Vec<_>>();
+
+    let found = searched
+        .iter()
+        .filter_map(|found| found.target.clone())
+        .collect::<Vec<_>>();
+    let mut summary = SyncSummary {
+        unmatched: searched.len() - found.len(),
+        ..Default::default()
+    };
+
+    // Nothing synced to an existing playlist yet: what it already holds is
+    // not added a second time.
+    let existing = match &state.playlist_id {
+        Some(playlist_id) if state.items.is_empty() && !found.is_empty() => {
+            let mut client = target
+                .source(&config)?
+                .ok_or_else(|| format!("{} playlists cannot be read", target.display_name()))?;
+            client.authenticate().await?;
+
+            client
+                .get_playlist_items(&target.playlist_location(playlist_id))
+                .await?
+                .into_iter()
+                .map(|playlist_item| playlist_item.id)
+                .collect()
+        }
+        _ => HashSet::new(),
+    };
+    let added = found
+        .into_iter()
+        .filter(|target| !existing.contains(&target.id))
+        .collect::<Vec<_>>();
+
+    for found in searched {
+        matches.insert(found.source.id, found.target);
+    }
+    let mut synced = playlist_items
+        .iter()
+        .map(|playlist_item| SyncedItem {
+            source: playlist_item.id.clone(),
+            target: matches.get(&playlist_item.id).cloned().flatten(),
+        })
+        .collect::<Vec<_>>();
+
+    // Another source item may still be matched with the same target.
+    let remaining = synced
+        .iter()
+        .filter_map(|synced| synced.target.as_ref())
+        .map(|target| &target.id)
+        .collect::<HashSet<_>>();
+    let removed = gone
+        .into_iter()
+        .filter(|target| !remaining.contains(&target.id))
+        .collect::<Vec<_>>();
+
+    if added.is_empty() && removed.is_empty() && state.playlist_id.is_some() {
+        state.items = synced;
+        state.snapshot = snapshot;
+
+        return Ok(summary);
+    }
+
+    // The sink reports items it could not add instead of failing, so they
+    // are picked out of its events and left unmatched in the state, to be
+    // tried again by the next sync.
+    let failed = Arc::new(Mutex::new(HashSet::new()));
+    let reporter = config.reporter.clone();
+    let failed_items = failed.clone();
+    let sink_config = ProviderConfig {
+        reporter: Arc::new(move |event: &Event| {
+            if let Event::ItemFailed { item, .. } = event {
+                failed_items.lock().unwrap().insert(item.id.clone());
+            }
+            reporter.report(event);
+        }),
+        ..config.clone()
+    };
+
+    let mut sink = target
+        .sink(&sink_config)?
+        .ok_or_else(|| format!("{} playlists cannot be written", target.display_name()))?;
+    sink.authenticate().await?;
+
+    match &state.playlist_id {
+        Some(playlist_id) => {
+            if !removed.is_empty() {
+                if target.capabilities().delete {
+                    sink.remove_items(playlist_id, &removed).await?;
+                    summary.removed = removed.len();
+                } else {
+                    summary.kept = removed.len();
+                }
+            }
+
+            if !added.is_empty() {
+                failed.lock().unwrap().clear();
+                sink.add_items(playlist_id, &added).await?;
+                summary.added = added.len();
+            }
+        }
+        None => {
+            let options = PlaylistOptions {
+                name: pair.name.clone().unwrap_or_default(),
+                description: pair.description.clone(),
+                privacy: pair.privacy,
+            };
+            let targets = synced
+                .iter()
+                .filter_map(|synced| synced.target.clone())
+                .collect::<Vec<_>>();
+
+            failed.lock().unwrap().clear();
+            state.playlist_id = Some(sink.create_playlist(&options, &targets).await?);
+            summary.added = targets.len();
+        }
+    }
+
+    let failed = failed.lock().unwrap();
+    for synced in &mut synced {
+        if synced
+            .target
+            .as_ref()
+            .is_some_and(|target| failed.contains(&target.id))
+        {
+            synced.target = None;
+        }
+    }
+    summary.failed = failed.len();
+    summary.added -= failed.len();
+
+    state.items = synced;
+    state.snapshot = snapshot;
+
+    Ok(summary)
+}
+
+fn describe(summary: &SyncSummary) -> String {
+    let mut changes = vec![
+        format!("{} added", summary.added),
+        format!("{} removed", summary.removed),
+    ];
+    if summary.unmatched > 0 {
+        changes.push(format!("{} not found", summary.unmatched));
+    }
+    if summary.failed > 0 {
+        changes.push(format!("{} failed", summary.failed));
+    }
+    if summary.kept > 0 {
+        changes.push(format!("{} gone from the source but kept", summary.kept));
+    }
+
+    changes.join(", ")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
@@ -1,35 +1,95 @@
-use std::{fs, process::exit};
-
+use std::{collections::HashSet, time::Duration};
+
+use crate::error::{Error, Result};
+use crate::events::{own_items, quiet, report_match, searched, Event, SharedReporter};
+use crate::matching::{confidence, durations_match};
+use crate::normalize::normalize_youtube_video;
+use crate::oauth::data_dir;
+use crate::playlist_file::parse_location;
+use crate::registry::{Capabilities, Provider, ProviderConfig};
 use crate::types::{
-    MusicClient, PlaylistItem, PlaylistItemId, YoutubeResponse,
-    YoutubeResponseItemSectionRendererContent, YoutubeResponseSectionListRendererContent,
+    ItemMatch, MusicClient, ParsedPlaylistItems, PlaylistItem, PlaylistItemId, PlaylistItemKind,
+    PlaylistOptions, PlaylistSink, PlaylistSource, PlaylistSummary, SearchPreference,
+    TrackSearcher, YoutubeMusicResponse, YoutubeMusicResponseListItem, YoutubeOembed,
+    YoutubeResponse, YoutubeResponseItemSectionRendererContent,
+    YoutubeResponseSectionListRendererContent, YoutubeResponseVideoRenderer,
 };
 use async_trait::async_trait;
-use colored::Colorize;
 use dotenv_codegen::dotenv;
 use google_youtube3::{
     api::{
-        Playlist, PlaylistItem as PlaylistItemAPI, PlaylistItemSnippet, PlaylistSnippet, ResourceId,
+        Playlist, PlaylistItem as PlaylistItemAPI, PlaylistItemSnippet, PlaylistSnippet,
+        PlaylistStatus, ResourceId,
     },
     hyper::{self, client::HttpConnector},
     hyper_rustls::{self, HttpsConnector},
     oauth2::{ApplicationSecret, InstalledFlowAuthenticator, InstalledFlowReturnMethod},
     YouTube as YouTubeAPI,
 };
-use indicatif::{ProgressBar, ProgressStyle};
 use regex::Regex;
-use std::io::Write;
+use serde_json::json;
+
+const OEMBED_URL: &str = "https://www.youtube.com/oembed";
+const MUSIC_SEARCH_URL: &str = "https://music.youtube.com/youtubei/v1/search?prettyPrint=false";
+const MUSIC_CLIENT_VERSION: &str = "1.20240101.01.00";
+/// Restricts a YouTube Music search to the "Songs" category.
+const MUSIC_SONGS_FILTER: &str = "EgWKAQIIAWoMEA4QChADEAQQCRAF";
 
 pub struct Youtube {
     hub: Option<YouTubeAPI<HttpsConnector<HttpConnector>>>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    preference: SearchPreference,
+    reporter: SharedReporter,
 }
 
 impl Youtube {
     pub fn new() -> Self {
-        return Self { hub: None };
+        Self {
+            hub: None,
+            client_id: None,
+            client_secret: None,
+            preference: SearchPreference::default(),
+            reporter: quiet(),
+        }
     }
 
-    pub async fn init_api_hub(&mut self, client_id: Option<String>, client_secret: Option<String>) {
+    /// Uses the given OAuth client instead of the bundled one when the client
+    /// authenticates.
+    pub fn with_credentials(client_id: Option<String>, client_secret: Option<String>) -> Self {
+        Self {
+            client_id,
+            client_secret,
+            ..Self::new()
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: SharedReporter) {
+        self.reporter = reporter;
+    }
+
+    /// With `SearchPreference::Songs`, items are looked up among the songs of
+    /// YouTube Music first and only then among all videos.
+    pub fn set_search_preference(&mut self, preference: SearchPreference) {
+        self.preference = preference;
+    }
+
+    fn from_config(config: &ProviderConfig, preference: SearchPreference) -> Youtube {
+        let mut youtube = Youtube::with_credentials(
+            config.google_client_id.clone(),
+            config.google_client_secret.clone(),
+        );
+        youtube.set_reporter(config.reporter.clone());
+        youtube.set_search_preference(config.search_preference.unwrap_or(preference));
+
+        youtube
+    }
+
+    pub async fn init_api_hub(
+        &mut self,
+        client_id: Option<String>,
+        client_secret: Option<String>,
+    ) -> Result<()> {
         let client_id = client_id.unwrap_or(dotenv!("GOOGLE_CLIENT_ID").into());
         let client_secret = client_secret.unwrap_or(dotenv!("GOOGLE_CLIENT_SECRET").into());
 
@@ -37,26 +97,21 @@ impl Youtube {
         let token_uri = dotenv!("GOOGLE_TOKEN_URI");
 
         let secret = ApplicationSecret {
-            client_id: client_id.into(),
-            client_secret: client_secret.into(),
+            client_id,
+            client_secret,
             auth_uri: auth_uri.into(),
             token_uri: token_uri.into(),
             redirect_uris: vec![],
             ..Default::default()
         };
 
-        let data_dir = dirs::data_local_dir().unwrap();
-        let data_dir = data_dir.to_str().unwrap();
-
-        fs::create_dir_all(format!("{}{}", data_dir, "/linksen")).unwrap();
-        let cache_path = format!("{}{}", data_dir, "/linksen/linksen.cache");
+        let cache_path = data_dir()?.join("linksen.cache");
 
         let auth =
             InstalledFlowAuthenticator::builder(secret, InstalledFlowReturnMethod::HTTPRedirect)
                 .persist_tokens_to_disk(cache_path)
                 .build()
-                .await
-                .unwrap();
+                .await?;
 
         let hub = YouTubeAPI::new(
             hyper::Client::builder().build(
@@ -71,251 +126,827 @@ impl Youtube {
         );
 
         self.hub = Some(hub);
+
+        Ok(())
     }
-}
 
-#[async_trait]
-impl MusicClient for Youtube {
-    async fn get_playlist_items(&self, url: &str) -> Vec<PlaylistItem> {
-        if self.hub.is_none() {
-            eprintln!(
-                "{}",
-                "[INTERNAL ERROR] YouTube API has not been initialized!"
-                    .on_red()
-                    .white()
-            );
+    fn hub(&self) -> Result<&YouTubeAPI<HttpsConnector<HttpConnector>>> {
+        self.hub.as_ref().ok_or(Error::NotInitialized("YouTube"))
+    }
 
-            exit(1);
-        }
+    async fn list_playlist_items(&self, playlist_id: &str) -> Result<Vec<PlaylistItemAPI>> {
+        let hub = self.hub()?;
 
-        print!("{}", "Loading playlist... ".yellow());
+        let mut playlist_items = vec![];
+        let mut page_token: Option<String> = None;
 
-        let hub = self.hub.as_ref().unwrap();
+        loop {
+            let mut call = hub
+                .playlist_items()
+                .list(&vec!["snippet".into()])
+                .playlist_id(playlist_id)
+                .max_results(50);
 
-        let (_, playlist_items) = hub
-            .playlist_items()
-            .list(&vec!["snippet".into()])
-            .playlist_id(extract_playlist_id(url).unwrap())
-            .doit()
-            .await
-            .unwrap();
+            if let Some(page_token) = &page_token {
+                call = call.page_token(page_token);
+            }
 
-        let playlist_items = playlist_items.items.unwrap();
+            let (_, page) = call.doit().await?;
 
-        let playlist_items = playlist_items
-            .iter()
-            .map(|playlist_item| {
-                let id = if let Some(snippet) = &playlist_item.snippet {
-                    if let Some(res_id) = &snippet.resource_id {
-                        if let Some(video_id) = &res_id.video_id {
-                            Some(PlaylistItemId::YouTube(video_id.clone()))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-
-                let artists = if let Some(snippet) = &playlist_item.snippet {
-                    if let Some(channel_title) = &snippet.video_owner_channel_title {
-                        Some(channel_title.clone())
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-
-                let name = if let Some(snippet) = &playlist_item.snippet {
-                    if let Some(title) = &snippet.title {
-                        Some(title.clone())
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-
-                if id.is_none() || artists.is_none() || name.is_none() {
-                    return None;
-                }
+            playlist_items.extend(page.items.unwrap_or_default());
 
-                return Some(PlaylistItem {
-                    id: id.unwrap(),
-                    artists: artists.unwrap(),
-                    name: name.as_ref().unwrap().clone(),
-                    handle: name.unwrap(),
-                });
-            })
-            .flat_map(|playlist_item| playlist_item)
-            .collect();
+            page_token = page.next_page_token;
+            if page_token.is_none() {
+                return Ok(playlist_items);
+            }
+        }
+    }
 
-        println!("{}", "Playlist loaded!".green());
+    async fn resolve_source(&self, source: YoutubeSource) -> Result<Option<String>> {
+        let hub = self.hub()?;
+
+        let call = hub.channels().list(&vec!["contentDetails".into()]);
+        let call = match &source {
+            YoutubeSource::Playlist(playlist_id) => return Ok(Some(playlist_id.clone())),
+            // The Data API answers every request for "Watch later" with an
+            // empty list, which would pass for an empty playlist.
+            YoutubeSource::WatchLater => {
+                return Err(Error::Unsupported(
+                    "reading \"Watch later\", which the YouTube Data API does not return",
+                ))
+            }
+            YoutubeSource::Channel(channel_id) => call.add_id(channel_id),
+            YoutubeSource::Username(username) => call.for_username(username),
+            YoutubeSource::Handle(handle) => call.param("forHandle", handle.as_str()),
+            YoutubeSource::Liked => call.mine(true),
+        };
 
-        return playlist_items;
+        let (_, channels) = call.doit().await?;
+        let related_playlists = channels
+            .items
+            .and_then(|channels| channels.into_iter().next())
+            .and_then(|channel| channel.content_details)
+            .and_then(|content_details| content_details.related_playlists);
+
+        Ok(
+            related_playlists.and_then(|related_playlists| match source {
+                YoutubeSource::Liked => related_playlists.likes,
+                _ => related_playlists.uploads,
+            }),
+        )
     }
 
-    async fn parse_playlist_items(&self, playlist_items: Vec<PlaylistItem>) -> Vec<PlaylistItem> {
-        println!("{}", "Transforming playlist...".yellow());
+    /// The ID of the channel a link, handle or username points at.
+    async fn resolve_channel(&self, user: &str) -> Result<String> {
+        let hub = self.hub()?;
+        let invalid_url = || Error::InvalidUrl(user.to_string());
+
+        let call = hub.channels().list(&vec!["id".into()]);
+        let call = match extract_source(user).ok_or_else(invalid_url)? {
+            YoutubeSource::Channel(channel_id) => return Ok(channel_id),
+            YoutubeSource::Username(username) => call.for_username(&username),
+            YoutubeSource::Handle(handle) => call.param("forHandle", &handle),
+            _ => return Err(invalid_url()),
+        };
 
-        let pb = ProgressBar::new(playlist_items.len() as u64);
-        pb.set_style(
-            ProgressStyle::with_template("{bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
-                .unwrap()
-                .progress_chars("##-"),
-        );
+        let (_, channels) = call.doit().await?;
 
-        let mut new_playlist_items = vec![];
+        channels
+            .items
+            .and_then(|channels| channels.into_iter().next())
+            .and_then(|channel| channel.id)
+            .ok_or_else(invalid_url)
+    }
 
-        for playlist_item in playlist_items.iter() {
-            pb.set_message(format!("{}", playlist_item.name));
+    async fn search_videos(&self, query: &str) -> Result<Vec<YoutubeResponseVideoRenderer>> {
+        let url = reqwest::Url::parse_with_params(
+            "https://www.youtube.com/results",
+            [("search_query", query)],
+        )
+        .map_err(|_| Error::InvalidUrl(query.to_string()))?;
 
-            if let PlaylistItemId::YouTube(_) = playlist_item.id {
-                new_playlist_items.push(playlist_item.clone());
-            } else {
-                let video_id = self.search(&playlist_item.handle).await;
-
-                if let Some(video_id) = video_id {
-                    new_playlist_items.push(PlaylistItem {
-                        id: PlaylistItemId::YouTube(video_id),
-                        name: playlist_item.name.clone(),
-                        handle: playlist_item.handle.clone(),
-                        artists: playlist_item.artists.clone(),
-                    })
+        let youtube_response = reqwest::get(url).await?.text().await?;
+        let initial_data = extract_yt_initial_data(&youtube_response).ok_or_else(|| {
+            Error::UnexpectedResponse("YouTube search results have no ytInitialData".into())
+        })?;
+
+        let youtube_response: YoutubeResponse = serde_json::from_str(&initial_data)?;
+
+        Ok(youtube_response
+            .contents
+            .two_column_search_results_renderer
+            .primary_contents
+            .section_list_renderer
+            .contents
+            .into_iter()
+            .flat_map(|section_content| match section_content {
+                YoutubeResponseSectionListRendererContent::ItemSectionRenderer(
+                    item_section_renderer,
+                ) => item_section_renderer.item_section_renderer.contents,
+                YoutubeResponseSectionListRendererContent::ContinuationItemRenderer(_) => vec![],
+            })
+            .filter_map(|item_content| match item_content {
+                YoutubeResponseItemSectionRendererContent::VideoRenderer(video_renderer) => {
+                    Some(video_renderer.video_renderer)
                 }
+                YoutubeResponseItemSectionRendererContent::OtherRenderer(_) => None,
+            })
+            .collect())
+    }
+
+    /// Searches the songs of YouTube Music. Unlike videos, their title,
+    /// artists and album come as separate fields.
+    async fn search_songs(&self, query: &str) -> Result<Vec<PlaylistItem>> {
+        let body = json!({
+            "context": {
+                "client": {
+                    "clientName": "WEB_REMIX",
+                    "clientVersion": MUSIC_CLIENT_VERSION,
+                    "hl": "en",
+                },
+            },
+            "query": query,
+            "params": MUSIC_SONGS_FILTER,
+        });
+
+        let response: YoutubeMusicResponse = reqwest::Client::new()
+            .post(MUSIC_SEARCH_URL)
+            .header("Origin", "https://music.youtube.com")
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response
+            .contents
+            .tabbed_search_results_renderer
+            .tabs
+            .into_iter()
+            .filter_map(|tab| tab.tab_renderer.content)
+            .flat_map(|content| content.section_list_renderer.contents)
+            .filter_map(|section| section.music_shelf_renderer)
+            .flat_map(|shelf| shelf.contents)
+            .filter_map(|item| item.music_responsive_list_item_renderer)
+            .filter_map(song_playlist_item)
+            .collect())
+    }
+
+    async fn search_track(&self, playlist_item: &PlaylistItem) -> Result<Option<PlaylistItem>> {
+        let query = playlist_item.credited_handle();
+
+        // Podcast episodes are never songs.
+        if self.preference == SearchPreference::Songs
+            && playlist_item.kind == PlaylistItemKind::Track
+        {
+            if let Some(song) = self.search_songs(&query).await?.into_iter().next() {
+                return Ok(Some(song));
             }
+        }
+
+        let mut videos = self.search_videos(&query).await?.into_iter();
+
+        let video = match (playlist_item.kind, playlist_item.duration) {
+            (PlaylistItemKind::Episode, Some(duration)) => {
+                videos.find(|video| video_matches_duration(video, duration))
+            }
+            _ => videos.next(),
+        };
+
+        Ok(video.map(|video| video_playlist_item(video, playlist_item.kind)))
+    }
+}
+
+impl Default for Youtube {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-            pb.inc(1);
+pub struct YoutubeProvider;
+
+impl Provider for YoutubeProvider {
+    fn name(&self) -> &'static str {
+        "youtube"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "YouTube"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            search: true,
+            write: true,
+            delete: true,
+            ..Default::default()
         }
+    }
 
-        pb.finish_with_message(format!("{}", "Transformed playlist!".green()));
+    fn owns(&self, location: &str) -> bool {
+        (location.contains("youtube.com") && !location.contains("music.youtube.com"))
+            || location.contains("youtu.be")
+    }
 
-        return new_playlist_items;
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(Some(Box::new(Youtube::from_config(
+            config,
+            SearchPreference::Videos,
+        ))))
     }
 
-    async fn search(&self, query: &String) -> Option<String> {
-        let url = format!("https://www.youtube.com/results?search_query={}", query);
+    fn searcher(&self, config: &ProviderConfig) -> Result<Option<Box<dyn TrackSearcher>>> {
+        Ok(Some(Box::new(Youtube::from_config(
+            config,
+            SearchPreference::Videos,
+        ))))
+    }
 
-        let youtube_response = reqwest::get(&url).await.unwrap().text().await.unwrap();
-        let initial_data = extract_yt_initial_data(&youtube_response).unwrap();
+    fn sink(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSink>>> {
+        Ok(Some(Box::new(Youtube::from_config(
+            config,
+            SearchPreference::Videos,
+        ))))
+    }
 
-        let youtube_response: YoutubeResponse = serde_json::from_str(&initial_data).unwrap();
+    /// Inserting a playlist or an item costs 50 units of the 10,000 the
+    /// YouTube Data API allows a day.
+    fn write_cost(&self, items: usize) -> u64 {
+        50 * (items as u64 + 1)
+    }
 
-        let section_content = &youtube_response
-            .contents
-            .two_column_search_results_renderer
-            .primary_contents
-            .section_list_renderer
-            .contents[0];
-
-        let first_video_id = match section_content {
-            YoutubeResponseSectionListRendererContent::ItemSectionRenderer(
-                item_section_renderer,
-            ) => {
-                let video_renderer = &item_section_renderer.item_section_renderer.contents[0];
-
-                match video_renderer {
-                    YoutubeResponseItemSectionRendererContent::VideoRenderer(video_renderer) => {
-                        Some(video_renderer.video_renderer.video_id.clone())
-                    }
-                    YoutubeResponseItemSectionRendererContent::OtherRenderer(_) => None,
-                }
+    fn playlist_location(&self, playlist_id: &str) -> String {
+        format!("https://www.youtube.com/playlist?list={}", playlist_id)
+    }
+}
+
+/// YouTube Music shares its playlists with YouTube, but matches items with
+/// the songs of YouTube Music rather than any video.
+pub struct YoutubeMusicProvider;
+
+impl Provider for YoutubeMusicProvider {
+    fn name(&self) -> &'static str {
+        "youtube-music"
+    }
+
+    fn display_name(&self) -> &'static str {
+        "YouTube Music"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        YoutubeProvider.capabilities()
+    }
+
+    fn owns(&self, location: &str) -> bool {
+        location.contains("music.youtube.com")
+    }
+
+    fn owns_item(&self, id: &PlaylistItemId) -> bool {
+        YoutubeProvider.owns_item(id)
+    }
+
+    fn source(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSource>>> {
+        Ok(Some(Box::new(Youtube::from_config(
+            config,
+            SearchPreference::Songs,
+        ))))
+    }
+
+    fn searcher(&self, config: &ProviderConfig) -> Result<Option<Box<dyn TrackSearcher>>> {
+        Ok(Some(Box::new(Youtube::from_config(
+            config,
+            SearchPreference::Songs,
+        ))))
+    }
+
+    fn sink(&self, config: &ProviderConfig) -> Result<Option<Box<dyn PlaylistSink>>> {
+        Ok(Some(Box::new(Youtube::from_config(
+            config,
+            SearchPreference::Songs,
+        ))))
+    }
+
+    fn write_cost(&self, items: usize) -> u64 {
+        YoutubeProvider.write_cost(items)
+    }
+
+    fn quota_pool(&self) -> &'static str {
+        YoutubeProvider.quota_pool()
+    }
+
+    fn playlist_location(&self, playlist_id: &str) -> String {
+        YoutubeProvider.playlist_location(playlist_id)
+    }
+}
+
+#[async_trait]
+impl MusicClient for Youtube {
+    async fn authenticate(&mut self) -> Result<()> {
+        if self.hub.is_some() {
+            return Ok(());
+        }
+
+        self.init_api_hub(self.client_id.clone(), self.client_secret.clone())
+            .await
+    }
+}
+
+#[async_trait]
+impl PlaylistSource for Youtube {
+    async fn get_playlist_items(&self, url: &str) -> Result<Vec<PlaylistItem>> {
+        self.hub()?;
+        self.reporter.report(&Event::LoadingStarted);
+
+        let source = extract_source(url).ok_or_else(|| Error::InvalidUrl(url.to_string()))?;
+        let playlist_id = self
+            .resolve_source(source)
+            .await?
+            .ok_or_else(|| Error::InvalidUrl(url.to_string()))?;
+
+        let playlist_items = self
+            .list_playlist_items(&playlist_id)
+            .await?
+            .iter()
+            .filter_map(|playlist_item| {
+                let snippet = playlist_item.snippet.as_ref()?;
+
+                let id = snippet.resource_id.as_ref()?.video_id.clone()?;
+                let channel_title = snippet.video_owner_channel_title.as_ref()?;
+                let video_title = snippet.title.clone()?;
+
+                let track = normalize_youtube_video(&video_title, channel_title);
+                let artists = std::iter::once(track.artist)
+                    .chain(track.featured_artists)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                Some(PlaylistItem {
+                    id: PlaylistItemId::YouTube(id),
+                    kind: PlaylistItemKind::Track,
+                    artists,
+                    name: track.title,
+                    handle: video_title,
+                    duration: None,
+                    album: None,
+                    isrc: None,
+                    mbid: None,
+                    canonical: None,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        self.reporter.report(&Event::LoadingFinished {
+            count: playlist_items.len(),
+        });
+
+        Ok(playlist_items)
+    }
+
+    /// YouTube does not let apps see the playlists a user saved, so only those
+    /// of the user's channel are listed.
+    async fn get_playlists(&self, user: Option<&str>) -> Result<Vec<PlaylistSummary>> {
+        let hub = self.hub()?;
+        let channel_id = match user {
+            Some(user) => Some(self.resolve_channel(user).await?),
+            None => None,
+        };
+
+        let mut playlists = vec![];
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut call = hub
+                .playlists()
+                .list(&vec!["snippet".into(), "contentDetails".into()])
+                .max_results(50);
+            call = match &channel_id {
+                Some(channel_id) => call.channel_id(channel_id),
+                None => call.mine(true),
+            };
+
+            if let Some(page_token) = &page_token {
+                call = call.page_token(page_token);
+            }
+
+            let (_, page) = call.doit().await?;
+
+            playlists.extend(
+                page.items
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|playlist| {
+                        let snippet = playlist.snippet?;
+
+                        Some(PlaylistSummary {
+                            url: format!("https://www.youtube.com/playlist?list={}", playlist.id?),
+                            name: snippet.title?,
+                            description: snippet
+                                .description
+                                .filter(|description| !description.is_empty()),
+                            owned: true,
+                            item_count: playlist
+                                .content_details
+                                .and_then(|content_details| content_details.item_count)
+                                .map(|item_count| item_count as usize),
+                        })
+                    }),
+            );
+
+            page_token = page.next_page_token;
+            if page_token.is_none() {
+                return Ok(playlists);
             }
-            YoutubeResponseSectionListRendererContent::ContinuationItemRenderer(_) => None,
+        }
+    }
+
+    /// Videos are read from their oEmbed description, which needs no
+    /// authorization but leaves out the duration.
+    async fn get_item(&self, url: &str) -> Result<PlaylistItem> {
+        let video_id = match parse_location(url) {
+            PlaylistItemId::YouTube(video_id) => video_id,
+            _ => return Err(Error::InvalidUrl(url.to_string())),
         };
 
-        first_video_id
+        let video: YoutubeOembed = reqwest::Client::new()
+            .get(OEMBED_URL)
+            .query(&[
+                ("url", PlaylistItemId::YouTube(video_id.clone()).to_string()),
+                ("format", "json".to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let track = normalize_youtube_video(&video.title, &video.author_name);
+
+        Ok(PlaylistItem {
+            id: PlaylistItemId::YouTube(video_id),
+            kind: PlaylistItemKind::Track,
+            name: track.title,
+            artists: std::iter::once(track.artist)
+                .chain(track.featured_artists)
+                .collect::<Vec<_>>()
+                .join(", "),
+            handle: video.title,
+            duration: None,
+            album: None,
+            isrc: None,
+            mbid: None,
+            canonical: None,
+        })
     }
+}
 
-    async fn create_playlist(&self, playlist_items: &Vec<PlaylistItem>) {
-        if self.hub.is_none() {
-            eprintln!(
-                "{}",
-                "[INTERNAL ERROR] YouTube API has not been initialized!"
-                    .on_red()
-                    .white()
-            );
+#[async_trait]
+impl TrackSearcher for Youtube {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        self.reporter.report(&Event::TransformStarted {
+            total: playlist_items.len(),
+        });
+
+        let mut parsed_playlist_items = ParsedPlaylistItems::default();
+
+        for playlist_item in playlist_items.into_iter() {
+            self.reporter.report(&Event::ItemSearching {
+                item: playlist_item.clone(),
+            });
+
+            let item_match = if let PlaylistItemId::YouTube(_) = playlist_item.id {
+                ItemMatch {
+                    target: Some(playlist_item.clone()),
+                    source: playlist_item,
+                    confidence: 1.0,
+                }
+            } else {
+                let target = searched(
+                    self.reporter.as_ref(),
+                    &playlist_item,
+                    self.search_track(&playlist_item).await,
+                );
+                let confidence = target
+                    .as_ref()
+                    .map_or(0.0, |target| confidence(&playlist_item, target));
+
+                ItemMatch {
+                    source: playlist_item,
+                    target,
+                    confidence,
+                }
+            };
 
-            exit(1);
+            report_match(self.reporter.as_ref(), &item_match);
+            parsed_playlist_items.items.push(item_match);
         }
 
-        let mut playlist_name = None;
-        while playlist_name.is_none() {
-            print!("Playlist name: ");
-            let _ = std::io::stdout().flush();
+        self.reporter.report(&Event::TransformFinished);
 
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
+        Ok(parsed_playlist_items)
+    }
 
-            if input.trim().len() == 0 {
-                println!("[ERROR] Playlist name cannot be empty!");
-                continue;
+    async fn search(&self, query: &str) -> Result<Option<String>> {
+        if self.preference == SearchPreference::Songs {
+            if let Some(PlaylistItemId::YouTube(video_id)) = self
+                .search_songs(query)
+                .await?
+                .into_iter()
+                .next()
+                .map(|song| song.id)
+            {
+                return Ok(Some(video_id));
             }
-
-            playlist_name = Some(input.trim().to_string());
         }
 
-        print!("{}", "Creating playlist... ".yellow());
-        let _ = std::io::stdout().flush();
+        Ok(self
+            .search_videos(query)
+            .await?
+            .into_iter()
+            .next()
+            .map(|video| video.video_id))
+    }
+}
 
-        let hub = self.hub.as_ref().unwrap();
+#[async_trait]
+impl PlaylistSink for Youtube {
+    async fn create_playlist(
+        &self,
+        options: &PlaylistOptions,
+        playlist_items: &[PlaylistItem],
+    ) -> Result<String> {
+        let hub = self.hub()?;
+
+        self.reporter.report(&Event::CreatingPlaylist {
+            name: options.name.clone(),
+        });
 
         let (_, new_playlist) = hub
             .playlists()
             .insert(Playlist {
                 snippet: Some(PlaylistSnippet {
-                    title: Some(playlist_name.unwrap()),
+                    title: Some(options.name.clone()),
+                    description: options.description.clone(),
                     ..Default::default()
                 }),
+                status: Some(PlaylistStatus {
+                    privacy_status: Some(options.privacy.to_string()),
+                }),
                 ..Default::default()
             })
             .doit()
-            .await
-            .unwrap();
+            .await?;
+
+        let playlist_id = new_playlist.id.ok_or_else(|| {
+            Error::UnexpectedResponse("created YouTube playlist has no ID".into())
+        })?;
+
+        self.add_items(&playlist_id, playlist_items).await?;
+
+        self.reporter.report(&Event::PlaylistCreated {
+            id: playlist_id.clone(),
+        });
+
+        Ok(playlist_id)
+    }
+
+    async fn add_items(&self, playlist_id: &str, playlist_items: &[PlaylistItem]) -> Result<()> {
+        let hub = self.hub()?;
+
+        let playlist_items = own_items(self.reporter.as_ref(), "YouTube", playlist_items, |id| {
+            matches!(id, PlaylistItemId::YouTube(_))
+        });
 
         for playlist_item in playlist_items {
-            if let PlaylistItemId::YouTube(video_id) = &playlist_item.id {
-                hub.playlist_items()
-                    .insert(PlaylistItemAPI {
-                        snippet: Some(PlaylistItemSnippet {
-                            playlist_id: Some(new_playlist.id.clone().unwrap()),
-                            resource_id: Some(ResourceId {
-                                video_id: Some(video_id.clone()),
-                                kind: Some("youtube#video".to_string()),
-                                ..Default::default()
-                            }),
+            let PlaylistItemId::YouTube(video_id) = &playlist_item.id else {
+                continue;
+            };
+
+            let inserted = hub
+                .playlist_items()
+                .insert(PlaylistItemAPI {
+                    snippet: Some(PlaylistItemSnippet {
+                        playlist_id: Some(playlist_id.to_string()),
+                        resource_id: Some(ResourceId {
+                            video_id: Some(video_id.clone()),
+                            kind: Some("youtube#video".to_string()),
                             ..Default::default()
                         }),
                         ..Default::default()
-                    })
-                    .doit()
-                    .await
-                    .unwrap();
+                    }),
+                    ..Default::default()
+                })
+                .doit()
+                .await;
+
+            match inserted {
+                Ok(_) => self.reporter.report(&Event::ItemInserted {
+                    item: playlist_item.clone(),
+                }),
+                Err(error) => self.reporter.report(&Event::ItemFailed {
+                    item: playlist_item.clone(),
+                    error: format!("not added: {}", error),
+                }),
             }
         }
 
-        println!("{}", "Created playlist!".green());
+        Ok(())
     }
-}
 
-fn extract_playlist_id(url: &str) -> Option<&str> {
-    let prefix = "https://www.youtube.com/playlist?list=";
-    let query_param_prefix = "&";
+    /// Playlist entries are deleted one by one, so that this costs as much
+    /// quota as inserting them did.
+    async fn remove_items(&self, playlist_id: &str, playlist_items: &[PlaylistItem]) -> Result<()> {
+        let hub = self.hub()?;
 
-    if let Some(start_index) = url.find(prefix) {
-        let rest_of_string = &url[start_index + prefix.len()..];
+        let video_ids = playlist_items
+            .iter()
+            .filter_map(|playlist_item| match &playlist_item.id {
+                PlaylistItemId::YouTube(video_id) => Some(video_id.as_str()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        if video_ids.is_empty() {
+            return Ok(());
+        }
 
-        if let Some(end_index) = rest_of_string.find(query_param_prefix) {
-            return Some(&rest_of_string[..end_index]);
-        } else {
-            return Some(rest_of_string);
+        for entry in self.list_playlist_items(playlist_id).await? {
+            let video_id = entry
+                .snippet
+                .as_ref()
+                .and_then(|snippet| snippet.resource_id.as_ref())
+                .and_then(|resource_id| resource_id.video_id.as_deref());
+
+            if let (Some(id), Some(video_id)) = (&entry.id, video_id) {
+                if video_ids.contains(video_id) {
+                    hub.playlist_items().delete(id).doit().await?;
+                }
+            }
         }
+
+        Ok(())
+    }
+}
+
+/// Where the items of a YouTube "playlist" come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum YoutubeSource {
+    Playlist(String),
+    Channel(String),
+    Handle(String),
+    Username(String),
+    Liked,
+    WatchLater,
+}
+
+fn extract_source(url: &str) -> Option<YoutubeSource> {
+    let url = url.trim();
+
+    match url.to_lowercase().as_str() {
+        "liked" | "likes" | "ll" => return Some(YoutubeSource::Liked),
+        "watch-later" | "watchlater" | "wl" => return Some(YoutubeSource::WatchLater),
+        _ => {}
+    }
+
+    if let Some(handle) = url.strip_prefix('@') {
+        return Some(YoutubeSource::Handle(handle.to_string()));
+    }
+
+    if let Some(playlist_id) = extract_path_segment(url, "list=", &['&', '#']) {
+        return Some(match playlist_id {
+            "LL" | "LM" => YoutubeSource::Liked,
+            "WL" => YoutubeSource::WatchLater,
+            _ => YoutubeSource::Playlist(playlist_id.to_string()),
+        });
+    }
+
+    if let Some(channel_id) = extract_path_segment(url, "youtube.com/channel/", &['/', '?', '#']) {
+        return Some(YoutubeSource::Channel(channel_id.to_string()));
+    }
+
+    if let Some(username) = extract_path_segment(url, "youtube.com/user/", &['/', '?', '#']) {
+        return Some(YoutubeSource::Username(username.to_string()));
+    }
+
+    if let Some(handle) = extract_path_segment(url, "youtube.com/@", &['/', '?', '#']) {
+        return Some(YoutubeSource::Handle(handle.to_string()));
+    }
+
+    None
+}
+
+fn extract_path_segment<'a>(url: &'a str, prefix: &str, terminators: &[char]) -> Option<&'a str> {
+    let start_index = url.find(prefix)?;
+    let rest_of_string = &url[start_index + prefix.len()..];
+
+    let segment = match rest_of_string.find(terminators) {
+        Some(end_index) => &rest_of_string[..end_index],
+        None => rest_of_string,
+    };
+
+    if segment.is_empty() {
+        None
+    } else {
+        Some(segment)
+    }
+}
+
+fn video_playlist_item(
+    video: YoutubeResponseVideoRenderer,
+    kind: PlaylistItemKind,
+) -> PlaylistItem {
+    let video_title = video.title.map(|title| title.text()).unwrap_or_default();
+    let channel_title = video
+        .owner_text
+        .map(|owner_text| owner_text.text())
+        .unwrap_or_default();
+
+    let track = normalize_youtube_video(&video_title, &channel_title);
+
+    PlaylistItem {
+        id: PlaylistItemId::YouTube(video.video_id),
+        kind,
+        name: track.title,
+        artists: std::iter::once(track.artist)
+            .chain(track.featured_artists)
+            .collect::<Vec<_>>()
+            .join(", "),
+        handle: video_title,
+        duration: video
+            .length_text
+            .and_then(|length_text| parse_length_text(&length_text.simple_text)),
+        album: None,
+        isrc: None,
+        mbid: None,
+        canonical: None,
+    }
+}
+
+/// The first column holds the title, the second the artists, album and
+/// duration, separated by " • ".
+fn song_playlist_item(song: YoutubeMusicResponseListItem) -> Option<PlaylistItem> {
+    let video_id = song.playlist_item_data?.video_id;
+
+    let mut columns = song.flex_columns.into_iter().map(|column| {
+        column
+            .music_responsive_list_item_flex_column_renderer
+            .text
+            .map(|text| text.text())
+            .unwrap_or_default()
+    });
+    let name = columns.next()?;
+    let details = columns.next().unwrap_or_default();
+
+    let mut details = details.split(" • ").map(str::trim).collect::<Vec<_>>();
+    let duration = details.last().and_then(|last| {
+        last.contains(':')
+            .then(|| parse_length_text(last))
+            .flatten()
+    });
+    if duration.is_some() {
+        details.pop();
+    }
+
+    let artists = details.first().copied().unwrap_or_default().to_string();
+    let album = details.get(1).map(|album| album.to_string());
+    let handle = match artists.is_empty() {
+        true => name.clone(),
+        false => format!("{} - {}", name, artists),
+    };
+
+    Some(PlaylistItem {
+        id: PlaylistItemId::YouTube(video_id),
+        kind: PlaylistItemKind::Track,
+        name,
+        artists,
+        handle,
+        duration,
+        album,
+        isrc: None,
+        mbid: None,
+        canonical: None,
+    })
+}
+
+fn video_matches_duration(video: &YoutubeResponseVideoRenderer, duration: Duration) -> bool {
+    video
+        .length_text
+        .as_ref()
+        .and_then(|length_text| parse_length_text(&length_text.simple_text))
+        .is_some_and(|video_duration| durations_match(video_duration, duration))
+}
+
+fn parse_length_text(length_text: &str) -> Option<Duration> {
+    let mut seconds = 0;
+
+    for part in length_text.trim().split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
     }
 
-    return None;
+    Some(Duration::from_secs(seconds))
 }
 
 fn extract_yt_initial_data(input: &str) -> Option<String> {
@@ -323,7 +954,7 @@ fn extract_yt_initial_data(input: &str) -> Option<String> {
 
     if let Some(captured) = re.captures(input) {
         if let Some(matched_text) = captured.get(1) {
-            return Some(format!("{}}}", matched_text.as_str().to_string()));
+            return Some(format!("{}}}", matched_text.as_str()));
         }
     }
 
@@ -0,0 +1,227 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Path, Query},
+    http::HeaderMap,
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use linksen::{
+    PlaylistItemId, PlaylistOptions, PlaylistSink, PlaylistSource, Privacy, TrackSearcher,
+};
+
+/// Two pages, the second linked by a path relative to the API. Library
+/// songs carry "i." IDs and point at the catalog song in their play params.
+async fn tracks(
+    Path(path): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let library = path.starts_with("me/");
+    let (id, catalog_id) = match library {
+        true => ("i.first", Some("100")),
+        false => ("100", None),
+    };
+
+    match query.get("offset").map(String::as_str) {
+        Some("100") => common::page(
+            vec![
+                common::apple_music_song("i.second", "Aerodynamic", None),
+                // Songs no longer available come without attributes.
+                json!({ "id": "i.gone" }),
+            ],
+            None,
+        ),
+        _ => common::page(
+            vec![common::apple_music_song(id, "One More Time", catalog_id)],
+            Some(format!("/v1/{}?offset=100", path)),
+        ),
+    }
+}
+
+#[tokio::test]
+async fn reads_every_page_of_catalog_and_library_playlists() {
+    let router = Router::new().route("/v1/*path", get(tracks));
+    let apple_music = common::apple_music(&common::serve(router));
+
+    let catalog = apple_music
+        .get_playlist_items("https://music.apple.com/gb/playlist/daft-punk/pl.abc")
+        .await
+        .unwrap();
+    let library = apple_music
+        .get_playlist_items("https://music.apple.com/library/playlist/p.xyz")
+        .await
+        .unwrap();
+
+    let ids = |items: &[linksen::PlaylistItem]| {
+        items.iter().map(|item| item.id.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        ids(&catalog),
+        [
+            PlaylistItemId::AppleMusic("100".into()),
+            PlaylistItemId::AppleMusic("i.second".into()),
+        ]
+    );
+    // The library song is remapped to its catalog song, the one without a
+    // catalog song keeps its library ID.
+    assert_eq!(
+        ids(&library),
+        [
+            PlaylistItemId::AppleMusic("100".into()),
+            PlaylistItemId::AppleMusic("i.second".into()),
+        ]
+    );
+    assert_eq!(catalog[0].album.as_deref(), Some("Discovery"));
+    assert_eq!(catalog[0].isrc.as_deref(), Some("GBDUW0000053"));
+}
+
+#[tokio::test]
+async fn matches_by_isrc_then_by_search() {
+    let requests = Arc::new(Mutex::new(vec![]));
+
+    let router = Router::new()
+        .route(
+            "/v1/catalog/us/songs",
+            get({
+                let requests = requests.clone();
+                move |Query(query): Query<HashMap<String, String>>| async move {
+                    let isrc = query["filter[isrc]"].clone();
+                    requests.lock().unwrap().push(format!("isrc {}", isrc));
+
+                    match isrc.as_str() {
+                        "GBDUW0000053" => {
+                            common::page(vec![common::apple_music_song("200", "One More Time", None)], None)
+                        }
+                        _ => common::page(vec![], None),
+                    }
+                }
+            }),
+        )
+        .route(
+            "/v1/catalog/us/search",
+            get({
+                let requests = requests.clone();
+                move |Query(query): Query<HashMap<String, String>>| async move {
+                    requests
+                        .lock()
+                        .unwrap()
+                        .push(format!("search {}", query["term"]));
+
+                    Json(json!({
+                        "results": { "songs": { "data": [common::apple_music_song("201", "Digital Love", None)] } },
+                    }))
+                }
+            }),
+        );
+    let apple_music = common::apple_music(&common::serve(router));
+
+    let mut by_isrc = common::track(
+        PlaylistItemId::Spotify("a".into()),
+        "One More Time",
+        "Daft Punk",
+    );
+    by_isrc.isrc = Some("GBDUW0000053".into());
+    let mut unknown_isrc = common::track(
+        PlaylistItemId::Spotify("b".into()),
+        "Digital Love",
+        "Daft Punk",
+    );
+    unknown_isrc.isrc = Some("GBDUW0000059".into());
+
+    let parsed = apple_music
+        .parse_playlist_items(vec![by_isrc, unknown_isrc])
+        .await
+        .unwrap();
+    let targets = parsed
+        .items
+        .iter()
+        .map(|item_match| item_match.target.as_ref().unwrap().id.clone())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        targets,
+        [
+            PlaylistItemId::AppleMusic("200".into()),
+            PlaylistItemId::AppleMusic("201".into()),
+        ]
+    );
+    assert_eq!(
+        apple_music.search("daft punk").await.unwrap(),
+        Some("201".into())
+    );
+    assert_eq!(
+        *requests.lock().unwrap(),
+        [
+            "isrc GBDUW0000053",
+            "isrc GBDUW0000059",
+            "search Daft Punk Digital Love",
+            "search daft punk",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn creates_library_playlists_and_adds_items() {
+    let requests = Arc::new(Mutex::new(vec![]));
+
+    let record = |response: Value| {
+        let requests = requests.clone();
+        move |headers: HeaderMap, Json(body): Json<Value>| async move {
+            assert_eq!(headers["authorization"], "Bearer developer");
+            assert_eq!(headers["music-user-token"], "user");
+
+            requests.lock().unwrap().push(body);
+            Json(response)
+        }
+    };
+    let router = Router::new()
+        .route(
+            "/v1/me/library/playlists",
+            post(record(json!({ "data": [{ "id": "p.new" }] }))),
+        )
+        .route(
+            "/v1/me/library/playlists/p.new/tracks",
+            post(record(Value::Null)),
+        );
+    let apple_music = common::apple_music(&common::serve(router));
+
+    let items = [
+        common::track(
+            PlaylistItemId::AppleMusic("100".into()),
+            "One More Time",
+            "Daft Punk",
+        ),
+        common::track(
+            PlaylistItemId::AppleMusic("i.second".into()),
+            "Aerodynamic",
+            "Daft Punk",
+        ),
+    ];
+    let options = PlaylistOptions {
+        name: "Discovery".into(),
+        description: Some("2001".into()),
+        privacy: Privacy::Public,
+    };
+    let playlist_id = apple_music.create_playlist(&options, &items).await.unwrap();
+
+    assert_eq!(playlist_id, "p.new");
+    assert_eq!(
+        *requests.lock().unwrap(),
+        [
+            json!({ "attributes": { "name": "Discovery", "description": "2001" } }),
+            json!({
+                "data": [
+                    { "id": "100", "type": "songs" },
+                    { "id": "i.second", "type": "library-songs" },
+                ],
+            }),
+        ]
+    );
+}
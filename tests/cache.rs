@@ -0,0 +1,147 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use linksen::{
+    CachedSearcher, CanonicalTrack, ItemMatch, MatchCache, MusicClient, ParsedPlaylistItems,
+    PlaylistItem, PlaylistItemId, Result, SearchPreference, TrackSearcher,
+};
+
+/// Matches every item with a track named after it, and counts the searches.
+#[derive(Clone, Default)]
+struct Searcher {
+    searched: Arc<Mutex<Vec<String>>>,
+}
+
+impl MusicClient for Searcher {}
+
+#[async_trait]
+impl TrackSearcher for Searcher {
+    async fn parse_playlist_items(
+        &self,
+        playlist_items: Vec<PlaylistItem>,
+    ) -> Result<ParsedPlaylistItems> {
+        let items = playlist_items
+            .into_iter()
+            .map(|source| {
+                self.searched.lock().unwrap().push(source.name.clone());
+
+                ItemMatch {
+                    target: Some(common::track(
+                        PlaylistItemId::Deezer(source.name.clone()),
+                        &source.name,
+                        &source.artists,
+                    )),
+                    source,
+                    confidence: 1.0,
+                }
+            })
+            .collect();
+
+        Ok(ParsedPlaylistItems { items })
+    }
+
+    async fn search(&self, _query: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+fn cached_searcher(
+    searcher: &Searcher,
+    cache: &Arc<MatchCache>,
+    preference: Option<SearchPreference>,
+) -> CachedSearcher {
+    let mut cached_searcher =
+        CachedSearcher::new(Box::new(searcher.clone()), "deezer", cache.clone());
+    cached_searcher.set_preference(preference);
+
+    cached_searcher
+}
+
+fn targets(parsed: &ParsedPlaylistItems) -> Vec<PlaylistItemId> {
+    parsed
+        .items
+        .iter()
+        .map(|item_match| item_match.target.as_ref().unwrap().id.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn does_not_take_local_items_for_one_another() {
+    let searcher = Searcher::default();
+    let cache = Arc::new(MatchCache::new());
+
+    // Rows of a playlist file without a location.
+    let items = vec![
+        common::track(
+            PlaylistItemId::Local("".into()),
+            "One More Time",
+            "Daft Punk",
+        ),
+        common::track(PlaylistItemId::Local("".into()), "Aerodynamic", "Daft Punk"),
+    ];
+
+    for _ in 0..2 {
+        let parsed = cached_searcher(&searcher, &cache, None)
+            .parse_playlist_items(items.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            targets(&parsed),
+            [
+                PlaylistItemId::Deezer("One More Time".into()),
+                PlaylistItemId::Deezer("Aerodynamic".into()),
+            ]
+        );
+    }
+
+    assert_eq!(searcher.searched.lock().unwrap().len(), 4);
+    assert!(cache.matches(None).is_empty());
+}
+
+#[tokio::test]
+async fn keeps_matches_of_other_preferences_and_enriched_items_apart() {
+    let searcher = Searcher::default();
+    let cache = Arc::new(MatchCache::new());
+
+    let item = common::track(
+        PlaylistItemId::Spotify("a".into()),
+        "One More Time",
+        "Daft Punk",
+    );
+    let enriched = PlaylistItem {
+        canonical: Some(CanonicalTrack {
+            title: "One More Time".into(),
+            artist_credit: "Daft Punk".into(),
+            release: None,
+            release_mbid: None,
+        }),
+        ..item.clone()
+    };
+
+    for _ in 0..2 {
+        for preference in [None, Some(SearchPreference::Songs)] {
+            cached_searcher(&searcher, &cache, preference)
+                .parse_playlist_items(vec![item.clone(), enriched.clone()])
+                .await
+                .unwrap();
+        }
+    }
+
+    // Searched once for each preference and enrichment, then taken from the
+    // cache.
+    assert_eq!(searcher.searched.lock().unwrap().len(), 4);
+
+    let variants = cache
+        .matches(Some("deezer"))
+        .into_iter()
+        .map(|cached| cached.variant)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        variants,
+        ["", "enriched", "prefer=songs", "prefer=songs,enriched"]
+    );
+}
@@ -0,0 +1,115 @@
+// Every test binary includes this module but only uses part of it.
+#![allow(dead_code)]
+
+use std::{
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
+
+use axum::{Json, Router};
+use serde_json::{json, Value};
+
+use linksen::{AppleMusic, Deezer, PlaylistItem, PlaylistItemId, PlaylistItemKind, SoundCloud};
+
+/// Serves the router on a free local port in the background and returns its
+/// base URL, for standing in for the APIs of a service.
+pub fn serve(router: Router) -> String {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service()),
+    );
+
+    format!("http://{}", address)
+}
+
+pub fn track(id: PlaylistItemId, name: &str, artists: &str) -> PlaylistItem {
+    PlaylistItem {
+        id,
+        kind: PlaylistItemKind::Track,
+        name: name.to_string(),
+        artists: artists.to_string(),
+        handle: format!("{} - {}", name, artists),
+        duration: None,
+        album: None,
+        isrc: None,
+        mbid: None,
+        canonical: None,
+    }
+}
+
+pub fn timed_track(id: PlaylistItemId, name: &str, artists: &str, millis: u64) -> PlaylistItem {
+    PlaylistItem {
+        duration: Some(Duration::from_millis(millis)),
+        ..track(id, name, artists)
+    }
+}
+
+pub fn deezer(url: &str) -> Deezer {
+    let mut deezer = Deezer::new();
+    deezer.set_base_urls(url.to_string(), url.to_string());
+    deezer.set_access_token("token".into());
+
+    deezer
+}
+
+pub fn apple_music(url: &str) -> AppleMusic {
+    let mut apple_music = AppleMusic::with_tokens("developer".into(), Some("user".into()));
+    apple_music.set_storefront("us".into());
+    apple_music.set_base_url(url.to_string());
+
+    apple_music
+}
+
+pub fn soundcloud(url: &str) -> SoundCloud {
+    let mut soundcloud = SoundCloud::new();
+    soundcloud.set_base_urls(url.to_string(), url.to_string());
+    soundcloud.set_access_token("token".into());
+
+    soundcloud
+}
+
+/// A page of results the way Deezer and Apple Music list them, linking to
+/// the next one if there is any.
+pub fn page(data: Vec<Value>, next: Option<String>) -> Json<Value> {
+    match next {
+        Some(next) => Json(json!({ "data": data, "next": next })),
+        None => Json(json!({ "data": data })),
+    }
+}
+
+pub fn deezer_track(id: u64, title: &str, album: Option<&str>) -> Value {
+    let mut track = json!({
+        "id": id,
+        "title": title,
+        "duration": 320,
+        "isrc": format!("ISRC{}", id),
+        "artist": { "name": "Daft Punk" },
+    });
+    if let Some(album) = album {
+        track["album"] = json!({ "title": album });
+    }
+
+    track
+}
+
+pub fn apple_music_song(id: &str, name: &str, catalog_id: Option<&str>) -> Value {
+    let mut song = json!({
+        "id": id,
+        "attributes": {
+            "name": name,
+            "artistName": "Daft Punk",
+            "albumName": "Discovery",
+            "durationInMillis": 320357,
+            "isrc": "GBDUW0000053",
+        },
+    });
+    if let Some(catalog_id) = catalog_id {
+        song["attributes"]["playParams"] = json!({ "catalogId": catalog_id });
+    }
+
+    song
+}
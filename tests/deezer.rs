@@ -0,0 +1,443 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Host, Path, Query},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use linksen::{
+    CanonicalTrack, Event, PlaylistItem, PlaylistItemId, PlaylistOptions, PlaylistSink,
+    PlaylistSource, Privacy, RecordingReporter, TrackSearcher,
+};
+
+type Requests = Arc<Mutex<Vec<(String, HashMap<String, String>)>>>;
+
+/// Two pages of one track each, linked like Deezer links them.
+async fn tracks(
+    Host(host): Host,
+    Path((kind, id)): Path<(String, u64)>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let album = (kind == "playlist").then_some("Discovery");
+
+    match query.get("index").map(String::as_str) {
+        Some("1") => common::page(
+            vec![common::deezer_track(id + 1, "Aerodynamic", album)],
+            None,
+        ),
+        _ => common::page(
+            vec![common::deezer_track(id, "One More Time", album)],
+            Some(format!(
+                "http://{}/{}/{}/tracks?limit=100&index=1",
+                host, kind, id
+            )),
+        ),
+    }
+}
+
+#[tokio::test]
+async fn reads_every_page_of_playlists_and_albums() {
+    let router = Router::new().route("/:kind/:id/tracks", get(tracks)).route(
+        "/album/:id",
+        get(|| async { Json(json!({ "title": "Homework" })) }),
+    );
+    let deezer = common::deezer(&common::serve(router));
+
+    let playlist = deezer
+        .get_playlist_items("https://www.deezer.com/en/playlist/10")
+        .await
+        .unwrap();
+    let album = deezer.get_playlist_items("album:20").await.unwrap();
+
+    let names = |items: &[PlaylistItem]| {
+        items
+            .iter()
+            .map(|item| (item.id.clone(), item.album.clone().unwrap()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(&playlist),
+        [
+            (PlaylistItemId::Deezer("10".into()), "Discovery".into()),
+            (PlaylistItemId::Deezer("11".into()), "Discovery".into()),
+        ]
+    );
+    // Album tracks take the album's title.
+    assert_eq!(
+        names(&album),
+        [
+            (PlaylistItemId::Deezer("20".into()), "Homework".into()),
+            (PlaylistItemId::Deezer("21".into()), "Homework".into()),
+        ]
+    );
+    assert_eq!(playlist[0].isrc.as_deref(), Some("ISRC10"));
+}
+
+#[tokio::test]
+async fn matches_by_isrc_then_by_search() {
+    let searches: Requests = Default::default();
+
+    let router = Router::new()
+        .route(
+            "/track/:id",
+            get(|Path(id): Path<String>| async move {
+                match id.as_str() {
+                    "isrc:GBDUW0000053" => Json(common::deezer_track(30, "One More Time", None)),
+                    // Deezer answers unknown ISRCs with an error body and 200.
+                    _ => Json(json!({
+                        "error": { "type": "DataException", "message": "no data", "code": 800 },
+                    })),
+                }
+            }),
+        )
+        .route(
+            "/search/track",
+            get({
+                let searches = searches.clone();
+                move |Query(query): Query<HashMap<String, String>>| async move {
+                    searches.lock().unwrap().push(("search".into(), query));
+                    common::page(vec![common::deezer_track(31, "Digital Love", None)], None)
+                }
+            }),
+        );
+    let deezer = common::deezer(&common::serve(router));
+
+    let mut by_isrc = common::track(
+        PlaylistItemId::Spotify("a".into()),
+        "One More Time",
+        "Daft Punk",
+    );
+    by_isrc.isrc = Some("GBDUW0000053".into());
+    let mut unknown_isrc = common::track(
+        PlaylistItemId::Spotify("b".into()),
+        "Digital Love",
+        "Daft Punk",
+    );
+    unknown_isrc.isrc = Some("GBDUW0000059".into());
+
+    let parsed = deezer
+        .parse_playlist_items(vec![by_isrc, unknown_isrc])
+        .await
+        .unwrap();
+
+    let targets = parsed
+        .items
+        .iter()
+        .map(|item_match| item_match.target.as_ref().unwrap().id.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        targets,
+        [
+            PlaylistItemId::Deezer("30".into()),
+            PlaylistItemId::Deezer("31".into()),
+        ]
+    );
+
+    assert_eq!(
+        deezer.search("daft punk digital love").await.unwrap(),
+        Some("31".into())
+    );
+
+    // The unknown ISRC is searched for by name, then the free search.
+    let searches = searches.lock().unwrap();
+    assert_eq!(searches.len(), 2);
+    assert_eq!(
+        searches[0].1["q"],
+        "track:\"Digital Love\" artist:\"Daft Punk\""
+    );
+    assert_eq!(searches[1].1["q"], "daft punk digital love");
+}
+
+#[tokio::test]
+async fn does_not_search_by_name_when_isrc_lookups_fail() {
+    let searches: Requests = Default::default();
+
+    let router = Router::new()
+        .route(
+            "/track/:id",
+            get(|| async {
+                Json(json!({
+                    "error": { "type": "Exception", "message": "Quota limit exceeded", "code": 4 },
+                }))
+            }),
+        )
+        .route(
+            "/search/track",
+            get({
+                let searches = searches.clone();
+                move |Query(query): Query<HashMap<String, String>>| async move {
+                    searches.lock().unwrap().push(("search".into(), query));
+                    common::page(vec![common::deezer_track(31, "Digital Love", None)], None)
+                }
+            }),
+        );
+    let mut deezer = common::deezer(&common::serve(router));
+    let reporter = Arc::new(RecordingReporter::new());
+    deezer.set_reporter(reporter.clone());
+
+    let mut item = common::track(
+        PlaylistItemId::Spotify("a".into()),
+        "Digital Love",
+        "Daft Punk",
+    );
+    item.isrc = Some("GBDUW0000059".into());
+    let parsed = deezer.parse_playlist_items(vec![item]).await.unwrap();
+
+    assert!(parsed.items[0].target.is_none());
+    assert!(searches.lock().unwrap().is_empty());
+    assert!(reporter.events().iter().any(|event| matches!(
+        event,
+        Event::ItemFailed { error, .. } if error.contains("Quota limit exceeded")
+    )));
+}
+
+#[tokio::test]
+async fn keeps_the_matches_of_other_items_when_a_search_fails() {
+    let router = Router::new().route(
+        "/search/track",
+        get(|Query(query): Query<HashMap<String, String>>| async move {
+            match query["q"].contains("Aerodynamic") {
+                true => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                false => Ok(common::page(
+                    vec![common::deezer_track(30, "One More Time", None)],
+                    None,
+                )),
+            }
+        }),
+    );
+    let mut deezer = common::deezer(&common::serve(router));
+    let reporter = Arc::new(RecordingReporter::new());
+    deezer.set_reporter(reporter.clone());
+
+    let parsed = deezer
+        .parse_playlist_items(vec![
+            common::track(
+                PlaylistItemId::Spotify("a".into()),
+                "Aerodynamic",
+                "Daft Punk",
+            ),
+            common::track(
+                PlaylistItemId::Spotify("b".into()),
+                "One More Time",
+                "Daft Punk",
+            ),
+        ])
+        .await
+        .unwrap();
+
+    assert!(parsed.items[0].target.is_none());
+    assert_eq!(
+        parsed.items[1].target.as_ref().map(|target| &target.id),
+        Some(&PlaylistItemId::Deezer("30".into()))
+    );
+
+    let failed = reporter
+        .events()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::ItemFailed { item, error } => Some((item.name, error)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].0, "Aerodynamic");
+    assert!(failed[0].1.starts_with("search failed"));
+}
+
+#[tokio::test]
+async fn creates_playlists_and_adds_and_removes_items() {
+    let requests: Requests = Default::default();
+
+    let record = |name: &'static str, response: Value| {
+        let requests = requests.clone();
+        move |Query(query): Query<HashMap<String, String>>| async move {
+            requests.lock().unwrap().push((name.into(), query));
+            Json(response)
+        }
+    };
+    let router = Router::new()
+        .route(
+            "/user/me/playlists",
+            post(record("create", json!({ "id": 5 }))),
+        )
+        .route("/playlist/5", post(record("settings", json!(true))))
+        .route(
+            "/playlist/5/tracks",
+            post(record("add", json!(true))).delete(record("remove", json!(true))),
+        );
+    let deezer = common::deezer(&common::serve(router));
+
+    let items = [
+        common::track(
+            PlaylistItemId::Deezer("1".into()),
+            "One More Time",
+            "Daft Punk",
+        ),
+        common::track(
+            PlaylistItemId::Deezer("2".into()),
+            "Aerodynamic",
+            "Daft Punk",
+        ),
+    ];
+    let options = PlaylistOptions {
+        name: "Discovery".into(),
+        description: Some("2001".into()),
+        privacy: Privacy::Public,
+    };
+    let playlist_id = deezer.create_playlist(&options, &items).await.unwrap();
+    deezer
+        .remove_items(&playlist_id, &items[1..])
+        .await
+        .unwrap();
+
+    assert_eq!(playlist_id, "5");
+
+    let requests = requests.lock().unwrap();
+    let names = requests
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["create", "settings", "add", "remove"]);
+    assert_eq!(requests[0].1["title"], "Discovery");
+    assert_eq!(requests[1].1["public"], "true");
+    assert_eq!(requests[1].1["description"], "2001");
+    assert_eq!(requests[2].1["songs"], "1,2");
+    assert_eq!(requests[3].1["songs"], "2");
+    assert!(requests
+        .iter()
+        .all(|(_, query)| query["access_token"] == "token"));
+}
+
+#[tokio::test]
+async fn searches_enriched_items_by_their_canonical_names() {
+    let searches: Requests = Default::default();
+
+    let router = Router::new().route(
+        "/search/track",
+        get({
+            let searches = searches.clone();
+            move |Query(query): Query<HashMap<String, String>>| async move {
+                searches.lock().unwrap().push(("search".into(), query));
+                common::page(vec![common::deezer_track(30, "One More Time", None)], None)
+            }
+        }),
+    );
+    let deezer = common::deezer(&common::serve(router));
+
+    let enriched = PlaylistItem {
+        canonical: Some(CanonicalTrack {
+            title: "One More Time".into(),
+            artist_credit: "Daft Punk".into(),
+            release: None,
+            release_mbid: None,
+        }),
+        ..common::track(
+            PlaylistItemId::YouTube("FGBhQbmPwH8".into()),
+            "Official Video 2000 HD",
+            "DaftPunkVEVO",
+        )
+    };
+    let parsed = deezer.parse_playlist_items(vec![enriched]).await.unwrap();
+
+    assert_eq!(
+        searches.lock().unwrap()[0].1["q"],
+        "track:\"One More Time\" artist:\"Daft Punk\""
+    );
+    assert_eq!(parsed.items[0].confidence, 1.0);
+}
+
+#[tokio::test]
+async fn falls_back_to_the_credited_handle() {
+    let searches: Requests = Default::default();
+
+    let router = Router::new().route(
+        "/search/track",
+        get({
+            let searches = searches.clone();
+            move |Query(query): Query<HashMap<String, String>>| async move {
+                let data = match query["q"].starts_with("track:") {
+                    true => json!([]),
+                    false => json!([common::deezer_track(30, "One More Time", None)]),
+                };
+                searches.lock().unwrap().push(("search".into(), query));
+                Json(json!({ "data": data }))
+            }
+        }),
+    );
+    let deezer = common::deezer(&common::serve(router));
+
+    let enriched = PlaylistItem {
+        canonical: Some(CanonicalTrack {
+            title: "One More Time".into(),
+            artist_credit: "Daft Punk".into(),
+            release: None,
+            release_mbid: None,
+        }),
+        ..common::track(
+            PlaylistItemId::YouTube("FGBhQbmPwH8".into()),
+            "Official Video 2000 HD",
+            "DaftPunkVEVO",
+        )
+    };
+    deezer.parse_playlist_items(vec![enriched]).await.unwrap();
+
+    let searches = searches.lock().unwrap();
+    assert_eq!(searches.len(), 2);
+    assert_eq!(searches[1].1["q"], "One More Time - Daft Punk");
+}
+
+#[tokio::test]
+async fn reads_descriptions_only_when_asked() {
+    let requests: Requests = Default::default();
+
+    let router = Router::new()
+        .route("/user/me", get(|| async { Json(json!({ "id": 7 })) }))
+        .route(
+            "/user/7/playlists",
+            get(|| async {
+                Json(json!({
+                    "data": [
+                        { "id": 10, "title": "Discovery", "nb_tracks": 14, "creator": { "id": 7 } },
+                        { "id": 11, "title": "Homework", "nb_tracks": 16, "creator": { "id": 8 } },
+                    ],
+                }))
+            }),
+        )
+        .route(
+            "/playlist/:id",
+            get({
+                let requests = requests.clone();
+                move |Path(id): Path<String>, Query(query): Query<HashMap<String, String>>| async move {
+                    requests.lock().unwrap().push((id, query));
+                    Json(json!({ "id": 10, "title": "Discovery", "description": "2001" }))
+                }
+            }),
+        );
+    let deezer = common::deezer(&common::serve(router));
+
+    let playlists = deezer.get_playlists(None).await.unwrap();
+
+    assert_eq!(playlists.len(), 2);
+    assert!(playlists[0].owned);
+    assert!(!playlists[1].owned);
+    assert!(playlists
+        .iter()
+        .all(|playlist| playlist.description.is_none()));
+    assert!(requests.lock().unwrap().is_empty());
+
+    let description = deezer.get_description(&playlists[0].url).await.unwrap();
+
+    assert_eq!(description.as_deref(), Some("2001"));
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, "10");
+    assert_eq!(requests[0].1["access_token"], "token");
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::Query,
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use linksen::{
+    Deezer, Event, FallbackSearcher, PlaylistItemId, PlaylistSink, RecordingReporter, SoundCloud,
+    TrackSearcher,
+};
+
+/// Deezer only knows "One More Time", SoundCloud has everything.
+fn clients() -> (Deezer, SoundCloud) {
+    let deezer_router = Router::new().route(
+        "/search/track",
+        get(|Query(query): Query<HashMap<String, String>>| async move {
+            let data = match query["q"].contains("One More Time") {
+                true => vec![common::deezer_track(1, "One More Time", None)],
+                false => vec![],
+            };
+
+            common::page(data, None)
+        }),
+    );
+    let soundcloud_router = Router::new().route(
+        "/tracks",
+        get(|| async {
+            Json(json!({
+                "collection": [{
+                    "title": "Essential Mix 1997",
+                    "permalink_url": "https://soundcloud.com/daftpunk/essential-mix-1997",
+                    "user": { "username": "Daft Punk" },
+                }],
+            }))
+        }),
+    );
+
+    (
+        common::deezer(&common::serve(deezer_router)),
+        common::soundcloud(&common::serve(soundcloud_router)),
+    )
+}
+
+#[tokio::test]
+async fn searches_the_fallback_for_what_is_missing() {
+    let (deezer, soundcloud) = clients();
+
+    assert_eq!(
+        soundcloud.search("essential mix").await.unwrap(),
+        Some("daftpunk/essential-mix-1997".into())
+    );
+
+    let searcher = FallbackSearcher::new(Box::new(deezer), Box::new(soundcloud));
+    let parsed = searcher
+        .parse_playlist_items(vec![
+            common::track(
+                PlaylistItemId::Spotify("a".into()),
+                "One More Time",
+                "Daft Punk",
+            ),
+            common::track(
+                PlaylistItemId::Spotify("b".into()),
+                "Essential Mix 1997",
+                "Daft Punk",
+            ),
+        ])
+        .await
+        .unwrap();
+
+    let targets = parsed
+        .items
+        .iter()
+        .map(|item_match| item_match.target.as_ref().unwrap().id.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        targets,
+        [
+            PlaylistItemId::Deezer("1".into()),
+            PlaylistItemId::SoundCloud("daftpunk/essential-mix-1997".into()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn sinks_report_items_of_other_providers_as_failed() {
+    let router = Router::new().route(
+        "/playlist/5/tracks",
+        post(|| async { Json(Value::Bool(true)) }),
+    );
+    let reporter = Arc::new(RecordingReporter::new());
+    let mut deezer = common::deezer(&common::serve(router));
+    deezer.set_reporter(reporter.clone());
+
+    let items = [
+        common::track(
+            PlaylistItemId::Deezer("1".into()),
+            "One More Time",
+            "Daft Punk",
+        ),
+        common::track(
+            PlaylistItemId::SoundCloud("daftpunk/essential-mix-1997".into()),
+            "Essential Mix 1997",
+            "Daft Punk",
+        ),
+    ];
+    deezer.add_items("5", &items).await.unwrap();
+
+    let events = reporter
+        .events()
+        .into_iter()
+        .map(|event| match event {
+            Event::ItemInserted { item } => format!("inserted {}", item.name),
+            Event::ItemFailed { item, error } => format!("failed {}: {}", item.name, error),
+            event => format!("{:?}", event),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            "failed Essential Mix 1997: not on Deezer",
+            "inserted One More Time",
+        ]
+    );
+}
@@ -0,0 +1,212 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{extract::Query, routing::get, Json, Router};
+use serde_json::{json, Value};
+
+use linksen::{Error, Lastfm, PlaylistItemId, PlaylistSource};
+
+type Requests = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+fn track(name: &str, artist: Value) -> Value {
+    json!({
+        "name": name,
+        "mbid": "",
+        "url": format!("https://www.last.fm/music/Daft+Punk/_/{}", name.replace(' ', "+")),
+        "artist": artist,
+    })
+}
+
+/// Answers every method the way Last.fm does, including a chart of a single
+/// track given as an object rather than an array, and errors in the body.
+fn respond(query: &HashMap<String, String>) -> Value {
+    let page = query.get("page").map_or("1", String::as_str);
+
+    match query["method"].as_str() {
+        "user.getLovedTracks" => json!({
+            "lovedtracks": {
+                "track": track("One More Time", json!({ "name": "Daft Punk" })),
+                "@attr": { "totalPages": "1" },
+            },
+        }),
+        "user.getTopTracks" => {
+            let mut track = track(&format!("Top {}", page), json!({ "name": "Daft Punk" }));
+            track["duration"] = json!("320");
+
+            json!({ "toptracks": { "track": [track], "@attr": { "totalPages": "2" } } })
+        }
+        "user.getRecentTracks" => {
+            let mut now_playing = track("Aerodynamic", json!({ "#text": "Daft Punk" }));
+            now_playing["@attr"] = json!({ "nowplaying": "true" });
+            let mut recent = track("Digital Love", json!({ "#text": "Daft Punk" }));
+            recent["album"] = json!({ "#text": "Discovery" });
+
+            json!({
+                "recenttracks": {
+                    "track": [
+                        now_playing,
+                        recent,
+                        recent,
+                        track("Unknown", json!({ "#text": "Daft Punk" })),
+                    ],
+                    "@attr": { "totalPages": "1" },
+                },
+            })
+        }
+        "track.getInfo" if query.get("track").map(String::as_str) == Some("Unknown") => {
+            json!({ "error": 6, "message": "Track not found" })
+        }
+        "track.getInfo" => json!({
+            "track": {
+                "name": query["track"],
+                "url": "https://www.last.fm/music/Daft+Punk/_/Digital+Love",
+                "artist": { "name": "Daft Punk" },
+                "mbid": "8e9f3d05",
+                "duration": "301000",
+                "album": { "title": "Discovery (Deluxe)" },
+            },
+        }),
+        _ => json!({ "error": 3, "message": "Invalid Method" }),
+    }
+}
+
+fn lastfm() -> (Lastfm, Requests) {
+    let requests: Requests = Default::default();
+
+    let router = Router::new().route(
+        "/",
+        get({
+            let requests = requests.clone();
+            move |Query(query): Query<HashMap<String, String>>| async move {
+                let response = respond(&query);
+                requests.lock().unwrap().push(query);
+
+                Json(response)
+            }
+        }),
+    );
+
+    let mut lastfm = Lastfm::with_api_key("key".into());
+    lastfm.set_base_url(format!("{}/", common::serve(router)));
+
+    (lastfm, requests)
+}
+
+#[tokio::test]
+async fn reads_charts_from_every_url_form() {
+    let (lastfm, requests) = lastfm();
+
+    let charts = [
+        (
+            "https://www.last.fm/user/rj/loved",
+            "user.getLovedTracks",
+            None,
+        ),
+        ("rj/top", "user.getTopTracks", Some("overall")),
+        ("rj/top/7day", "user.getTopTracks", Some("7day")),
+        (
+            "https://www.last.fm/user/rj/library/tracks?date_preset=LAST_90_DAYS",
+            "user.getTopTracks",
+            Some("3month"),
+        ),
+        (
+            "https://www.last.fm/user/rj/library",
+            "user.getRecentTracks",
+            None,
+        ),
+    ];
+
+    for (url, method, period) in charts {
+        lastfm.get_playlist_items(url).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        let chart = requests
+            .iter()
+            .rev()
+            .find(|query| query["method"] != "track.getInfo")
+            .unwrap();
+        assert_eq!(chart["method"], method, "{}", url);
+        assert_eq!(chart["user"], "rj", "{}", url);
+        assert_eq!(chart.get("period").map(String::as_str), period, "{}", url);
+    }
+
+    for url in [
+        "rj/top/fortnight",
+        "rj/friends",
+        "https://www.last.fm/user/rj",
+    ] {
+        assert!(matches!(
+            lastfm.get_playlist_items(url).await,
+            Err(Error::InvalidUrl(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn reads_single_track_charts_and_completes_them() {
+    let (lastfm, requests) = lastfm();
+
+    let items = lastfm.get_playlist_items("rj/loved").await.unwrap();
+
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0].id,
+        PlaylistItemId::Lastfm("Daft+Punk/_/One+More+Time".into())
+    );
+    // Loved tracks have no duration, which track.getInfo fills in.
+    assert_eq!(items[0].duration, Some(Duration::from_millis(301000)));
+    assert_eq!(items[0].mbid.as_deref(), Some("8e9f3d05"));
+    assert_eq!(items[0].album.as_deref(), Some("Discovery (Deluxe)"));
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests[1]["method"], "track.getInfo");
+    assert_eq!(requests[1]["artist"], "Daft Punk");
+    assert_eq!(requests[1]["track"], "One More Time");
+}
+
+#[tokio::test]
+async fn pages_top_tracks_up_to_the_limit() {
+    let (mut lastfm, requests) = lastfm();
+
+    let items = lastfm.get_playlist_items("rj/top").await.unwrap();
+    let names = items
+        .iter()
+        .map(|item| item.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Top 1", "Top 2"]);
+    // Top tracks have a duration of their own and need no completion.
+    assert_eq!(items[0].duration, Some(Duration::from_secs(320)));
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    lastfm.set_limit(1);
+    let items = lastfm.get_playlist_items("rj/top").await.unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(requests.lock().unwrap()[2]["limit"], "1");
+}
+
+#[tokio::test]
+async fn skips_now_playing_and_repeated_scrobbles() {
+    let (lastfm, _) = lastfm();
+
+    let items = lastfm.get_playlist_items("rj/recent").await.unwrap();
+
+    let names = items
+        .iter()
+        .map(|item| (item.name.as_str(), item.artists.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [("Digital Love", "Daft Punk"), ("Unknown", "Daft Punk")]
+    );
+    // The scrobbled album is kept, and a track Last.fm cannot complete is
+    // kept as it was.
+    assert_eq!(items[0].album.as_deref(), Some("Discovery"));
+    assert_eq!(items[0].duration, Some(Duration::from_millis(301000)));
+    assert_eq!(items[1].duration, None);
+    assert_eq!(items[1].mbid, None);
+}
@@ -0,0 +1,126 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use linksen::{LocalLibrary, PlaylistItemId, PlaylistSource};
+
+fn library(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("linksen-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    directory
+}
+
+/// A FLAC stream without audio frames: a STREAMINFO block for two minutes at
+/// 44.1 kHz followed by the given Vorbis comments.
+fn empty_flac(comments: &[&str]) -> Vec<u8> {
+    let sample_rate: u64 = 44_100;
+    let samples = sample_rate * 120;
+
+    let mut info = vec![0x10, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0, 0];
+    // Sample rate (20 bits), channels - 1 (3 bits), bits per sample - 1 (5
+    // bits) and total samples (36 bits).
+    let packed = (sample_rate << 44) | (1 << 41) | (15 << 36) | samples;
+    info.extend_from_slice(&packed.to_be_bytes());
+    info.extend_from_slice(&[0; 16]);
+
+    let field = |bytes: &mut Vec<u8>, text: &str| {
+        bytes.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(text.as_bytes());
+    };
+    let mut vorbis = vec![];
+    field(&mut vorbis, "linksen");
+    vorbis.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        field(&mut vorbis, comment);
+    }
+
+    let mut flac = b"fLaC".to_vec();
+    flac.extend_from_slice(&[0, 0, 0, info.len() as u8]);
+    flac.extend_from_slice(&info);
+    flac.extend_from_slice(&[0x84, 0, 0, vorbis.len() as u8]);
+    flac.extend_from_slice(&vorbis);
+
+    flac
+}
+
+#[tokio::test]
+async fn reads_tags_and_duration() {
+    let directory = library("tags");
+    let path = directory.join("track.flac");
+    let flac = empty_flac(&[
+        "TITLE=One More Time",
+        "ARTIST=Daft Punk",
+        "ALBUM=Discovery",
+        "ISRC=GBDUW0000053",
+    ]);
+    fs::write(&path, flac).unwrap();
+
+    let items = LocalLibrary::new()
+        .get_playlist_items(directory.to_str().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].name, "One More Time");
+    assert_eq!(items[0].artists, "Daft Punk");
+    assert_eq!(items[0].album.as_deref(), Some("Discovery"));
+    assert_eq!(items[0].isrc.as_deref(), Some("GBDUW0000053"));
+    assert_eq!(items[0].duration, Some(Duration::from_secs(120)));
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn falls_back_to_file_names_for_broken_files() {
+    let directory = library("broken");
+
+    // An MP4 atom claiming to be 4 GiB long.
+    let mut mp4 = vec![0xff, 0xff, 0xff, 0xf0];
+    mp4.extend_from_slice(b"ftypM4A ");
+    fs::write(directory.join("huge atom.m4a"), mp4).unwrap();
+    fs::write(directory.join("garbage.flac"), b"not a flac file").unwrap();
+    fs::write(directory.join("empty.mp3"), b"").unwrap();
+    fs::write(directory.join("cover.jpg"), b"").unwrap();
+
+    let items = LocalLibrary::new()
+        .get_playlist_items(directory.to_str().unwrap())
+        .await
+        .unwrap();
+
+    let names = items
+        .iter()
+        .map(|item| item.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["empty", "garbage", "huge atom"]);
+    assert!(items
+        .iter()
+        .all(|item| item.artists.is_empty() && item.duration.is_none()));
+    assert!(matches!(&items[0].id, PlaylistItemId::Local(path) if path.ends_with("empty.mp3")));
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn reads_linked_directories_once() {
+    let directory = library("links");
+    let albums = directory.join("albums");
+    fs::create_dir(&albums).unwrap();
+    fs::write(
+        albums.join("track.flac"),
+        empty_flac(&["TITLE=Veridis Quo"]),
+    )
+    .unwrap();
+    std::os::unix::fs::symlink("..", albums.join("loop")).unwrap();
+    std::os::unix::fs::symlink(&albums, directory.join("favourites")).unwrap();
+
+    let items = LocalLibrary::new()
+        .get_playlist_items(directory.to_str().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].name, "Veridis Quo");
+
+    fs::remove_dir_all(directory).unwrap();
+}
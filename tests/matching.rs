@@ -0,0 +1,37 @@
+mod common;
+
+use std::time::Duration;
+
+use linksen::{matching::confidence, CanonicalTrack, PlaylistItem, PlaylistItemId};
+
+#[test]
+fn enriched_items_are_compared_the_way_musicbrainz_credits_them() {
+    let source = PlaylistItem {
+        duration: Some(Duration::from_secs(322)),
+        ..common::track(
+            PlaylistItemId::YouTube("FGBhQbmPwH8".into()),
+            "Official Video 2000 HD",
+            "DaftPunkVEVO",
+        )
+    };
+    let target = PlaylistItem {
+        duration: Some(Duration::from_secs(320)),
+        ..common::track(
+            PlaylistItemId::Deezer("3135553".into()),
+            "One More Time",
+            "Daft Punk",
+        )
+    };
+    let enriched = PlaylistItem {
+        canonical: Some(CanonicalTrack {
+            title: "One More Time".into(),
+            artist_credit: "Daft Punk".into(),
+            release: Some("Discovery".into()),
+            release_mbid: None,
+        }),
+        ..source.clone()
+    };
+
+    assert!(confidence(&source, &target) < 0.5);
+    assert_eq!(confidence(&enriched, &target), 1.0);
+}
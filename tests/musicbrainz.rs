@@ -0,0 +1,175 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use linksen::{MusicBrainz, PlaylistItem, PlaylistItemId, PlaylistItemKind};
+
+type Requests = Arc<Mutex<Vec<String>>>;
+
+fn recording(id: &str, length: u64, score: u32) -> Value {
+    json!({
+        "id": id,
+        "title": "One More Time",
+        "length": length,
+        "score": score,
+        "artist-credit": [
+            { "name": "Daft Punk", "joinphrase": " feat. " },
+            { "name": "Romanthony" },
+        ],
+        "releases": [{ "id": "rel-1", "title": "Discovery" }],
+        "isrcs": ["GBDUW0000053"],
+    })
+}
+
+fn musicbrainz() -> (MusicBrainz, Requests) {
+    let requests: Requests = Default::default();
+
+    let router = Router::new()
+        .route(
+            "/recording/:mbid",
+            get(|Path(mbid): Path<String>| async move {
+                match mbid.as_str() {
+                    "known" => Json(recording("known", 320357, 100)).into_response(),
+                    "malformed" => StatusCode::BAD_REQUEST.into_response(),
+                    _ => StatusCode::NOT_FOUND.into_response(),
+                }
+            }),
+        )
+        .route(
+            "/isrc/:isrc",
+            get(|Path(isrc): Path<String>| async move {
+                match isrc.as_str() {
+                    "GBDUW0000053" => {
+                        Json(json!({ "recordings": [recording("by-isrc", 320357, 100)] }))
+                            .into_response()
+                    }
+                    _ => StatusCode::NOT_FOUND.into_response(),
+                }
+            }),
+        )
+        .route(
+            "/recording",
+            get({
+                let requests = requests.clone();
+                move |Query(query): Query<HashMap<String, String>>| async move {
+                    requests.lock().unwrap().push(query["query"].clone());
+
+                    // Sorted by score, like MusicBrainz does.
+                    Json(json!({
+                        "recordings": [
+                            recording("radio-edit", 240000, 100),
+                            recording("album", 320357, 95),
+                            recording("unlikely", 320357, 40),
+                        ],
+                    }))
+                }
+            }),
+        );
+
+    let mut musicbrainz = MusicBrainz::new();
+    musicbrainz.set_base_url(common::serve(router));
+    musicbrainz.set_request_interval(Duration::ZERO);
+
+    (musicbrainz, requests)
+}
+
+const ARTISTS: &str = "Daft Punk & Romanthony";
+
+fn video() -> PlaylistItemId {
+    PlaylistItemId::YouTube("v".into())
+}
+
+#[tokio::test]
+async fn finds_recordings_by_mbid_then_isrc() {
+    let (musicbrainz, requests) = musicbrainz();
+
+    let by_mbid = PlaylistItem {
+        mbid: Some("known".into()),
+        ..common::track(video(), "One More Time (Official Video)", ARTISTS)
+    };
+    let by_isrc = PlaylistItem {
+        // An MBID MusicBrainz rejects falls through to the ISRC.
+        mbid: Some("malformed".into()),
+        isrc: Some("GBDUW0000053".into()),
+        ..common::track(video(), "One More Time", ARTISTS)
+    };
+
+    let enriched = musicbrainz.enrich(vec![by_mbid, by_isrc]).await.unwrap();
+
+    assert_eq!(enriched[0].mbid.as_deref(), Some("known"));
+    assert_eq!(enriched[0].isrc.as_deref(), Some("GBDUW0000053"));
+    assert_eq!(enriched[0].duration, Some(Duration::from_millis(320357)));
+    let canonical = enriched[0].canonical.as_ref().unwrap();
+    assert_eq!(canonical.title, "One More Time");
+    assert_eq!(canonical.artist_credit, "Daft Punk feat. Romanthony");
+    assert_eq!(canonical.release.as_deref(), Some("Discovery"));
+    assert_eq!(canonical.release_mbid.as_deref(), Some("rel-1"));
+
+    assert_eq!(enriched[1].mbid.as_deref(), Some("by-isrc"));
+    assert!(requests.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn searches_for_the_recording_of_the_right_length() {
+    let (musicbrainz, requests) = musicbrainz();
+
+    let enriched = musicbrainz
+        .enrich(vec![
+            common::timed_track(video(), "One More Time", ARTISTS, 321000),
+            common::track(video(), "One More Time", ARTISTS),
+            // Nothing trustworthy is as long as this.
+            common::timed_track(video(), "One More Time", ARTISTS, 600000),
+        ])
+        .await
+        .unwrap();
+
+    let mbids = enriched
+        .iter()
+        .map(|item| item.mbid.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(mbids, [Some("album"), Some("radio-edit"), None]);
+    assert_eq!(enriched[2].canonical, None);
+
+    // Only the first of the credited artists is searched for.
+    assert_eq!(
+        requests.lock().unwrap()[0],
+        "recording:\"One More Time\" AND artist:\"Daft Punk\""
+    );
+}
+
+#[tokio::test]
+async fn leaves_episodes_and_unknown_items_as_they_are() {
+    let (musicbrainz, requests) = musicbrainz();
+
+    let episode = PlaylistItem {
+        kind: PlaylistItemKind::Episode,
+        ..common::track(video(), "Episode 1", ARTISTS)
+    };
+    let unknown = PlaylistItem {
+        mbid: Some("unknown".into()),
+        isrc: Some("XXXXX0000000".into()),
+        ..common::timed_track(video(), "Something (Live) [2024]", ARTISTS, 600000)
+    };
+
+    let enriched = musicbrainz.enrich(vec![episode, unknown]).await.unwrap();
+
+    assert!(enriched.iter().all(|item| item.canonical.is_none()));
+    assert_eq!(enriched[1].mbid.as_deref(), Some("unknown"));
+    assert_eq!(
+        *requests.lock().unwrap(),
+        ["recording:\"Something \\(Live\\) \\[2024\\]\" AND artist:\"Daft Punk\""]
+    );
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use std::time::Duration;
+
+use linksen::{
+    playlist_file::{read_playlist, write_playlist},
+    PlaylistFileFormat, PlaylistItem, PlaylistItemId,
+};
+
+fn playlist() -> Vec<PlaylistItem> {
+    vec![
+        PlaylistItem {
+            album: Some("Discovery".into()),
+            isrc: Some("GBDUW0000053".into()),
+            ..common::timed_track(
+                PlaylistItemId::Spotify("0DiWol3AO6WpXZgp0goxAV".into()),
+                "One More Time",
+                "Daft Punk",
+                320000,
+            )
+        },
+        common::track(
+            PlaylistItemId::Deezer("3135556".into()),
+            "Harder, \"Better\"\nFaster",
+            "Daft Punk",
+        ),
+    ]
+}
+
+fn round_trip(format: PlaylistFileFormat) -> Vec<PlaylistItem> {
+    let mut out = vec![];
+    write_playlist(format, &playlist(), &mut out).unwrap();
+
+    read_playlist(format, &String::from_utf8(out).unwrap()).unwrap()
+}
+
+#[test]
+fn csv_keeps_album_isrc_and_quoted_newlines() {
+    let items = round_trip(PlaylistFileFormat::Csv);
+
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].album.as_deref(), Some("Discovery"));
+    assert_eq!(items[0].isrc.as_deref(), Some("GBDUW0000053"));
+    assert_eq!(items[0].duration, Some(Duration::from_secs(320)));
+    assert_eq!(items[1].name, "Harder, \"Better\"\nFaster");
+    assert_eq!(items[1].id, PlaylistItemId::Deezer("3135556".into()));
+    assert_eq!(items[1].album, None);
+    assert_eq!(items[1].isrc, None);
+}
+
+#[test]
+fn xspf_keeps_album_and_isrc() {
+    let items = round_trip(PlaylistFileFormat::Xspf);
+
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].album.as_deref(), Some("Discovery"));
+    assert_eq!(items[0].isrc.as_deref(), Some("GBDUW0000053"));
+    assert_eq!(items[1].album, None);
+    assert_eq!(items[1].isrc, None);
+}
+
+#[test]
+fn m3u_keeps_album_and_isrc() {
+    let mut out = vec![];
+    write_playlist(PlaylistFileFormat::M3u, &playlist()[..1], &mut out).unwrap();
+    let items = read_playlist(PlaylistFileFormat::M3u, &String::from_utf8(out).unwrap()).unwrap();
+
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].name, "One More Time");
+    assert_eq!(items[0].artists, "Daft Punk");
+    assert_eq!(items[0].album.as_deref(), Some("Discovery"));
+    assert_eq!(items[0].isrc.as_deref(), Some("GBDUW0000053"));
+}
+
+#[test]
+fn csv_reads_exported_columns_of_other_tools() {
+    let contents = "\
+Track URI,Track Name,Artist Name(s),Album Name,ISRC,Track Duration (ms)
+spotify:track:0DiWol3AO6WpXZgp0goxAV,One More Time,Daft Punk,Discovery,GBDUW0000053,320357
+
+spotify:track:2VEZx7NWsZ1D0eJ4uv5Fym,Digital Love,Daft Punk
+";
+    let items = read_playlist(PlaylistFileFormat::Csv, contents).unwrap();
+
+    assert_eq!(items.len(), 2);
+    assert_eq!(
+        items[0].id,
+        PlaylistItemId::Spotify("0DiWol3AO6WpXZgp0goxAV".into())
+    );
+    assert_eq!(items[0].album.as_deref(), Some("Discovery"));
+    assert_eq!(items[0].isrc.as_deref(), Some("GBDUW0000053"));
+    assert_eq!(items[1].name, "Digital Love");
+    assert_eq!(items[1].album, None);
+}
+
+#[test]
+fn xspf_reads_attributes_namespaces_and_entities() {
+    let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<x:playlist version="1" xmlns:x="http://xspf.org/ns/0/">
+  <x:trackList>
+    <x:track id="1">
+      <x:location>https://open.spotify.com/track/0DiWol3AO6WpXZgp0goxAV</x:location>
+      <x:title>Caf&#233; &amp; Cr&#xE8;me</x:title>
+      <x:creator><![CDATA[Daft <Punk>]]></x:creator>
+      <x:extension application="https://example.com">
+        <x:title>Ignored</x:title>
+      </x:extension>
+      <x:identifier>https://musicbrainz.org/recording/1</x:identifier>
+      <x:identifier>urn:isrc:GBDUW0000053</x:identifier>
+    </x:track>
+  </x:trackList>
+</x:playlist>
+"#;
+    let items = read_playlist(PlaylistFileFormat::Xspf, contents).unwrap();
+
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        items[0].id,
+        PlaylistItemId::Spotify("0DiWol3AO6WpXZgp0goxAV".into())
+    );
+    assert_eq!(items[0].name, "Café & Crème");
+    assert_eq!(items[0].artists, "Daft <Punk>");
+    assert_eq!(items[0].isrc.as_deref(), Some("GBDUW0000053"));
+}
+
+#[cfg(unix)]
+#[test]
+fn xspf_writes_local_paths_as_file_uris() {
+    let path = "/music/Daft Punk/One More Time #1.flac";
+    let playlist = vec![common::track(
+        PlaylistItemId::Local(path.into()),
+        "One More Time",
+        "Daft Punk",
+    )];
+
+    let mut out = vec![];
+    write_playlist(PlaylistFileFormat::Xspf, &playlist, &mut out).unwrap();
+    let contents = String::from_utf8(out).unwrap();
+    let items = read_playlist(PlaylistFileFormat::Xspf, &contents).unwrap();
+
+    assert!(contents
+        .contains("<location>file:///music/Daft%20Punk/One%20More%20Time%20%231.flac</location>"));
+    assert_eq!(items[0].id, PlaylistItemId::Local(path.into()));
+}
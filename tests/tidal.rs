@@ -0,0 +1,88 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Form, Path},
+    http::{header::ETAG, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use linksen::{Event, PlaylistItemId, PlaylistSink, RecordingReporter, Tidal};
+
+#[tokio::test]
+async fn add_items_skips_other_services_as_failed() {
+    let added = Arc::new(Mutex::new(vec![]));
+
+    let router = Router::new()
+        .route(
+            "/playlists/:id",
+            get(|| async { ([(ETAG, "\"1\"")], Json(json!({}))) }),
+        )
+        .route(
+            "/playlists/:id/tracks",
+            get(|| async { Json(json!({ "items": [], "totalNumberOfItems": 0 })) }),
+        )
+        .route(
+            "/playlists/:id/items",
+            post({
+                let added = added.clone();
+                move |Path(id): Path<String>, Form(form): Form<HashMap<String, String>>| async move {
+                    added.lock().unwrap().push((id, form));
+                    (StatusCode::OK, Json(Value::Null))
+                }
+            }),
+        );
+    let url = common::serve(router);
+
+    let reporter = Arc::new(RecordingReporter::new());
+    let mut tidal = Tidal::with_credentials("client".into(), None);
+    tidal.set_base_urls(url.clone(), url.clone(), url);
+    tidal.set_access_token("token".into(), 1);
+    tidal.set_reporter(reporter.clone());
+
+    let items = [
+        common::track(
+            PlaylistItemId::Tidal("1".into()),
+            "One More Time",
+            "Daft Punk",
+        ),
+        common::track(
+            PlaylistItemId::SoundCloud("2".into()),
+            "Aerodynamic",
+            "Daft Punk",
+        ),
+        common::track(
+            PlaylistItemId::Tidal("3".into()),
+            "Digital Love",
+            "Daft Punk",
+        ),
+    ];
+    tidal.add_items("playlist", &items).await.unwrap();
+
+    let added = added.lock().unwrap();
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0].0, "playlist");
+    assert_eq!(added[0].1["trackIds"], "1,3");
+    assert_eq!(added[0].1["toIndex"], "0");
+
+    let events = reporter.events();
+    let failed = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::ItemFailed { item, .. } => Some(item.name.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let inserted = events
+        .iter()
+        .filter(|event| matches!(event, Event::ItemInserted { .. }))
+        .count();
+    assert_eq!(failed, ["Aerodynamic"]);
+    assert_eq!(inserted, 2);
+}